@@ -0,0 +1,12 @@
+CREATE TABLE samples (
+  id TEXT PRIMARY KEY NOT NULL,
+  backend TEXT NOT NULL,
+  key TEXT NOT NULL
+);
+
+INSERT INTO samples (id, backend, key) VALUES
+  ('sample-bam', 'local', 'bam/htsnexus_test_NA12878'),
+  ('sample-cram', 'local', 'cram/htsnexus_test_NA12878'),
+  ('sample-vcf', 'local', 'vcf/sample1-bcbio-cancer'),
+  ('sample-bcf', 'local', 'bcf/sample1-bcbio-cancer'),
+  ('sample-unknown-backend', 'unknown', 'bam/htsnexus_test_NA12878');
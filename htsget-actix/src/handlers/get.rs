@@ -51,3 +51,47 @@ pub async fn variants<H: HtsGet + Clone + Send + Sync + 'static>(
     .await,
   )
 }
+
+/// GET request sequences endpoint
+#[instrument(skip(app_state))]
+pub async fn sequences<H: HtsGet + Clone + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  path: Path<String>,
+  http_request: HttpRequest,
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  let request = extract_request(request, path, http_request);
+
+  info!(request = ?request, "sequences endpoint GET request");
+
+  handle_response(
+    get(
+      app_state.get_ref().htsget.clone(),
+      request,
+      Endpoint::Sequences,
+    )
+    .await,
+  )
+}
+
+/// GET request annotations endpoint
+#[instrument(skip(app_state))]
+pub async fn annotations<H: HtsGet + Clone + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  path: Path<String>,
+  http_request: HttpRequest,
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  let request = extract_request(request, path, http_request);
+
+  info!(request = ?request, "annotations endpoint GET request");
+
+  handle_response(
+    get(
+      app_state.get_ref().htsget.clone(),
+      request,
+      Endpoint::Annotations,
+    )
+    .await,
+  )
+}
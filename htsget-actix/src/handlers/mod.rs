@@ -5,14 +5,17 @@ use actix_web::{http::StatusCode, Either, HttpRequest, Responder};
 use http::{HeaderMap as HttpHeaderMap, HeaderName, Method};
 
 use htsget_config::types::{JsonResponse, Request};
-use htsget_http::Result;
+use htsget_http::{remove_authorizer_headers, Result};
 use pretty_json::PrettyJson;
 
+pub use crate::handlers::openapi::openapi;
 pub use crate::handlers::service_info::{
-  get_service_info_json, reads_service_info, variants_service_info,
+  annotations_service_info, get_service_info_json, reads_service_info, sequences_service_info,
+  variants_service_info,
 };
 
 pub mod get;
+pub mod openapi;
 pub mod post;
 pub mod service_info;
 
@@ -53,12 +56,11 @@ fn extract_request(
   http_request: HttpRequest,
 ) -> Request {
   let query = request.into_inner();
+  let mut headers =
+    HttpVersionCompat::header_map_0_2_to_1(HeaderMap::from(&http_request).into_inner());
+  remove_authorizer_headers(&mut headers);
 
-  Request::new(
-    path.into_inner(),
-    query,
-    HttpVersionCompat::header_map_0_2_to_1(HeaderMap::from(&http_request).into_inner()),
-  )
+  Request::new(path.into_inner(), query, headers)
 }
 
 // Todo, remove this when actix-web starts using http 1.0.
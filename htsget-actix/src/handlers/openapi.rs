@@ -0,0 +1,17 @@
+use actix_web::web::Data;
+use actix_web::Responder;
+use tracing::instrument;
+
+use htsget_http::openapi_json;
+use htsget_search::HtsGet;
+
+use crate::handlers::pretty_json::PrettyJson;
+use crate::AppState;
+
+/// Gets the OpenAPI document describing the ticket server.
+#[instrument(skip(app_state))]
+pub async fn openapi<H: HtsGet + Clone + Send + Sync + 'static>(
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  PrettyJson(openapi_json(&app_state.config_service_info))
+}
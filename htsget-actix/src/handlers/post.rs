@@ -63,3 +63,51 @@ pub async fn variants<H: HtsGet + Clone + Send + Sync + 'static>(
     .await,
   )
 }
+
+/// POST request sequences endpoint
+#[instrument(skip(app_state))]
+pub async fn sequences<H: HtsGet + Clone + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  body: Json<PostRequest>,
+  path: Path<String>,
+  http_request: HttpRequest,
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  let request = extract_request(request, path, http_request);
+
+  info!(body = ?body, "sequences endpoint POST request");
+
+  handle_response(
+    post(
+      app_state.get_ref().htsget.clone(),
+      body.into_inner(),
+      request,
+      Endpoint::Sequences,
+    )
+    .await,
+  )
+}
+
+/// POST request annotations endpoint
+#[instrument(skip(app_state))]
+pub async fn annotations<H: HtsGet + Clone + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  body: Json<PostRequest>,
+  path: Path<String>,
+  http_request: HttpRequest,
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  let request = extract_request(request, path, http_request);
+
+  info!(body = ?body, "annotations endpoint POST request");
+
+  handle_response(
+    post(
+      app_state.get_ref().htsget.clone(),
+      body.into_inner(),
+      request,
+      Endpoint::Annotations,
+    )
+    .await,
+  )
+}
@@ -38,3 +38,17 @@ pub async fn variants_service_info<H: HtsGet + Clone + Send + Sync + 'static>(
 ) -> impl Responder {
   get_service_info_json(app_state.get_ref(), Endpoint::Variants)
 }
+
+/// Gets the JSON to return for the sequences service-info endpoint
+pub async fn sequences_service_info<H: HtsGet + Clone + Send + Sync + 'static>(
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  get_service_info_json(app_state.get_ref(), Endpoint::Sequences)
+}
+
+/// Gets the JSON to return for the annotations service-info endpoint
+pub async fn annotations_service_info<H: HtsGet + Clone + Send + Sync + 'static>(
+  app_state: Data<AppState<H>>,
+) -> impl Responder {
+  get_service_info_json(app_state.get_ref(), Endpoint::Annotations)
+}
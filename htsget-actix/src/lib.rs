@@ -1,4 +1,7 @@
+use std::path::Path;
+
 use actix_cors::Cors;
+use actix_files::Files;
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpServer};
 use tracing::info;
@@ -6,12 +9,18 @@ use tracing::instrument;
 use tracing_actix_web::TracingLogger;
 
 use htsget_config::config::advanced::cors::CorsConfig;
+use htsget_config::config::data_server::DataServerConfig;
 use htsget_config::config::service_info::ServiceInfo;
+use htsget_config::config::services::Services;
 use htsget_config::config::ticket_server::TicketServerConfig;
 pub use htsget_config::config::{Config, USAGE};
+use htsget_http::OPENAPI_PATH;
 use htsget_search::HtsGet;
 
-use crate::handlers::{get, post, reads_service_info, variants_service_info, HttpVersionCompat};
+use crate::handlers::{
+  annotations_service_info, get, openapi, post, reads_service_info, sequences_service_info,
+  variants_service_info, HttpVersionCompat,
+};
 
 pub mod handlers;
 
@@ -45,7 +54,47 @@ pub fn configure_server<H: HtsGet + Clone + Send + Sync + 'static>(
         .route("/service-info", web::post().to(variants_service_info::<H>))
         .route("/{id:.+}", web::get().to(get::variants::<H>))
         .route("/{id:.+}", web::post().to(post::variants::<H>)),
+    )
+    .service(
+      web::scope("/sequences")
+        .route("/service-info", web::get().to(sequences_service_info::<H>))
+        .route("/service-info", web::post().to(sequences_service_info::<H>))
+        .route("/{id:.+}", web::get().to(get::sequences::<H>))
+        .route("/{id:.+}", web::post().to(post::sequences::<H>)),
+    )
+    .service(
+      web::scope("/annotations")
+        .route(
+          "/service-info",
+          web::get().to(annotations_service_info::<H>),
+        )
+        .route(
+          "/service-info",
+          web::post().to(annotations_service_info::<H>),
+        )
+        .route("/{id:.+}", web::get().to(get::annotations::<H>))
+        .route("/{id:.+}", web::post().to(post::annotations::<H>)),
+    )
+    .route(OPENAPI_PATH, web::get().to(openapi::<H>));
+}
+
+/// Configure the services mounted under path prefixes. Services use the top-level service info
+/// and cors config if they don't set their own.
+pub fn configure_services(
+  service_config: &mut web::ServiceConfig,
+  services: Services,
+  config_service_info: &ServiceInfo,
+  cors: &CorsConfig,
+) {
+  for service in services.into_inner() {
+    let (prefix, locations, service_info, cors) = service.into_inner_or(config_service_info, cors);
+
+    service_config.service(
+      web::scope(&prefix)
+        .configure(|service_config| configure_server(service_config, locations, service_info))
+        .wrap(configure_cors(cors)),
     );
+  }
 }
 
 /// Configure cors, settings allowed methods, max age, allowed origins, and if credentials
@@ -106,12 +155,14 @@ pub fn configure_cors(cors: CorsConfig) -> Cors {
   cors_layer.max_age(cors.max_age())
 }
 
-/// Run the server using a http-actix `HttpServer`.
+/// Run the server using a http-actix `HttpServer`. Services are mounted under their prefixes,
+/// and the top-level service is mounted at the root.
 #[instrument(skip_all)]
 pub fn run_server<H: HtsGet + Clone + Send + Sync + 'static>(
   htsget: H,
   config: TicketServerConfig,
   service_info: ServiceInfo,
+  services: Services,
 ) -> std::io::Result<Server> {
   let addr = config.addr();
 
@@ -119,9 +170,20 @@ pub fn run_server<H: HtsGet + Clone + Send + Sync + 'static>(
   let server = HttpServer::new(Box::new(move || {
     App::new()
       .configure(|service_config: &mut web::ServiceConfig| {
-        configure_server(service_config, htsget.clone(), service_info.clone());
+        configure_services(
+          service_config,
+          services.clone(),
+          &service_info,
+          config_copy.cors(),
+        );
       })
-      .wrap(configure_cors(config_copy.cors().clone()))
+      .service(
+        web::scope("")
+          .configure(|service_config: &mut web::ServiceConfig| {
+            configure_server(service_config, htsget.clone(), service_info.clone());
+          })
+          .wrap(configure_cors(config_copy.cors().clone())),
+      )
       .wrap(TracingLogger::default())
   }));
 
@@ -140,6 +202,51 @@ pub fn run_server<H: HtsGet + Clone + Send + Sync + 'static>(
   Ok(server.run())
 }
 
+/// Configure the data server, which serves the files under the path. Range requests are supported.
+pub fn configure_data_server<P: AsRef<Path>>(service_config: &mut web::ServiceConfig, path: P) {
+  service_config.service(Files::new("/", path.as_ref()));
+}
+
+/// Run the data server using a http-actix `HttpServer`, serving files from the local path of the
+/// config. Decrypting Crypt4GH files is not supported, so this returns an error if it is configured.
+#[instrument(skip_all)]
+pub fn run_data_server(config: DataServerConfig) -> std::io::Result<Server> {
+  #[cfg(feature = "experimental")]
+  if config.decrypt().is_some() {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::Unsupported,
+      "decrypting Crypt4GH files with `data_server.decrypt` is not supported by the actix data server",
+    ));
+  }
+
+  let addr = config.addr();
+  let path = config.local_path().to_path_buf();
+  let cors = config.cors().clone();
+
+  let server = HttpServer::new(Box::new(move || {
+    App::new()
+      .configure(|service_config: &mut web::ServiceConfig| {
+        configure_data_server(service_config, &path);
+      })
+      .wrap(configure_cors(cors.clone()))
+      .wrap(TracingLogger::default())
+  }));
+
+  let server = match config.into_tls() {
+    None => {
+      info!("using non-TLS data server");
+      server.bind(addr)?
+    }
+    Some(tls) => {
+      info!("using TLS data server");
+      server.bind_rustls_0_23(addr, tls.into_inner())?
+    }
+  };
+
+  info!(addresses = ?server.addrs(), "htsget data server addresses bound");
+  Ok(server.run())
+}
+
 #[cfg(test)]
 mod tests {
   use std::path::Path;
@@ -151,14 +258,22 @@ mod tests {
   use rustls::crypto::aws_lc_rs;
   use tempfile::TempDir;
 
-  use htsget_axum::server::BindServer;
+  #[cfg(feature = "experimental")]
+  use htsget_config::config::data_server::DataServerDecrypt;
+  #[cfg(feature = "experimental")]
+  use htsget_config::storage::c4gh::local::C4GHLocal;
   use htsget_config::types::JsonResponse;
+  use htsget_test::http::data::DATA_SERVER_TEST_FILE;
   use htsget_test::http::server::expected_url_path;
-  use htsget_test::http::{config_with_tls, default_test_config};
-  use htsget_test::http::{cors, server};
+  use htsget_test::http::{
+    config_with_tls, default_test_config, default_test_config_with_services,
+  };
+  use htsget_test::http::{cors, data, server};
   use htsget_test::http::{
     Header as TestHeader, Response as TestResponse, TestRequest, TestServer,
   };
+  #[cfg(feature = "experimental")]
+  use htsget_test::util::default_dir;
 
   use crate::Config;
 
@@ -168,6 +283,10 @@ mod tests {
     config: Config,
   }
 
+  struct ActixDataTestServer {
+    config: Config,
+  }
+
   struct ActixTestRequest<T>(T);
 
   impl TestRequest for ActixTestRequest<test::TestRequest> {
@@ -221,14 +340,10 @@ mod tests {
         .as_data_server_config()
         .unwrap();
 
-      let path = data_server.local_path().to_path_buf();
-      let mut bind_data_server = BindServer::from(data_server.clone());
-      let server = bind_data_server.bind_data_server().await.unwrap();
-      let addr = server.local_addr();
-
-      tokio::spawn(async move { server.serve(path).await.unwrap() });
+      let addr = data_server.addr();
+      actix_web::rt::spawn(run_data_server(data_server.clone()).unwrap());
 
-      expected_url_path(self.get_config(), addr.unwrap())
+      expected_url_path(self.get_config(), addr)
     }
 
     fn get_config(&self) -> &Config {
@@ -264,6 +379,62 @@ mod tests {
     }
   }
 
+  impl Default for ActixDataTestServer {
+    fn default() -> Self {
+      Self {
+        config: default_test_config(),
+      }
+    }
+  }
+
+  #[async_trait(?Send)]
+  impl TestServer<ActixTestRequest<test::TestRequest>> for ActixDataTestServer {
+    async fn get_expected_path(&self) -> String {
+      "".to_string()
+    }
+
+    fn get_config(&self) -> &Config {
+      &self.config
+    }
+
+    fn request(&self) -> ActixTestRequest<test::TestRequest> {
+      ActixTestRequest(test::TestRequest::default())
+    }
+
+    async fn test_server(
+      &self,
+      request: ActixTestRequest<test::TestRequest>,
+      expected_path: String,
+    ) -> TestResponse {
+      let data_server = self.config.data_server().as_data_server_config().unwrap();
+      let app = test::init_service(
+        App::new()
+          .configure(|service_config: &mut web::ServiceConfig| {
+            configure_data_server(service_config, data_server.local_path());
+          })
+          .wrap(configure_cors(data_server.cors().clone())),
+      )
+      .await;
+
+      let response = request.0.send_request(&app).await;
+      let status: u16 = response.status().into();
+      let mut headers = response.headers().clone();
+      let bytes = test::read_body(response).await.to_vec();
+
+      TestResponse::new(
+        status,
+        HttpVersionCompat::header_map_0_2_to_1(
+          headers
+            .drain()
+            .map(|(name, value)| (name.unwrap(), value))
+            .collect(),
+        ),
+        bytes,
+        expected_path,
+      )
+    }
+  }
+
   impl ActixTestServer {
     fn new_with_tls<P: AsRef<Path>>(path: P) -> Self {
       let _ = aws_lc_rs::default_provider().install_default();
@@ -273,6 +444,12 @@ mod tests {
       }
     }
 
+    fn new_with_services() -> Self {
+      Self {
+        config: default_test_config_with_services(),
+      }
+    }
+
     async fn get_response(
       &self,
       request: test::TestRequest,
@@ -280,13 +457,24 @@ mod tests {
       let app = test::init_service(
         App::new()
           .configure(|service_config: &mut web::ServiceConfig| {
-            configure_server(
+            configure_services(
               service_config,
-              self.config.clone().into_locations(),
-              self.config.service_info().clone(),
+              self.config.services().clone(),
+              self.config.service_info(),
+              self.config.ticket_server().cors(),
             );
           })
-          .wrap(configure_cors(self.config.ticket_server().cors().clone())),
+          .service(
+            web::scope("")
+              .configure(|service_config: &mut web::ServiceConfig| {
+                configure_server(
+                  service_config,
+                  self.config.clone().into_locations(),
+                  self.config.service_info().clone(),
+                );
+              })
+              .wrap(configure_cors(self.config.ticket_server().cors().clone())),
+          ),
       )
       .await;
 
@@ -325,6 +513,16 @@ mod tests {
     server::test_service_info(&ActixTestServer::default()).await;
   }
 
+  #[actix_web::test]
+  async fn services_http_tickets() {
+    server::test_services::<JsonResponse, _>(&ActixTestServer::new_with_services()).await;
+  }
+
+  #[actix_web::test]
+  async fn openapi() {
+    server::test_openapi(&ActixTestServer::default()).await;
+  }
+
   #[actix_web::test]
   async fn get_https_tickets() {
     let base_path = TempDir::new().unwrap();
@@ -373,4 +571,48 @@ mod tests {
   async fn cors_preflight_request() {
     cors::test_cors_preflight_request(&ActixTestServer::default()).await;
   }
+
+  #[actix_web::test]
+  async fn data_server_file() {
+    data::test_data_server_file_uri(&ActixDataTestServer::default(), "").await;
+  }
+
+  #[actix_web::test]
+  async fn data_server_range() {
+    data::test_data_server_range_uri(&ActixDataTestServer::default(), "").await;
+  }
+
+  #[actix_web::test]
+  async fn data_server_missing_file() {
+    data::test_data_server_missing_file_uri(&ActixDataTestServer::default(), "").await;
+  }
+
+  #[actix_web::test]
+  async fn data_server_cors_simple_request() {
+    cors::test_cors_simple_request_uri(
+      &ActixDataTestServer::default(),
+      &format!("/{DATA_SERVER_TEST_FILE}"),
+    )
+    .await;
+  }
+
+  #[cfg(feature = "experimental")]
+  #[actix_web::test]
+  async fn data_server_decrypt_unsupported() {
+    let keys = default_dir().join("data/c4gh/keys");
+    let keys = C4GHLocal::new(keys.join("bob.sec"), keys.join("alice.pub"))
+      .try_into()
+      .unwrap();
+    let mut data_server = default_test_config()
+      .data_server()
+      .as_data_server_config()
+      .unwrap()
+      .clone();
+    data_server.set_decrypt(Some(DataServerDecrypt::new(
+      keys,
+      vec!["127.0.0.1".parse().unwrap()],
+    )));
+
+    assert!(run_data_server(data_server).is_err());
+  }
 }
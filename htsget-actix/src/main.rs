@@ -3,9 +3,7 @@ use std::io;
 use tokio::select;
 use tracing::debug;
 
-use htsget_actix::run_server;
-use htsget_actix::Config;
-use htsget_axum::server::data;
+use htsget_actix::{run_data_server, run_server, Config};
 use htsget_config::config::data_server::DataServerEnabled;
 use htsget_config::{command, package_info};
 
@@ -20,30 +18,38 @@ async fn main() -> io::Result<()> {
 
     config.setup_tracing()?;
 
-    let service_info = config.service_info_mut();
-    service_info.set_from_package_info(package_info!())?;
+    config.set_from_package_info(package_info!())?;
 
     debug!(config = ?config, "config parsed");
 
     if let DataServerEnabled::Some(data_server) = config.data_server() {
-      let local_server = data::join_handle(data_server.clone()).await?;
+      let data_server = run_data_server(data_server.clone())?;
 
       let ticket_server_config = config.ticket_server().clone();
       let service_info = config.service_info().clone();
+      let services = config.services().clone();
 
       select! {
-        local_server = local_server => Ok(local_server??),
+        data_server = data_server => data_server,
         actix_server = run_server(
           config.into_locations(),
           ticket_server_config,
-          service_info
+          service_info,
+          services
         )? => actix_server
       }
     } else {
       let ticket_server_config = config.ticket_server().clone();
       let service_info = config.service_info().clone();
-
-      run_server(config.into_locations(), ticket_server_config, service_info)?.await
+      let services = config.services().clone();
+
+      run_server(
+        config.into_locations(),
+        ticket_server_config,
+        service_info,
+        services,
+      )?
+      .await
     }
   } else {
     Ok(())
@@ -35,3 +35,27 @@ pub async fn variants<H: HtsGet + Send + Sync + 'static>(
 
   handle_response(get(app_state.htsget, request, Endpoint::Variants).await)
 }
+
+/// GET request sequences endpoint.
+pub async fn sequences<H: HtsGet + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  path: Path<String>,
+  headers: HeaderMap,
+  State(app_state): State<AppState<H>>,
+) -> impl IntoResponse {
+  let request = extract_request(request, path, headers);
+
+  handle_response(get(app_state.htsget, request, Endpoint::Sequences).await)
+}
+
+/// GET request annotations endpoint.
+pub async fn annotations<H: HtsGet + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  path: Path<String>,
+  headers: HeaderMap,
+  State(app_state): State<AppState<H>>,
+) -> impl IntoResponse {
+  let request = extract_request(request, path, headers);
+
+  handle_response(get(app_state.htsget, request, Endpoint::Annotations).await)
+}
@@ -7,11 +7,14 @@ use http::{HeaderMap, StatusCode};
 
 use htsget_config::types::{JsonResponse, Request};
 
+pub use crate::handlers::openapi::openapi;
 pub use crate::handlers::service_info::{
-  get_service_info_json, reads_service_info, variants_service_info,
+  annotations_service_info, get_service_info_json, reads_service_info, sequences_service_info,
+  variants_service_info,
 };
 
 pub mod get;
+pub mod openapi;
 pub mod post;
 pub mod service_info;
 
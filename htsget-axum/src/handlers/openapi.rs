@@ -0,0 +1,15 @@
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum_extra::response::ErasedJson;
+
+use htsget_http::openapi_json;
+use htsget_search::HtsGet;
+
+use crate::server::AppState;
+
+/// Gets the OpenAPI document describing the ticket server.
+pub async fn openapi<H: HtsGet + Send + Sync + 'static>(
+  State(app_state): State<AppState<H>>,
+) -> impl IntoResponse {
+  ErasedJson::pretty(openapi_json(&app_state.service_info))
+}
@@ -38,3 +38,29 @@ pub async fn variants<H: HtsGet + Clone + Send + Sync + 'static>(
 
   handle_response(post(app_state.htsget, body, request, Endpoint::Variants).await)
 }
+
+/// POST request sequences endpoint.
+pub async fn sequences<H: HtsGet + Clone + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  path: Path<String>,
+  headers: HeaderMap,
+  State(app_state): State<AppState<H>>,
+  Json(body): Json<PostRequest>,
+) -> impl IntoResponse {
+  let request = extract_request(request, path, headers);
+
+  handle_response(post(app_state.htsget, body, request, Endpoint::Sequences).await)
+}
+
+/// POST request annotations endpoint.
+pub async fn annotations<H: HtsGet + Clone + Send + Sync + 'static>(
+  request: Query<HashMap<String, String>>,
+  path: Path<String>,
+  headers: HeaderMap,
+  State(app_state): State<AppState<H>>,
+  Json(body): Json<PostRequest>,
+) -> impl IntoResponse {
+  let request = extract_request(request, path, headers);
+
+  handle_response(post(app_state.htsget, body, request, Endpoint::Annotations).await)
+}
@@ -33,3 +33,17 @@ pub async fn variants_service_info<H: HtsGet + Send + Sync + 'static>(
 ) -> impl IntoResponse {
   get_service_info_json(app_state, Endpoint::Variants)
 }
+
+/// Gets the JSON to return for the sequences service-info endpoint
+pub async fn sequences_service_info<H: HtsGet + Send + Sync + 'static>(
+  State(app_state): State<AppState<H>>,
+) -> impl IntoResponse {
+  get_service_info_json(app_state, Endpoint::Sequences)
+}
+
+/// Gets the JSON to return for the annotations service-info endpoint
+pub async fn annotations_service_info<H: HtsGet + Send + Sync + 'static>(
+  State(app_state): State<AppState<H>>,
+) -> impl IntoResponse {
+  get_service_info_json(app_state, Endpoint::Annotations)
+}
@@ -21,8 +21,7 @@ async fn main() -> io::Result<()> {
 
     config.setup_tracing()?;
 
-    let service_info = config.service_info_mut();
-    service_info.set_from_package_info(package_info!())?;
+    config.set_from_package_info(package_info!())?;
 
     debug!(config = ?config, "config parsed");
 
@@ -1,41 +1,107 @@
 //! The axum data server.
 //!
 
+#[cfg(feature = "experimental")]
+use crate::error::Error::{self, ServerError};
 use crate::error::Result;
+#[cfg(feature = "experimental")]
+use crate::server::range::ByteRange;
 use crate::server::{configure_cors, BindServer, Server};
+#[cfg(feature = "experimental")]
+use axum::body::Body;
+#[cfg(feature = "experimental")]
+use axum::extract::{ConnectInfo, Request, State};
+#[cfg(feature = "experimental")]
+use axum::middleware::{from_fn_with_state, Next};
+#[cfg(feature = "experimental")]
+use axum::response::{IntoResponse, Response};
 use axum::Router;
+#[cfg(feature = "experimental")]
+use futures::stream;
 use htsget_config::config::advanced::cors::CorsConfig;
 use htsget_config::config::data_server::DataServerConfig;
+#[cfg(feature = "experimental")]
+use htsget_config::config::data_server::DataServerDecrypt;
+#[cfg(feature = "experimental")]
+use htsget_storage::c4gh::{
+  to_unencrypted_file_size, unencrypted_to_data_block, unencrypted_to_next_data_block,
+  DecryptedData, DeserializedHeader, ENCRYPTED_BLOCK_SIZE,
+};
+#[cfg(feature = "experimental")]
+use http::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+#[cfg(feature = "experimental")]
+use http::{HeaderMap, Method, StatusCode};
+#[cfg(feature = "experimental")]
+use std::cmp::min;
+#[cfg(feature = "experimental")]
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::net::SocketAddr;
 use std::path::Path;
+#[cfg(feature = "experimental")]
+use std::path::PathBuf;
+#[cfg(feature = "experimental")]
+use std::sync::Arc;
+#[cfg(feature = "experimental")]
+use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 use tracing::info;
+#[cfg(feature = "experimental")]
+use tracing::{trace, warn};
 
 /// An data block server.
 #[derive(Debug)]
 pub struct DataServer {
   server: Server,
   cors: CorsConfig,
+  #[cfg(feature = "experimental")]
+  decrypt: Option<DataServerDecrypt>,
 }
 
 impl DataServer {
   /// Create a new data server.
   pub fn new(server: Server, cors: CorsConfig) -> Self {
-    Self { server, cors }
+    Self {
+      server,
+      cors,
+      #[cfg(feature = "experimental")]
+      decrypt: None,
+    }
+  }
+
+  /// Decrypt C4GH objects for trusted clients.
+  #[cfg(feature = "experimental")]
+  pub fn with_decrypt(mut self, decrypt: Option<DataServerDecrypt>) -> Self {
+    self.decrypt = decrypt;
+    self
   }
 
   /// Run the data server, using the provided path, key and certificate.
   pub async fn serve<P: AsRef<Path>>(self, path: P) -> Result<()> {
-    self.server.serve(Self::router(self.cors, path)).await
+    let router = self.router(path);
+    self.server.serve(router).await
   }
 
-  /// Create the router for the data server.
-  pub fn router<P: AsRef<Path>>(cors: CorsConfig, path: P) -> Router {
-    Router::new()
-      .nest_service("/", ServeDir::new(path))
-      .layer(configure_cors(cors))
+  /// Create the router for the data server. If decryption is configured, C4GH objects are
+  /// decrypted for trusted clients.
+  pub fn router<P: AsRef<Path>>(&self, path: P) -> Router {
+    let router = Router::new().nest_service("/", ServeDir::new(&path));
+
+    #[cfg(feature = "experimental")]
+    let router = match &self.decrypt {
+      Some(decrypt) => router.layer(from_fn_with_state(
+        Arc::new(DecryptState::new(
+          decrypt.clone(),
+          path.as_ref().to_path_buf(),
+        )),
+        decrypt_c4gh,
+      )),
+      None => router,
+    };
+
+    router
+      .layer(configure_cors(self.cors.clone()))
       .layer(TraceLayer::new_for_http())
   }
 
@@ -45,6 +111,254 @@ impl DataServer {
   }
 }
 
+/// The number of C4GH data blocks that are decrypted at a time when streaming plaintext.
+#[cfg(feature = "experimental")]
+const STREAM_BLOCKS: u64 = 16;
+
+/// State for decrypting C4GH objects in the data server.
+#[cfg(feature = "experimental")]
+#[derive(Debug)]
+struct DecryptState {
+  decrypt: DataServerDecrypt,
+  path: PathBuf,
+}
+
+#[cfg(feature = "experimental")]
+impl DecryptState {
+  fn new(decrypt: DataServerDecrypt, path: PathBuf) -> Self {
+    Self { decrypt, path }
+  }
+
+  /// Get the path of the encrypted object for a request path, if the plaintext object does not
+  /// exist and the encrypted object does.
+  async fn encrypted_path(&self, request_path: &str) -> Option<PathBuf> {
+    let key = request_path.trim_start_matches('/');
+    if key.is_empty()
+      || key
+        .split('/')
+        .any(|segment| segment == ".." || segment.contains('\\'))
+    {
+      return None;
+    }
+
+    let plaintext = self.path.join(key);
+    if tokio::fs::try_exists(&plaintext).await.unwrap_or(true) {
+      return None;
+    }
+
+    let encrypted = self.path.join(format!("{key}.c4gh"));
+    tokio::fs::metadata(&encrypted)
+      .await
+      .is_ok_and(|metadata| metadata.is_file())
+      .then_some(encrypted)
+  }
+
+  /// Decrypt a C4GH object, returning a body with the plaintext for the `Range` header along with
+  /// the plaintext size. Only the data blocks that cover the range are read, and they are
+  /// decrypted as the body is streamed.
+  async fn decrypt(&self, path: PathBuf, headers: HeaderMap) -> Result<(Body, ByteRange, u64)> {
+    let keys = self
+      .decrypt
+      .keys()
+      .clone()
+      .keys()
+      .await
+      .map_err(|err| ServerError(format!("loading C4GH keys: {err}")))?;
+
+    let (file, header, encrypted_size) = tokio::task::spawn_blocking(move || {
+      let mut file = std::fs::File::open(path)?;
+      let encrypted_size = file.metadata()?.len();
+      let header = DeserializedHeader::from_buffer(&mut BufReader::new(&mut file), &keys)
+        .map_err(|err| ServerError(format!("decrypting C4GH header: {err}")))?;
+
+      Ok::<_, Error>((file, header, encrypted_size))
+    })
+    .await
+    .map_err(|err| ServerError(err.to_string()))??;
+    let header_size = header.header_size();
+
+    if header.contains_edit_list() {
+      // An edit list discards parts of the plaintext, so the data blocks of a range cannot be
+      // located without decrypting the whole object.
+      let mut file = file;
+      let data = tokio::task::spawn_blocking(move || {
+        decrypt_blocks(&mut file, header, header_size, encrypted_size)
+      })
+      .await
+      .map_err(|err| ServerError(err.to_string()))??;
+      let size = data.len() as u64;
+
+      return Ok(match ByteRange::from_headers(&headers, size) {
+        ByteRange::Partial(range) => (
+          Body::from(data[*range.start() as usize..=*range.end() as usize].to_vec()),
+          ByteRange::Partial(range),
+          size,
+        ),
+        range => (Body::from(data), range, size),
+      });
+    }
+
+    let size = to_unencrypted_file_size(encrypted_size, header_size);
+    Ok(match ByteRange::from_headers(&headers, size) {
+      ByteRange::Full => (
+        decrypt_stream(file, header, encrypted_size, 0, size),
+        ByteRange::Full,
+        size,
+      ),
+      ByteRange::Partial(range) => (
+        decrypt_stream(
+          file,
+          header,
+          encrypted_size,
+          *range.start(),
+          range.end() + 1,
+        ),
+        ByteRange::Partial(range),
+        size,
+      ),
+      ByteRange::Unsatisfiable => (Body::empty(), ByteRange::Unsatisfiable, size),
+    })
+  }
+}
+
+/// Stream the plaintext between the unencrypted positions `start` and `end` of a C4GH object
+/// without an edit list. The data blocks are decrypted in chunks of `STREAM_BLOCKS` blocks.
+#[cfg(feature = "experimental")]
+fn decrypt_stream(
+  mut file: std::fs::File,
+  header: DeserializedHeader,
+  encrypted_size: u64,
+  start: u64,
+  end: u64,
+) -> Body {
+  let (tx, rx) = mpsc::channel(1);
+
+  tokio::task::spawn_blocking(move || {
+    let header_size = header.header_size();
+    let mut position = start;
+
+    while position < end {
+      let block = position / ENCRYPTED_BLOCK_SIZE * ENCRYPTED_BLOCK_SIZE;
+      let chunk_end = min(block + STREAM_BLOCKS * ENCRYPTED_BLOCK_SIZE, end);
+
+      let chunk = decrypt_blocks(
+        &mut file,
+        header.clone(),
+        unencrypted_to_data_block(block, header_size, encrypted_size),
+        unencrypted_to_next_data_block(chunk_end - 1, header_size, encrypted_size),
+      )
+      .and_then(|data| {
+        data
+          .get((position - block) as usize..(chunk_end - block) as usize)
+          .map(<[u8]>::to_vec)
+          .ok_or_else(|| ServerError("C4GH object is shorter than expected".to_string()))
+      })
+      .map_err(|err| io::Error::other(err.to_string()));
+
+      let failed = chunk.is_err();
+      if tx.blocking_send(chunk).is_err() || failed {
+        break;
+      }
+
+      position = chunk_end;
+    }
+  });
+
+  Body::from_stream(stream::unfold(rx, |mut rx| async move {
+    rx.recv().await.map(|chunk| (chunk, rx))
+  }))
+}
+
+/// Decrypt the data blocks of a C4GH object between the encrypted positions `start` and `end`.
+#[cfg(feature = "experimental")]
+fn decrypt_blocks(
+  file: &mut std::fs::File,
+  header: DeserializedHeader,
+  start: u64,
+  end: u64,
+) -> Result<Vec<u8>> {
+  file.seek(SeekFrom::Start(start))?;
+  let mut reader = BufReader::new(file).take(end.saturating_sub(start));
+
+  Ok(
+    DecryptedData::from_header(&mut reader, header)
+      .map_err(|err| ServerError(format!("decrypting C4GH data: {err}")))?
+      .into_inner(),
+  )
+}
+
+/// Middleware which returns plaintext data for C4GH objects when the client is trusted. Requests
+/// from other clients, or for objects that are not encrypted, are passed on to the next service.
+/// Clients are identified by the IP address of the connection, and forwarding headers such as
+/// `X-Forwarded-For` are not used.
+#[cfg(feature = "experimental")]
+async fn decrypt_c4gh(
+  State(state): State<Arc<DecryptState>>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let trusted = request
+    .extensions()
+    .get::<ConnectInfo<SocketAddr>>()
+    .is_some_and(|ConnectInfo(addr)| state.decrypt.is_trusted(addr.ip()));
+  if !trusted || request.method() != Method::GET {
+    return next.run(request).await;
+  }
+
+  let Some(path) = state.encrypted_path(request.uri().path()).await else {
+    return next.run(request).await;
+  };
+
+  trace!(path = ?path, "decrypting C4GH object for trusted client");
+  match state.decrypt(path, request.headers().clone()).await {
+    Ok((body, range, size)) => plaintext_response(body, range, size),
+    Err(err) => {
+      warn!("failed to decrypt C4GH object: {}", err);
+      StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+  }
+}
+
+/// Create a response for decrypted data, where the body only contains the requested range for a
+/// partial response.
+#[cfg(feature = "experimental")]
+fn plaintext_response(body: Body, range: ByteRange, size: u64) -> Response {
+  match range {
+    ByteRange::Full => (
+      [
+        (CONTENT_TYPE, "application/octet-stream".to_string()),
+        (ACCEPT_RANGES, "bytes".to_string()),
+        (CONTENT_LENGTH, size.to_string()),
+      ],
+      body,
+    )
+      .into_response(),
+    ByteRange::Partial(range) => {
+      let content_range = format!("bytes {}-{}/{}", range.start(), range.end(), size);
+
+      (
+        StatusCode::PARTIAL_CONTENT,
+        [
+          (CONTENT_TYPE, "application/octet-stream".to_string()),
+          (ACCEPT_RANGES, "bytes".to_string()),
+          (
+            CONTENT_LENGTH,
+            (range.end() - range.start() + 1).to_string(),
+          ),
+          (CONTENT_RANGE, content_range),
+        ],
+        body,
+      )
+        .into_response()
+    }
+    ByteRange::Unsatisfiable => (
+      StatusCode::RANGE_NOT_SATISFIABLE,
+      [(CONTENT_RANGE, format!("bytes */{size}"))],
+    )
+      .into_response(),
+  }
+}
+
 impl From<DataServerConfig> for BindServer {
   /// Returns a data server with TLS enabled if the tls config is not None or without TLS enabled
   /// if it is None.
@@ -63,6 +377,8 @@ impl From<DataServerConfig> for BindServer {
 pub async fn join_handle(config: DataServerConfig) -> Result<JoinHandle<Result<()>>> {
   let local_path = config.local_path().to_path_buf();
   let data_server = BindServer::from(config.clone()).bind_data_server().await?;
+  #[cfg(feature = "experimental")]
+  let data_server = data_server.with_decrypt(config.decrypt().cloned());
 
   info!(address = ?data_server.local_addr()?, "data server address bound to");
 
@@ -85,13 +401,33 @@ mod tests {
   use tokio::io::AsyncWriteExt;
 
   use htsget_config::config::Config;
+  #[cfg(feature = "experimental")]
+  use htsget_config::resolver::ResolveResponse;
+  #[cfg(feature = "experimental")]
+  use htsget_config::storage::c4gh::local::C4GHLocal;
   use htsget_config::tls::TlsServerConfig;
   use htsget_config::types::Scheme;
+  #[cfg(feature = "experimental")]
+  use htsget_config::types::{Class, Format, Query};
+  #[cfg(feature = "experimental")]
+  use htsget_search::from_storage::HtsGetFromStorage;
+  #[cfg(feature = "experimental")]
+  use htsget_test::http::concat::ConcatResponse;
   use htsget_test::http::cors::{test_cors_preflight_request_uri, test_cors_simple_request_uri};
+  use htsget_test::http::data::{
+    test_data_server_file_uri, test_data_server_missing_file_uri, test_data_server_range_uri,
+  };
   use htsget_test::http::{
     config_with_tls, default_cors_config, default_test_config, Header, Response as TestResponse,
     TestRequest, TestServer,
   };
+  #[cfg(feature = "experimental")]
+  use htsget_test::util::default_dir;
+  use htsget_test::util::default_dir_data;
+  #[cfg(feature = "experimental")]
+  use http::header::RANGE;
+  #[cfg(feature = "experimental")]
+  use http::uri::Authority;
 
   use super::*;
 
@@ -256,6 +592,195 @@ mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn data_server_file() {
+    let port = start_data_server(None, default_dir_data()).await;
+
+    test_data_server_file_uri(
+      &DataTestServer::default(),
+      &format!("http://localhost:{port}"),
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn data_server_range() {
+    let port = start_data_server(None, default_dir_data()).await;
+
+    test_data_server_range_uri(
+      &DataTestServer::default(),
+      &format!("http://localhost:{port}"),
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn data_server_missing_file() {
+    let port = start_data_server(None, default_dir_data()).await;
+
+    test_data_server_missing_file_uri(
+      &DataTestServer::default(),
+      &format!("http://localhost:{port}"),
+    )
+    .await;
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn decrypt_for_trusted_client() {
+    let port = start_decrypting_data_server("127.0.0.1").await;
+
+    let response = reqwest::get(format!("http://127.0.0.1:{port}/htsnexus_test_NA12878.bam"))
+      .await
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.bytes().await.unwrap().to_vec(),
+      tokio::fs::read(default_dir().join("data/bam/htsnexus_test_NA12878.bam"))
+        .await
+        .unwrap()
+    );
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn decrypt_range_for_trusted_client() {
+    test_decrypted_range("bytes=4-11", 4..=11).await;
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn decrypt_range_across_blocks_for_trusted_client() {
+    test_decrypted_range("bytes=65530-131080", 65530..=131080).await;
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn decrypt_suffix_range_for_trusted_client() {
+    test_decrypted_range("bytes=-100", 2596699..=2596798).await;
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn encrypted_for_untrusted_client() {
+    let port = start_decrypting_data_server("10.0.0.0/8").await;
+
+    let response = reqwest::get(format!("http://127.0.0.1:{port}/htsnexus_test_NA12878.bam"))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = reqwest::get(format!(
+      "http://127.0.0.1:{port}/htsnexus_test_NA12878.bam.c4gh"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.bytes().await.unwrap().to_vec(),
+      tokio::fs::read(default_dir().join("data/c4gh/htsnexus_test_NA12878.bam.c4gh"))
+        .await
+        .unwrap()
+    );
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn follow_plaintext_ticket_for_trusted_client() {
+    let port = start_decrypting_data_server("127.0.0.1").await;
+
+    let keys = default_dir().join("data/c4gh/keys");
+    let mut file = htsget_config::storage::file::File::new(
+      Scheme::Http,
+      Authority::from_str(&format!("127.0.0.1:{port}")).unwrap(),
+      default_dir()
+        .join("data/c4gh")
+        .to_string_lossy()
+        .to_string(),
+    );
+    file.set_keys(Some(
+      C4GHLocal::new(keys.join("bob.sec"), keys.join("alice.pub"))
+        .try_into()
+        .unwrap(),
+    ));
+    file.set_plaintext_urls(true);
+
+    let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+      .with_reference_name("11")
+      .with_start(5015000)
+      .with_end(5050000);
+    let response = HtsGetFromStorage::from_file(&file, &query).await.unwrap();
+
+    let urls = &response.urls;
+    assert!(urls.iter().all(|url| !url.url.contains(".c4gh")));
+    assert!(urls
+      .iter()
+      .any(|url| url.url == format!("http://127.0.0.1:{port}/htsnexus_test_NA12878.bam")));
+
+    ConcatResponse::new(response, Class::Body)
+      .concat_from_client(&Client::new())
+      .await
+      .unwrap()
+      .read_records()
+      .await
+      .unwrap();
+  }
+
+  #[cfg(feature = "experimental")]
+  async fn test_decrypted_range(range: &str, expected_range: std::ops::RangeInclusive<usize>) {
+    let port = start_decrypting_data_server("127.0.0.0/8").await;
+    let expected = tokio::fs::read(default_dir().join("data/bam/htsnexus_test_NA12878.bam"))
+      .await
+      .unwrap();
+
+    let response = Client::new()
+      .get(format!("http://127.0.0.1:{port}/htsnexus_test_NA12878.bam"))
+      .header(RANGE, range)
+      .send()
+      .await
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+      response.headers().get(CONTENT_RANGE).unwrap(),
+      format!(
+        "bytes {}-{}/{}",
+        expected_range.start(),
+        expected_range.end(),
+        expected.len()
+      )
+      .as_str()
+    );
+    assert_eq!(
+      response.bytes().await.unwrap().to_vec(),
+      expected[expected_range]
+    );
+  }
+
+  #[cfg(feature = "experimental")]
+  async fn start_decrypting_data_server(trusted_client: &str) -> u16 {
+    let keys = default_dir().join("data/c4gh/keys");
+    let keys = C4GHLocal::new(keys.join("bob.sec"), keys.join("alice.pub"))
+      .try_into()
+      .unwrap();
+    let decrypt = DataServerDecrypt::new(keys, vec![trusted_client.parse().unwrap()]);
+
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+    let server = Server::bind_addr(addr, None).await.unwrap();
+    let port = server.local_addr().unwrap().port();
+
+    let data_server = DataServer::new(server, default_cors_config()).with_decrypt(Some(decrypt));
+    tokio::spawn(async move {
+      data_server
+        .serve(default_dir().join("data/c4gh"))
+        .await
+        .unwrap()
+    });
+
+    port
+  }
+
   fn tls_formatter() -> BindServer {
     let _ = aws_lc_rs::default_provider().install_default();
 
@@ -2,18 +2,23 @@
 //!
 
 pub mod data;
+#[cfg(feature = "aws")]
+pub mod proxy;
+#[cfg(any(feature = "experimental", feature = "aws"))]
+mod range;
 pub mod ticket;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use axum::extract::Request;
+use axum::extract::{ConnectInfo, Request};
 use axum::Router;
 use htsget_config::config::advanced::cors::CorsConfig;
 use htsget_config::config::service_info::ServiceInfo;
 use htsget_config::tls::TlsServerConfig;
 use htsget_config::types::Scheme;
+use htsget_http::remove_authorizer_headers;
 use htsget_search::HtsGet;
 use http::HeaderValue;
 use hyper::body::Incoming;
@@ -22,6 +27,7 @@ use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder;
 use tokio::net::TcpListener;
 use tokio_rustls::TlsAcceptor;
+use tower::util::MapRequestLayer;
 use tower::Service;
 use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer, ExposeHeaders};
 use tracing::trace;
@@ -209,12 +215,22 @@ impl Server {
     })
   }
 
-  /// Run the actual server, using the router, key and certificate.
+  /// Run the actual server, using the router, key and certificate. The address of the client is
+  /// available to the router as `ConnectInfo<SocketAddr>`. Authorizer headers sent by clients are
+  /// removed, as this server does not run behind an authorizer.
   pub async fn serve(self, app: Router) -> Result<()> {
+    let app = app.layer(MapRequestLayer::new(|mut request: Request| {
+      remove_authorizer_headers(request.headers_mut());
+      request
+    }));
+
     match self.cert_key_pair {
-      None => axum::serve(self.listener, app)
-        .await
-        .map_err(|err| ServerError(err.to_string())),
+      None => axum::serve(
+        self.listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+      )
+      .await
+      .map_err(|err| ServerError(err.to_string())),
       Some(tls) => {
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls.into_inner()));
 
@@ -232,8 +248,10 @@ impl Server {
             };
 
             let stream = TokioIo::new(stream);
-            let hyper_service =
-              service_fn(move |request: Request<Incoming>| tower_service.clone().call(request));
+            let hyper_service = service_fn(move |mut request: Request<Incoming>| {
+              request.extensions_mut().insert(ConnectInfo(addr));
+              tower_service.clone().call(request)
+            });
 
             let ret = Builder::new(TokioExecutor::new())
               .serve_connection_with_upgrades(stream, hyper_service)
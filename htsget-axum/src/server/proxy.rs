@@ -0,0 +1,308 @@
+//! A router which streams byte ranges of S3 objects through the server, for clients which cannot
+//! reach S3 pre-signed URLs directly. Requests must carry the signature which is added to the
+//! urls in tickets.
+//!
+
+use crate::error::Error::ServerError;
+use crate::error::Result;
+use crate::server::range::ByteRange;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use htsget_config::config::location::LocationEither;
+use htsget_config::storage::Backend;
+use htsget_storage::error::StorageError;
+use htsget_storage::s3::{ProxySigner, S3Storage};
+use htsget_storage::types::BytesPosition;
+use htsget_storage::{GetOptions, HeadOptions, StorageTrait};
+use http::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use http::{HeaderMap, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+/// Create a router that serves the objects of each S3 location which has a `proxy` set. Objects
+/// are served under the path of the proxy URL, so tickets which point to the proxy resolve to the
+/// routes of this router. The path prefix is removed from the proxy paths for servers which strip
+/// it from requests before routing.
+pub async fn router<'a>(
+  locations: impl IntoIterator<Item = &'a LocationEither>,
+  path_prefix: Option<&str>,
+) -> Result<Router> {
+  let mut paths = HashSet::new();
+  let mut router = Router::new();
+
+  for location in locations {
+    let Some(Backend::S3(s3)) = location.backend() else {
+      continue;
+    };
+    let (Some(proxy), Some(secret)) = (s3.proxy(), s3.proxy_secret()) else {
+      continue;
+    };
+
+    let path = proxy.path().trim_end_matches('/');
+    let path = path_prefix
+      .map(|prefix| prefix.trim_end_matches('/'))
+      .and_then(|prefix| path.strip_prefix(prefix))
+      .filter(|path| path.is_empty() || path.starts_with('/'))
+      .unwrap_or(path)
+      .to_string();
+    if !paths.insert(path.clone()) {
+      return Err(ServerError(format!(
+        "multiple S3 locations use the proxy path `{path}`"
+      )));
+    }
+
+    let storage = S3Storage::new_with_default_config(
+      s3.bucket().to_string(),
+      s3.endpoint().map(str::to_string),
+      s3.path_style(),
+    )
+    .await;
+    router = router.merge(storage_router(&path, storage, ProxySigner::new(secret)));
+  }
+
+  Ok(router)
+}
+
+/// The storage served by a proxy route, and the signer which verifies its requests.
+#[derive(Debug)]
+struct ProxyState {
+  storage: S3Storage,
+  signer: ProxySigner,
+}
+
+/// Create the route which serves objects from the storage under the path. Requests are rejected
+/// unless they are signed by the signer.
+pub fn storage_router(path: &str, storage: S3Storage, signer: ProxySigner) -> Router {
+  Router::new().route(
+    &format!("{}/*key", path.trim_end_matches('/')),
+    get(proxy_object).with_state(Arc::new(ProxyState { storage, signer })),
+  )
+}
+
+/// Stream the object, or the byte range of it requested with a `Range` header.
+async fn proxy_object(
+  State(state): State<Arc<ProxyState>>,
+  Path(key): Path<String>,
+  Query(query): Query<HashMap<String, String>>,
+  headers: HeaderMap,
+) -> Response {
+  let (Some(expires), Some(signature)) = (query.get("expires"), query.get("signature")) else {
+    return StatusCode::FORBIDDEN.into_response();
+  };
+  if !state.signer.verify(&key, expires, signature) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let storage = &state.storage;
+  let size = match storage.head(&key, HeadOptions::new(&headers)).await {
+    Ok(size) => size,
+    Err(err) => return error_response(err),
+  };
+
+  let (status, range, content_range) = match ByteRange::from_headers(&headers, size) {
+    ByteRange::Full => (StatusCode::OK, None, None),
+    ByteRange::Partial(range) => (
+      StatusCode::PARTIAL_CONTENT,
+      Some(range.clone()),
+      Some(format!("bytes {}-{}/{}", range.start(), range.end(), size)),
+    ),
+    ByteRange::Unsatisfiable => {
+      return (
+        StatusCode::RANGE_NOT_SATISFIABLE,
+        [(CONTENT_RANGE, format!("bytes */{size}"))],
+      )
+        .into_response()
+    }
+  };
+
+  let (position, length) = match &range {
+    Some(range) => (
+      BytesPosition::default()
+        .with_start(*range.start())
+        .with_end(range.end() + 1),
+      range.end() - range.start() + 1,
+    ),
+    None => (BytesPosition::default(), size),
+  };
+
+  let body = if length == 0 {
+    Body::empty()
+  } else {
+    match storage.get(&key, GetOptions::new(position, &headers)).await {
+      Ok(stream) => Body::from_stream(ReaderStream::new(stream)),
+      Err(err) => return error_response(err),
+    }
+  };
+
+  let mut response = (
+    status,
+    [
+      (CONTENT_TYPE, "application/octet-stream".to_string()),
+      (ACCEPT_RANGES, "bytes".to_string()),
+      (CONTENT_LENGTH, length.to_string()),
+    ],
+    body,
+  )
+    .into_response();
+  if let Some(content_range) = content_range {
+    response.headers_mut().insert(
+      CONTENT_RANGE,
+      content_range
+        .parse()
+        .expect("expected valid content range header"),
+    );
+  }
+
+  response
+}
+
+/// Convert a storage error into a response.
+fn error_response(err: StorageError) -> Response {
+  match err {
+    StorageError::KeyNotFound(_) => StatusCode::NOT_FOUND.into_response(),
+    StorageError::InvalidKey(_) => StatusCode::BAD_REQUEST.into_response(),
+    err => {
+      warn!(error = %err, "failed to proxy S3 object");
+      StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::body::to_bytes;
+  use axum::extract::Request;
+  use htsget_test::aws_mocks::with_s3_test_server;
+  use http::header::RANGE;
+  use std::fs;
+  use tempfile::TempDir;
+  use tower::ServiceExt;
+
+  async fn with_proxy_router<F, Fut>(test: F)
+  where
+    F: FnOnce(Router) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+  {
+    let base_path = TempDir::new().unwrap();
+    fs::create_dir(base_path.path().join("bucket")).unwrap();
+    fs::write(base_path.path().join("bucket/key1"), "value1 and more").unwrap();
+
+    with_s3_test_server(base_path.path(), |client| async move {
+      test(storage_router(
+        "/data/",
+        S3Storage::new(client, "bucket".to_string()),
+        ProxySigner::new("secret"),
+      ))
+      .await;
+    })
+    .await;
+  }
+
+  fn signed_path(key: &str) -> String {
+    format!("/data/{key}?{}", ProxySigner::new("secret").sign(key))
+  }
+
+  async fn request(router: Router, path: &str, range: Option<&str>) -> Response {
+    let mut request = Request::get(path);
+    if let Some(range) = range {
+      request = request.header(RANGE, range);
+    }
+
+    router
+      .oneshot(request.body(Body::empty()).unwrap())
+      .await
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn proxy_full_object() {
+    with_proxy_router(|router| async move {
+      let response = request(router, &signed_path("key1"), None).await;
+
+      assert_eq!(response.status(), StatusCode::OK);
+      assert_eq!(response.headers()[CONTENT_LENGTH], "15");
+      assert_eq!(
+        to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+        "value1 and more"
+      );
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn proxy_byte_range() {
+    with_proxy_router(|router| async move {
+      let response = request(router, &signed_path("key1"), Some("bytes=7-9")).await;
+
+      assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+      assert_eq!(response.headers()[CONTENT_RANGE], "bytes 7-9/15");
+      assert_eq!(
+        to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+        "and"
+      );
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn proxy_unsatisfiable_range() {
+    with_proxy_router(|router| async move {
+      let response = request(router, &signed_path("key1"), Some("bytes=20-")).await;
+
+      assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+      assert_eq!(response.headers()[CONTENT_RANGE], "bytes */15");
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn proxy_missing_object() {
+    with_proxy_router(|router| async move {
+      let response = request(router, &signed_path("missing"), None).await;
+
+      assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn proxy_unsigned_request() {
+    with_proxy_router(|router| async move {
+      let response = request(router, "/data/key1", None).await;
+
+      assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn proxy_request_signed_for_another_key() {
+    with_proxy_router(|router| async move {
+      let path = signed_path("missing").replace("/data/missing", "/data/key1");
+      let response = request(router, &path, None).await;
+
+      assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn proxy_expired_request() {
+    with_proxy_router(|router| async move {
+      let path = format!(
+        "/data/key1?{}",
+        ProxySigner::new("secret").sign_until("key1", 0)
+      );
+      let response = request(router, &path, None).await;
+
+      assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    })
+    .await;
+  }
+}
@@ -0,0 +1,82 @@
+//! Parsing of `Range` headers for servers that respond with byte ranges of objects.
+//!
+
+use http::header::RANGE;
+use http::HeaderMap;
+use std::ops::RangeInclusive;
+
+/// A byte range requested with a `Range` header.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ByteRange {
+  Full,
+  Partial(RangeInclusive<u64>),
+  Unsatisfiable,
+}
+
+impl ByteRange {
+  /// Resolve the `Range` header against the size of the object. Multiple ranges and invalid
+  /// ranges are ignored, which returns the full object.
+  pub(crate) fn from_headers(headers: &HeaderMap, size: u64) -> Self {
+    let Some(range) = headers
+      .get(RANGE)
+      .and_then(|range| range.to_str().ok())
+      .and_then(|range| range.trim().strip_prefix("bytes="))
+    else {
+      return Self::Full;
+    };
+    if range.contains(',') {
+      return Self::Full;
+    }
+    let Some((start, end)) = range.split_once('-') else {
+      return Self::Full;
+    };
+
+    let last = size.saturating_sub(1);
+    let range = match (start.trim(), end.trim()) {
+      ("", suffix) => match suffix.parse::<u64>() {
+        Ok(suffix) if suffix > 0 => size.saturating_sub(suffix)..=last,
+        Ok(_) => return Self::Unsatisfiable,
+        Err(_) => return Self::Full,
+      },
+      (start, "") => match start.parse::<u64>() {
+        Ok(start) => start..=last,
+        Err(_) => return Self::Full,
+      },
+      (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => start..=end.min(last),
+        _ => return Self::Full,
+      },
+    };
+
+    if size == 0 || *range.start() >= size {
+      Self::Unsatisfiable
+    } else {
+      Self::Partial(range)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn byte_range_from_headers() {
+    let range = |value: &str| {
+      let mut headers = HeaderMap::new();
+      headers.insert(RANGE, value.parse().unwrap());
+      ByteRange::from_headers(&headers, 10)
+    };
+
+    assert_eq!(range("bytes=2-4"), ByteRange::Partial(2..=4));
+    assert_eq!(range("bytes=2-"), ByteRange::Partial(2..=9));
+    assert_eq!(range("bytes=-3"), ByteRange::Partial(7..=9));
+    assert_eq!(range("bytes=8-20"), ByteRange::Partial(8..=9));
+    assert_eq!(range("bytes=10-"), ByteRange::Unsatisfiable);
+    assert_eq!(range("bytes=0-1,4-5"), ByteRange::Full);
+    assert_eq!(
+      ByteRange::from_headers(&HeaderMap::new(), 10),
+      ByteRange::Full
+    );
+  }
+}
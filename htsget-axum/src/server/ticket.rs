@@ -2,14 +2,20 @@
 //!
 
 use crate::error::Result;
-use crate::handlers::{get, post, reads_service_info, variants_service_info};
+use crate::handlers::{
+  annotations_service_info, get, openapi, post, reads_service_info, sequences_service_info,
+  variants_service_info,
+};
 use crate::server::{configure_cors, AppState, BindServer, Server};
 use axum::routing::get;
 use axum::Router;
 use htsget_config::config::advanced::cors::CorsConfig;
+use htsget_config::config::location::Locations;
 use htsget_config::config::service_info::ServiceInfo;
+use htsget_config::config::services::Services;
 use htsget_config::config::ticket_server::TicketServerConfig;
 use htsget_config::config::Config;
+use htsget_http::OPENAPI_PATH;
 use htsget_search::HtsGet;
 use std::net::SocketAddr;
 use tokio::task::JoinHandle;
@@ -38,6 +44,7 @@ pub struct TicketServer<H> {
   htsget: H,
   service_info: ServiceInfo,
   cors: CorsConfig,
+  services: Services,
 }
 
 impl<H> TicketServer<H>
@@ -51,14 +58,26 @@ where
       htsget,
       service_info,
       cors,
+      services: Default::default(),
     }
   }
 
+  /// Set the services that are mounted under path prefixes.
+  pub fn with_services(mut self, services: Services) -> Self {
+    self.services = services;
+    self
+  }
+
   /// Run the data server, using the key and certificate.
   pub async fn serve(self) -> Result<()> {
     self
       .server
-      .serve(Self::router(self.htsget, self.service_info, self.cors))
+      .serve(Self::router_with_services(
+        self.htsget,
+        self.service_info,
+        self.cors,
+        self.services,
+      ))
       .await
   }
 
@@ -75,6 +94,20 @@ where
         get(variants_service_info::<H>).post(variants_service_info::<H>),
       )
       .route("/variants/*id", get(get::variants).post(post::variants))
+      .route(
+        "/sequences/service-info",
+        get(sequences_service_info::<H>).post(sequences_service_info::<H>),
+      )
+      .route("/sequences/*id", get(get::sequences).post(post::sequences))
+      .route(
+        "/annotations/service-info",
+        get(annotations_service_info::<H>).post(annotations_service_info::<H>),
+      )
+      .route(
+        "/annotations/*id",
+        get(get::annotations).post(post::annotations),
+      )
+      .route(OPENAPI_PATH, get(openapi::<H>))
       .layer(
         ServiceBuilder::new()
           .layer(TraceLayer::new_for_http())
@@ -83,6 +116,28 @@ where
       .with_state(AppState::new(htsget, service_info))
   }
 
+  /// Create the router for the ticket server, and nest a router for each service under its
+  /// prefix. Services use the top-level service info and CORS config if they don't set their own.
+  pub fn router_with_services(
+    htsget: H,
+    service_info: ServiceInfo,
+    cors: CorsConfig,
+    services: Services,
+  ) -> Router {
+    let router = Self::router(htsget, service_info.clone(), cors.clone());
+
+    services
+      .into_inner()
+      .into_iter()
+      .fold(router, |router, service| {
+        let (prefix, locations, service_info, cors) = service.into_inner_or(&service_info, &cors);
+        router.nest(
+          &prefix,
+          TicketServer::<Locations>::router(locations, service_info, cors),
+        )
+      })
+  }
+
   /// Get the local address the server has bound to.
   pub fn local_addr(&self) -> Result<SocketAddr> {
     self.server.local_addr()
@@ -92,9 +147,11 @@ where
 /// Spawn a task to run the ticket server.
 pub async fn join_handle(config: Config) -> Result<JoinHandle<Result<()>>> {
   let service_info = config.service_info().clone();
+  let services = config.services().clone();
   let ticket_server = BindServer::from(config.ticket_server().clone())
     .bind_ticket_server(config.into_locations(), service_info)
-    .await?;
+    .await?
+    .with_services(services);
 
   info!(address = ?ticket_server.local_addr()?, "ticket server address bound to");
 
@@ -115,8 +172,8 @@ mod tests {
   use htsget_config::types::JsonResponse;
   use htsget_test::http::server::expected_url_path;
   use htsget_test::http::{
-    config_with_tls, cors, default_test_config, server, Header, Response as TestResponse,
-    TestRequest, TestServer,
+    config_with_tls, cors, default_test_config, default_test_config_with_services, server, Header,
+    Response as TestResponse, TestRequest, TestServer,
   };
   use http::header::HeaderName;
   use http::{Method, Request};
@@ -221,11 +278,18 @@ mod tests {
       }
     }
 
+    fn new_with_services() -> Self {
+      Self {
+        config: default_test_config_with_services(),
+      }
+    }
+
     async fn get_response(&self, request: Request<Body>) -> result::Result<Response, Infallible> {
-      let app = TicketServer::router(
+      let app = TicketServer::router_with_services(
         self.config.clone().into_locations(),
         self.config.service_info().clone(),
         self.config.ticket_server().cors().clone(),
+        self.config.services().clone(),
       );
 
       app.oneshot(request).await
@@ -263,6 +327,16 @@ mod tests {
     server::test_service_info(&AxumTestServer::default()).await;
   }
 
+  #[tokio::test]
+  async fn services_http_tickets() {
+    server::test_services::<JsonResponse, _>(&AxumTestServer::new_with_services()).await;
+  }
+
+  #[tokio::test]
+  async fn openapi() {
+    server::test_openapi(&AxumTestServer::default()).await;
+  }
+
   #[tokio::test]
   async fn get_https_tickets() {
     let base_path = TempDir::new().unwrap();
@@ -1,10 +1,10 @@
 //! Allow guard configuration.
 //!
 
-use crate::types::Format::{Bam, Bcf, Cram, Vcf};
+use crate::types::Format::{Bam, Bcf, Bed, Cram, Fasta, Gff3, Gtf, Vcf};
 use crate::types::{Class, Fields, Format, Interval, Query, TaggedTypeAll, Tags};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Determines whether the query matches for use with the storage.
 pub trait QueryAllowed {
@@ -22,17 +22,19 @@ pub struct AllowGuard {
   allow_formats: Vec<Format>,
   allow_classes: Vec<Class>,
   allow_interval: Interval,
+  allow_request_headers: HashMap<String, HashSet<String>>,
 }
 
 impl Default for AllowGuard {
   fn default() -> Self {
     Self {
-      allow_formats: vec![Bam, Cram, Vcf, Bcf],
+      allow_formats: vec![Bam, Cram, Vcf, Bcf, Fasta, Bed, Gff3, Gtf],
       allow_classes: vec![Class::Body, Class::Header],
       allow_interval: Default::default(),
       allow_reference_names: ReferenceNames::Tagged(TaggedTypeAll::All),
       allow_fields: Fields::Tagged(TaggedTypeAll::All),
       allow_tags: Tags::Tagged(TaggedTypeAll::All),
+      allow_request_headers: Default::default(),
     }
   }
 }
@@ -62,9 +64,19 @@ impl AllowGuard {
       allow_formats,
       allow_classes,
       allow_interval,
+      allow_request_headers: Default::default(),
     }
   }
 
+  /// Set the request headers which must be present with one of the allowed values.
+  pub fn with_allow_request_headers(
+    mut self,
+    allow_request_headers: HashMap<String, HashSet<String>>,
+  ) -> Self {
+    self.allow_request_headers = allow_request_headers;
+    self
+  }
+
   /// Get allow formats.
   pub fn allow_formats(&self) -> &[Format] {
     &self.allow_formats
@@ -94,6 +106,11 @@ impl AllowGuard {
   pub fn allow_tags(&self) -> &Tags {
     &self.allow_tags
   }
+
+  /// Get allow request headers.
+  pub fn allow_request_headers(&self) -> &HashMap<String, HashSet<String>> {
+    &self.allow_request_headers
+  }
 }
 
 impl QueryAllowed for ReferenceNames {
@@ -130,19 +147,42 @@ impl QueryAllowed for Tags {
   }
 }
 
-impl QueryAllowed for AllowGuard {
-  fn query_allowed(&self, query: &Query) -> bool {
-    self.allow_formats().contains(&query.format())
-      && self.allow_classes().contains(&query.class())
-      && self
-        .allow_interval()
-        .contains(query.interval().start().unwrap_or(u32::MIN))
+impl AllowGuard {
+  /// Check whether the reference name and interval of a single region are allowed.
+  fn region_allowed(&self, query: &Query) -> bool {
+    self
+      .allow_interval()
+      .contains(query.interval().start().unwrap_or(u32::MIN))
       && self
         .allow_interval()
         .contains(query.interval().end().unwrap_or(u32::MAX))
       && self.allow_reference_names().query_allowed(query)
+  }
+
+  /// Check whether each allowed request header is present with one of its allowed values.
+  fn request_headers_allowed(&self, query: &Query) -> bool {
+    self.allow_request_headers().iter().all(|(name, allowed)| {
+      query
+        .request()
+        .headers()
+        .get_all(name.as_str())
+        .iter()
+        .any(|value| value.to_str().is_ok_and(|value| allowed.contains(value)))
+    })
+  }
+}
+
+impl QueryAllowed for AllowGuard {
+  fn query_allowed(&self, query: &Query) -> bool {
+    self.allow_formats().contains(&query.format())
+      && self.allow_classes().contains(&query.class())
+      && query
+        .region_queries()
+        .iter()
+        .all(|region| self.region_allowed(region))
       && self.allow_fields().query_allowed(query)
       && self.allow_tags().query_allowed(query)
+      && self.request_headers_allowed(query)
   }
 }
 
@@ -153,6 +193,8 @@ mod tests {
   #[cfg(feature = "aws")]
   use crate::config::Config;
   use crate::types::Class::Header;
+  use crate::types::Request;
+  use http::{HeaderMap, HeaderValue};
 
   #[test]
   fn allow_reference_names_all() {
@@ -265,6 +307,21 @@ mod tests {
     );
   }
 
+  #[test]
+  fn allow_request_headers() {
+    test_serialize_and_deserialize(
+      "allow_request_headers.x-htsget-authorizer-group = [\"cohort\"]",
+      AllowGuard {
+        allow_request_headers: HashMap::from_iter(vec![(
+          "x-htsget-authorizer-group".to_string(),
+          HashSet::from_iter(vec!["cohort".to_string()]),
+        )]),
+        ..Default::default()
+      },
+      |result| result,
+    );
+  }
+
   #[cfg(feature = "aws")]
   #[test]
   fn allow_guard() {
@@ -338,4 +395,47 @@ mod tests {
         .with_end(1000)
     ));
   }
+
+  #[test]
+  fn query_allowed_regions() {
+    let guard = AllowGuard {
+      allow_reference_names: ReferenceNames::List(HashSet::from_iter(vec!["chr1".to_string()])),
+      allow_interval: Interval::new(Some(0), Some(100)),
+      ..Default::default()
+    };
+
+    let query = Query::new_with_default_request("", Bam)
+      .with_reference_name("chr1")
+      .with_start(1)
+      .with_end(50);
+    assert!(guard.query_allowed(
+      &query
+        .clone()
+        .with_region("chr1", Interval::new(Some(60), Some(80)))
+    ));
+    assert!(!guard.query_allowed(
+      &query
+        .clone()
+        .with_region("chr2", Interval::new(Some(60), Some(80)))
+    ));
+    assert!(!guard.query_allowed(&query.with_region("chr1", Interval::new(Some(60), Some(200)))));
+  }
+
+  #[test]
+  fn query_allowed_request_headers() {
+    let guard = AllowGuard::default().with_allow_request_headers(HashMap::from_iter(vec![(
+      "x-htsget-authorizer-group".to_string(),
+      HashSet::from_iter(vec!["cohort".to_string()]),
+    )]));
+
+    let request = |group: &'static str| {
+      let mut headers = HeaderMap::new();
+      headers.insert("x-htsget-authorizer-group", HeaderValue::from_static(group));
+      Request::new("".to_string(), Default::default(), headers)
+    };
+
+    assert!(guard.query_allowed(&Query::new("", Bam, request("cohort"))));
+    assert!(!guard.query_allowed(&Query::new("", Bam, request("other"))));
+    assert!(!guard.query_allowed(&Query::new_with_default_request("", Bam)));
+  }
 }
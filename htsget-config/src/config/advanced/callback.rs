@@ -0,0 +1,531 @@
+//! Resolve ids by asking an HTTP callback endpoint where the data is stored.
+//!
+
+use crate::config::location::LocationEither;
+use crate::storage::{Backend, ResolvedId};
+use crate::tls::client::TlsClientConfig;
+use crate::types::{Class, Format, HtsGetError, Query, Result};
+use http::Uri;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{instrument, trace};
+
+/// A location which resolves ids by POSTing the query to a callback endpoint. The endpoint
+/// responds with the name of one of the `backends` and the key that the id resolves to, or
+/// denies the request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CallbackLocation {
+  callback: Callback,
+  backends: HashMap<String, Backend>,
+  #[serde(skip)]
+  client: Arc<OnceLock<Client>>,
+}
+
+/// Options for calling the callback endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Callback {
+  #[serde(with = "http_serde::uri")]
+  url: Uri,
+  #[serde(default)]
+  forward_headers: bool,
+  #[serde(default = "default_timeout")]
+  timeout: u64,
+  #[serde(skip_serializing, default)]
+  tls: TlsClientConfig,
+}
+
+impl Callback {
+  /// Create new callback options.
+  pub fn new(url: Uri, forward_headers: bool, tls: TlsClientConfig) -> Self {
+    Self {
+      url,
+      forward_headers,
+      timeout: default_timeout(),
+      tls,
+    }
+  }
+
+  /// Set the timeout of callback requests in seconds.
+  pub fn with_timeout(mut self, timeout: u64) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Get the url of the callback endpoint.
+  pub fn url(&self) -> &Uri {
+    &self.url
+  }
+
+  /// Whether headers received in a query request are sent to the callback endpoint.
+  pub fn forward_headers(&self) -> bool {
+    self.forward_headers
+  }
+
+  /// Get the timeout of callback requests in seconds.
+  pub fn timeout(&self) -> u64 {
+    self.timeout
+  }
+
+  /// Get the tls client config.
+  pub fn tls(&self) -> &TlsClientConfig {
+    &self.tls
+  }
+}
+
+fn default_timeout() -> u64 {
+  10
+}
+
+/// The body sent to the callback endpoint.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct CallbackRequest<'a> {
+  id: &'a str,
+  format: Format,
+  class: Class,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  reference_name: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  start: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  end: Option<u32>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  regions: Vec<CallbackRegion<'a>>,
+  headers: HashMap<&'a str, &'a str>,
+}
+
+/// An additional region of the query sent to the callback endpoint.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct CallbackRegion<'a> {
+  reference_name: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  start: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  end: Option<u32>,
+}
+
+/// The decision returned by the callback endpoint.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+enum CallbackResponse {
+  Allow { backend: String, key: String },
+  Deny { message: Option<String> },
+}
+
+impl CallbackLocation {
+  /// Create a new callback location.
+  pub fn new(callback: Callback, backends: HashMap<String, Backend>) -> Self {
+    Self {
+      callback,
+      backends,
+      client: Default::default(),
+    }
+  }
+
+  /// Get the callback options.
+  pub fn callback(&self) -> &Callback {
+    &self.callback
+  }
+
+  /// Get the named backends.
+  pub fn backends(&self) -> &HashMap<String, Backend> {
+    &self.backends
+  }
+
+  /// Ask the callback endpoint where the query id is stored. Returns `None` if the endpoint
+  /// responds with not found, and a permission denied error if it denies the request.
+  #[instrument(level = "trace", skip(self))]
+  pub async fn lookup(&self, query: &Query) -> Result<Option<(&Backend, ResolvedId)>> {
+    let body = serde_json::to_vec(&self.request_body(query))
+      .map_err(|err| HtsGetError::internal_error(format!("serializing callback request: {err}")))?;
+
+    let response = self
+      .client()?
+      .post(self.callback.url().to_string())
+      .header(CONTENT_TYPE, "application/json")
+      .body(body)
+      .send()
+      .await
+      .map_err(|err| HtsGetError::internal_error(format!("callback request failed: {err}")))?;
+
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+      trace!(id = query.id(), "callback returned not found");
+      return Ok(None);
+    }
+    if !status.is_success() {
+      return Err(HtsGetError::internal_error(format!(
+        "callback returned status {status}"
+      )));
+    }
+
+    let body = response
+      .bytes()
+      .await
+      .map_err(|err| HtsGetError::internal_error(format!("reading callback response: {err}")))?;
+    let decision = serde_json::from_slice(&body)
+      .map_err(|err| HtsGetError::internal_error(format!("invalid callback response: {err}")))?;
+
+    self.resolve_decision(decision).map(Some)
+  }
+
+  fn request_body<'a>(&self, query: &'a Query) -> CallbackRequest<'a> {
+    let headers = if self.callback.forward_headers() {
+      query
+        .request()
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .collect()
+    } else {
+      HashMap::new()
+    };
+
+    CallbackRequest {
+      id: query.id(),
+      format: query.format(),
+      class: query.class(),
+      reference_name: query.reference_name(),
+      start: query.interval().start(),
+      end: query.interval().end(),
+      regions: query
+        .regions()
+        .iter()
+        .map(|(reference_name, interval)| CallbackRegion {
+          reference_name,
+          start: interval.start(),
+          end: interval.end(),
+        })
+        .collect(),
+      headers,
+    }
+  }
+
+  fn resolve_decision(&self, decision: CallbackResponse) -> Result<(&Backend, ResolvedId)> {
+    match decision {
+      CallbackResponse::Allow { backend, key } => {
+        let backend = self.backends.get(&backend).ok_or_else(|| {
+          HtsGetError::internal_error(format!("callback returned unknown backend `{backend}`"))
+        })?;
+
+        Ok((backend, ResolvedId::new(key)))
+      }
+      CallbackResponse::Deny { message } => Err(HtsGetError::permission_denied(
+        message.unwrap_or_else(|| "access denied by callback".to_string()),
+      )),
+    }
+  }
+
+  fn client(&self) -> Result<&Client> {
+    if let Some(client) = self.client.get() {
+      return Ok(client);
+    }
+
+    let mut builder = Client::builder().timeout(Duration::from_secs(self.callback.timeout()));
+    let (certs, identity) = self.callback.tls().clone().into_inner();
+    if let Some(certs) = certs {
+      for cert in certs {
+        builder = builder.add_root_certificate(cert);
+      }
+    }
+    if let Some(identity) = identity {
+      builder = builder.identity(identity);
+    }
+
+    let client = builder
+      .build()
+      .map_err(|err| HtsGetError::internal_error(format!("building callback client: {err}")))?;
+
+    Ok(self.client.get_or_init(|| client))
+  }
+}
+
+impl From<CallbackLocation> for LocationEither {
+  fn from(location: CallbackLocation) -> Self {
+    Self::Callback(location)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::test_config_from_file;
+  use crate::config::Config;
+  use crate::types::{Interval, Request};
+  use http::{HeaderMap, HeaderValue};
+  use serde_json::json;
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::TcpListener;
+
+  fn callback_location(forward_headers: bool) -> CallbackLocation {
+    callback_location_with_url(
+      Uri::from_static("http://localhost:8080/resolve"),
+      forward_headers,
+    )
+  }
+
+  fn callback_location_with_url(url: Uri, forward_headers: bool) -> CallbackLocation {
+    CallbackLocation::new(
+      Callback::new(url, forward_headers, Default::default()),
+      HashMap::from_iter([("local".to_string(), Backend::default())]),
+    )
+  }
+
+  /// A stand-in for a callback endpoint which responds to a single request with the status and
+  /// body, or never responds if the status is not set.
+  async fn callback_stand_in(response: Option<(&'static str, &'static str)>) -> Uri {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+      let (mut stream, _) = listener.accept().await.unwrap();
+      let mut request = vec![];
+      let mut buf = [0; 1024];
+      loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+          break;
+        }
+        request.extend_from_slice(&buf[..n]);
+
+        let request = String::from_utf8_lossy(&request).to_lowercase();
+        let Some((head, body)) = request.split_once("\r\n\r\n") else {
+          continue;
+        };
+        let length = head
+          .lines()
+          .find_map(|line| line.strip_prefix("content-length: "))
+          .and_then(|length| length.trim().parse::<usize>().ok())
+          .unwrap_or_default();
+        if body.len() >= length {
+          break;
+        }
+      }
+
+      let Some((status, body)) = response else {
+        return std::future::pending().await;
+      };
+      let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+      );
+      stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    format!("http://{address}/resolve").parse().unwrap()
+  }
+
+  fn query() -> Query {
+    let mut headers = HeaderMap::new();
+    headers.insert("authorization", HeaderValue::from_static("Bearer token"));
+
+    Query::new(
+      "sample",
+      Format::Bam,
+      Request::new("sample".to_string(), Default::default(), headers),
+    )
+    .with_reference_name("chr1")
+    .with_start(1)
+    .with_end(2)
+  }
+
+  #[test]
+  fn config_callback_location() {
+    test_config_from_file(
+      r#"
+      [[locations]]
+      callback.url = "https://catalogue.internal/resolve"
+      callback.forward_headers = false
+
+      backends.local.kind = "File"
+      backends.local.local_path = "data"
+      "#,
+      |config: Config| {
+        let LocationEither::Callback(location) = config.locations().first().unwrap() else {
+          panic!("expected callback location");
+        };
+
+        assert_eq!(
+          location.callback().url(),
+          &Uri::from_static("https://catalogue.internal/resolve")
+        );
+        assert!(!location.callback().forward_headers());
+        assert_eq!(location.callback().timeout(), 10);
+        assert!(matches!(
+          location.backends().get("local").unwrap(),
+          Backend::File(file) if file.local_path() == "data"
+        ));
+      },
+    );
+  }
+
+  #[test]
+  fn callback_request_body() {
+    let query = query();
+
+    assert_eq!(
+      serde_json::to_value(callback_location(true).request_body(&query)).unwrap(),
+      json!({
+        "id": "sample",
+        "format": "BAM",
+        "class": "body",
+        "referenceName": "chr1",
+        "start": 1,
+        "end": 2,
+        "headers": { "authorization": "Bearer token" },
+      })
+    );
+  }
+
+  #[test]
+  fn callback_request_body_with_regions() {
+    let query = query().with_region("chr2", Interval::new(Some(5), None));
+
+    assert_eq!(
+      serde_json::to_value(callback_location(false).request_body(&query)).unwrap(),
+      json!({
+        "id": "sample",
+        "format": "BAM",
+        "class": "body",
+        "referenceName": "chr1",
+        "start": 1,
+        "end": 2,
+        "regions": [{ "referenceName": "chr2", "start": 5 }],
+        "headers": {},
+      })
+    );
+  }
+
+  #[test]
+  fn callback_request_body_no_headers() {
+    let query = query();
+
+    assert!(callback_location(false)
+      .request_body(&query)
+      .headers
+      .is_empty());
+  }
+
+  #[test]
+  fn callback_allow() {
+    let location = callback_location(true);
+    let decision = serde_json::from_value(json!({
+      "decision": "allow",
+      "backend": "local",
+      "key": "bam/htsnexus_test_NA12878",
+    }))
+    .unwrap();
+    let (backend, id) = location.resolve_decision(decision).unwrap();
+
+    assert!(matches!(backend, Backend::File(_)));
+    assert_eq!(id.into_inner(), "bam/htsnexus_test_NA12878");
+  }
+
+  #[test]
+  fn callback_deny() {
+    let location = callback_location(true);
+    let decision = serde_json::from_value(json!({
+      "decision": "deny",
+      "message": "not in cohort",
+    }))
+    .unwrap();
+
+    assert_eq!(
+      location.resolve_decision(decision).unwrap_err(),
+      HtsGetError::permission_denied("not in cohort")
+    );
+  }
+
+  #[test]
+  fn callback_unknown_backend() {
+    let location = callback_location(true);
+    let decision = serde_json::from_value(json!({
+      "decision": "allow",
+      "backend": "unknown",
+      "key": "key",
+    }))
+    .unwrap();
+
+    assert!(location.resolve_decision(decision).is_err());
+  }
+
+  #[test]
+  fn callback_defaults() {
+    let callback: Callback =
+      serde_json::from_value(json!({ "url": "https://catalogue.internal/resolve" })).unwrap();
+
+    assert!(!callback.forward_headers());
+    assert_eq!(callback.timeout(), 10);
+  }
+
+  #[tokio::test]
+  async fn lookup_allow() {
+    let url = callback_stand_in(Some((
+      "200 OK",
+      r#"{ "decision": "allow", "backend": "local", "key": "bam/htsnexus_test_NA12878" }"#,
+    )))
+    .await;
+    let location = callback_location_with_url(url, false);
+    let (backend, id) = location.lookup(&query()).await.unwrap().unwrap();
+
+    assert!(matches!(backend, Backend::File(_)));
+    assert_eq!(id.into_inner(), "bam/htsnexus_test_NA12878");
+  }
+
+  #[tokio::test]
+  async fn lookup_deny() {
+    let url = callback_stand_in(Some((
+      "200 OK",
+      r#"{ "decision": "deny", "message": "not in cohort" }"#,
+    )))
+    .await;
+    let location = callback_location_with_url(url, false);
+
+    assert_eq!(
+      location.lookup(&query()).await.unwrap_err(),
+      HtsGetError::permission_denied("not in cohort")
+    );
+  }
+
+  #[tokio::test]
+  async fn lookup_not_found() {
+    let url = callback_stand_in(Some(("404 Not Found", "{}"))).await;
+    let location = callback_location_with_url(url, false);
+
+    assert!(location.lookup(&query()).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn lookup_error_status() {
+    let url = callback_stand_in(Some(("500 Internal Server Error", "{}"))).await;
+    let location = callback_location_with_url(url, false);
+
+    assert!(matches!(
+      location.lookup(&query()).await.unwrap_err(),
+      HtsGetError::InternalError(_)
+    ));
+  }
+
+  #[tokio::test]
+  async fn lookup_timeout() {
+    let url = callback_stand_in(None).await;
+    let location = CallbackLocation::new(
+      Callback::new(url, false, Default::default()).with_timeout(1),
+      HashMap::from_iter([("local".to_string(), Backend::default())]),
+    );
+
+    assert!(matches!(
+      location.lookup(&query()).await.unwrap_err(),
+      HtsGetError::InternalError(_)
+    ));
+  }
+}
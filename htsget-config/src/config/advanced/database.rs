@@ -0,0 +1,396 @@
+//! Resolve ids by looking them up in a SQLite or Postgres database.
+//!
+
+use crate::config::advanced::allow_guard::{AllowGuard, QueryAllowed};
+use crate::config::location::LocationEither;
+use crate::storage::{Backend, ResolvedId};
+use crate::types::{HtsGetError, Query, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::any::{install_default_drivers, AnyPoolOptions};
+use sqlx::AnyPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{instrument, trace};
+
+/// A location which resolves ids using a database query. The query is passed the id as its only
+/// parameter, and should return a row containing the name of one of the `backends` and the key
+/// that the id resolves to. Only ids which match the `regex` and queries which are allowed by the
+/// `guard` are looked up.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DatabaseLocation {
+  #[serde(with = "serde_regex", default = "default_regex")]
+  regex: Regex,
+  #[serde(default)]
+  guard: Option<AllowGuard>,
+  database: Database,
+  backends: HashMap<String, Backend>,
+  #[serde(skip)]
+  state: DatabaseState,
+}
+
+/// Options for connecting to and querying the database.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Database {
+  url: String,
+  query: String,
+  #[serde(default = "default_cache_ttl")]
+  cache_ttl: u64,
+  #[serde(default = "default_cache_max_entries")]
+  cache_max_entries: usize,
+}
+
+impl Database {
+  /// Create new database options.
+  pub fn new(url: String, query: String, cache_ttl: u64, cache_max_entries: usize) -> Self {
+    Self {
+      url,
+      query,
+      cache_ttl,
+      cache_max_entries,
+    }
+  }
+
+  /// Get the database url.
+  pub fn url(&self) -> &str {
+    &self.url
+  }
+
+  /// Get the query used to look up ids.
+  pub fn query(&self) -> &str {
+    &self.query
+  }
+
+  /// Get the cache TTL in seconds.
+  pub fn cache_ttl(&self) -> u64 {
+    self.cache_ttl
+  }
+
+  /// Get the maximum number of cached lookups.
+  pub fn cache_max_entries(&self) -> usize {
+    self.cache_max_entries
+  }
+}
+
+fn default_regex() -> Regex {
+  ".*".parse().expect("expected valid regex")
+}
+
+fn default_cache_ttl() -> u64 {
+  300
+}
+
+fn default_cache_max_entries() -> usize {
+  100_000
+}
+
+/// A cached lookup, where `None` represents an id that does not exist in the database.
+#[derive(Debug, Clone)]
+struct CacheEntry {
+  value: Option<(String, String)>,
+  expires_at: Instant,
+}
+
+/// The connection pool and lookup cache, shared between clones of the location.
+#[derive(Debug, Clone, Default)]
+struct DatabaseState {
+  pool: Arc<OnceLock<AnyPool>>,
+  cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+}
+
+impl DatabaseLocation {
+  /// Create a new database location.
+  pub fn new(database: Database, backends: HashMap<String, Backend>) -> Self {
+    Self {
+      regex: default_regex(),
+      guard: None,
+      database,
+      backends,
+      state: Default::default(),
+    }
+  }
+
+  /// Set the regex that ids must match to be looked up.
+  pub fn with_regex(mut self, regex: Regex) -> Self {
+    self.regex = regex;
+    self
+  }
+
+  /// Set the allow guard.
+  pub fn with_guard(mut self, guard: AllowGuard) -> Self {
+    self.guard = Some(guard);
+    self
+  }
+
+  /// Get the regex that ids must match to be looked up.
+  pub fn regex(&self) -> &Regex {
+    &self.regex
+  }
+
+  /// Get the allow guard.
+  pub fn guard(&self) -> Option<&AllowGuard> {
+    self.guard.as_ref()
+  }
+
+  /// Get the database options.
+  pub fn database(&self) -> &Database {
+    &self.database
+  }
+
+  /// Get the named backends.
+  pub fn backends(&self) -> &HashMap<String, Backend> {
+    &self.backends
+  }
+
+  /// Get the named backends as mutable.
+  pub fn backends_mut(&mut self) -> &mut HashMap<String, Backend> {
+    &mut self.backends
+  }
+
+  /// Whether the query should be looked up in the database. The id must match the regex, and the
+  /// query must be allowed by the guard if it is set.
+  pub fn query_matches(&self, query: &Query) -> bool {
+    self.regex.is_match(query.id())
+      && self
+        .guard
+        .as_ref()
+        .is_none_or(|guard| guard.query_allowed(query))
+  }
+
+  /// Look up the id, returning the backend and resolved id if it exists in the database.
+  #[instrument(level = "trace", skip(self))]
+  pub async fn lookup(&self, id: &str) -> Result<Option<(&Backend, ResolvedId)>> {
+    let value = match self.cached(id) {
+      Some(value) => value,
+      None => {
+        let value = sqlx::query_as::<_, (String, String)>(self.database.query())
+          .bind(id)
+          .fetch_optional(self.pool()?)
+          .await
+          .map_err(|err| HtsGetError::internal_error(format!("database lookup failed: {err}")))?;
+        self.cache(id, value.clone());
+
+        value
+      }
+    };
+
+    value
+      .map(|(backend, key)| {
+        let backend = self.backends.get(&backend).ok_or_else(|| {
+          HtsGetError::internal_error(format!("database returned unknown backend `{backend}`"))
+        })?;
+
+        Ok((backend, ResolvedId::new(key)))
+      })
+      .transpose()
+  }
+
+  fn pool(&self) -> Result<&AnyPool> {
+    if let Some(pool) = self.state.pool.get() {
+      return Ok(pool);
+    }
+
+    install_default_drivers();
+    let pool = AnyPoolOptions::new()
+      .connect_lazy(self.database.url())
+      .map_err(|err| HtsGetError::internal_error(format!("invalid database url: {err}")))?;
+
+    Ok(self.state.pool.get_or_init(|| pool))
+  }
+
+  fn cached(&self, id: &str) -> Option<Option<(String, String)>> {
+    let cache = self.state.cache.lock().ok()?;
+    let entry = cache.get(id)?;
+
+    if entry.expires_at > Instant::now() {
+      trace!(id, "database lookup cache hit");
+      Some(entry.value.clone())
+    } else {
+      None
+    }
+  }
+
+  fn cache(&self, id: &str, value: Option<(String, String)>) {
+    if self.database.cache_ttl() == 0 {
+      return;
+    }
+
+    let Ok(mut cache) = self.state.cache.lock() else {
+      return;
+    };
+
+    let now = Instant::now();
+    if cache.len() >= self.database.cache_max_entries() {
+      cache.retain(|_, entry| entry.expires_at > now);
+    }
+    if cache.len() >= self.database.cache_max_entries() {
+      return;
+    }
+
+    cache.insert(
+      id.to_string(),
+      CacheEntry {
+        value,
+        expires_at: now + Duration::from_secs(self.database.cache_ttl()),
+      },
+    );
+  }
+}
+
+impl From<DatabaseLocation> for LocationEither {
+  fn from(location: DatabaseLocation) -> Self {
+    Self::Database(location)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::test_config_from_file;
+  use crate::config::Config;
+  use crate::types::Format;
+  use http::uri::Authority;
+
+  const CATALOGUE: &str = "sqlite://../data/database/catalogue.db?mode=ro";
+
+  fn database_location(cache_ttl: u64) -> DatabaseLocation {
+    DatabaseLocation::new(
+      Database::new(
+        CATALOGUE.to_string(),
+        "SELECT backend, key FROM samples WHERE id = $1".to_string(),
+        cache_ttl,
+        default_cache_max_entries(),
+      ),
+      HashMap::from_iter([("local".to_string(), Backend::default())]),
+    )
+  }
+
+  #[test]
+  fn config_database_location() {
+    test_config_from_file(
+      &format!(
+        r#"
+        [[locations]]
+        database.url = "{CATALOGUE}"
+        database.query = "SELECT backend, key FROM samples WHERE id = $1"
+        database.cache_ttl = 10
+
+        backends.local.kind = "File"
+        backends.local.local_path = "data"
+        "#
+      ),
+      |config: Config| {
+        let LocationEither::Database(location) = config.locations().first().unwrap() else {
+          panic!("expected database location");
+        };
+
+        assert_eq!(location.database().url(), CATALOGUE);
+        assert_eq!(location.database().cache_ttl(), 10);
+        assert_eq!(
+          location.database().cache_max_entries(),
+          default_cache_max_entries()
+        );
+        assert!(matches!(
+          location.backends().get("local").unwrap(),
+          Backend::File(file) if file.local_path() == "data"
+        ));
+      },
+    );
+  }
+
+  #[test]
+  fn config_database_location_matcher() {
+    test_config_from_file(
+      &format!(
+        r#"
+        [[locations]]
+        regex = "^sample-"
+        guard.allow_formats = ["BAM"]
+        database.url = "{CATALOGUE}"
+        database.query = "SELECT backend, key FROM samples WHERE id = $1"
+
+        backends.local.kind = "File"
+        "#
+      ),
+      |config: Config| {
+        let LocationEither::Database(location) = config.locations().first().unwrap() else {
+          panic!("expected database location");
+        };
+
+        assert_eq!(location.regex().as_str(), "^sample-");
+        assert_eq!(location.guard().unwrap().allow_formats(), &[Format::Bam]);
+      },
+    );
+  }
+
+  #[test]
+  fn config_database_location_data_server() {
+    test_config_from_file(
+      &format!(
+        r#"
+        data_server.addr = "127.0.0.1:8000"
+
+        [[locations]]
+        database.url = "{CATALOGUE}"
+        database.query = "SELECT backend, key FROM samples WHERE id = $1"
+
+        backends.local.kind = "File"
+        backends.local.local_path = "data"
+        "#
+      ),
+      |config: Config| {
+        let LocationEither::Database(location) = config.locations().first().unwrap() else {
+          panic!("expected database location");
+        };
+
+        assert!(matches!(
+          location.backends().get("local").unwrap(),
+          Backend::File(file) if file.local_path() == "data"
+            && file.authority() == &Authority::from_static("127.0.0.1:8000")
+        ));
+      },
+    );
+  }
+
+  #[tokio::test]
+  async fn lookup_existing_id() {
+    let location = database_location(default_cache_ttl());
+    let (backend, id) = location.lookup("sample-bam").await.unwrap().unwrap();
+
+    assert!(matches!(backend, Backend::File(_)));
+    assert_eq!(id.into_inner(), "bam/htsnexus_test_NA12878");
+    assert_eq!(
+      location.cached("sample-bam"),
+      Some(Some((
+        "local".to_string(),
+        "bam/htsnexus_test_NA12878".to_string()
+      )))
+    );
+  }
+
+  #[tokio::test]
+  async fn lookup_missing_id() {
+    let location = database_location(default_cache_ttl());
+
+    assert!(location.lookup("missing").await.unwrap().is_none());
+    assert_eq!(location.cached("missing"), Some(None));
+  }
+
+  #[tokio::test]
+  async fn lookup_unknown_backend() {
+    let location = database_location(default_cache_ttl());
+
+    assert!(location.lookup("sample-unknown-backend").await.is_err());
+  }
+
+  #[tokio::test]
+  async fn lookup_no_cache() {
+    let location = database_location(0);
+    location.lookup("sample-bam").await.unwrap();
+
+    assert!(location.cached("sample-bam").is_none());
+  }
+}
@@ -4,7 +4,11 @@
 use serde::{Deserialize, Serialize};
 
 pub mod allow_guard;
+#[cfg(feature = "url")]
+pub mod callback;
 pub mod cors;
+#[cfg(feature = "database")]
+pub mod database;
 pub mod regex_location;
 #[cfg(feature = "url")]
 pub mod url;
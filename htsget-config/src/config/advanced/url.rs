@@ -7,8 +7,8 @@ use crate::error::Result;
 use crate::storage;
 #[cfg(feature = "experimental")]
 use crate::storage::c4gh::C4GHKeys;
+use crate::storage::options::LocationOptions;
 use crate::tls::client::TlsClientConfig;
-use cfg_if::cfg_if;
 use http::Uri;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -27,6 +27,8 @@ pub struct Url {
   header_blacklist: Vec<String>,
   #[serde(skip_serializing, default)]
   tls: TlsClientConfig,
+  #[serde(flatten)]
+  options: LocationOptions,
   #[cfg(feature = "experimental")]
   #[serde(skip_serializing, default)]
   keys: Option<C4GHKeys>,
@@ -47,6 +49,7 @@ impl Url {
       forward_headers,
       header_blacklist,
       tls,
+      options: Default::default(),
       #[cfg(feature = "experimental")]
       keys: None,
     }
@@ -73,6 +76,11 @@ impl Url {
     &self.tls
   }
 
+  /// Get the location options.
+  pub fn options(&self) -> &LocationOptions {
+    &self.options
+  }
+
   #[cfg(feature = "experimental")]
   /// Set the C4GH keys.
   pub fn set_keys(mut self, keys: Option<C4GHKeys>) -> Self {
@@ -108,23 +116,18 @@ impl TryFrom<Url> for storage::url::Url {
       .build()
       .map_err(|err| ParseError(format!("building url storage client: {}", err)))?;
 
-    let url_storage = Self::new(
+    let mut url_storage = Self::new(
       storage.url.clone(),
       storage.response_url.unwrap_or(storage.url),
       storage.forward_headers,
       storage.header_blacklist,
       client,
     );
+    url_storage.set_options(storage.options);
+    #[cfg(feature = "experimental")]
+    url_storage.set_keys(storage.keys);
 
-    cfg_if! {
-      if #[cfg(feature = "experimental")] {
-        let mut url_storage = url_storage;
-        url_storage.set_keys(storage.keys);
-        Ok(url_storage)
-      } else {
-        Ok(url_storage)
-      }
-    }
+    Ok(url_storage)
   }
 }
 
@@ -3,11 +3,17 @@
 
 use crate::config::advanced::cors::CorsConfig;
 use crate::error::{Error::ParseError, Result};
+#[cfg(feature = "experimental")]
+use crate::storage::c4gh::C4GHKeys;
 use crate::storage::file::{default_localstorage_addr, default_path};
 use crate::tls::TlsServerConfig;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "experimental")]
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "experimental")]
+use std::str::FromStr;
 
 /// Tagged allow headers for cors config, either Mirror or Any.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -46,6 +52,9 @@ pub struct DataServerConfig {
   #[serde(skip_serializing)]
   tls: Option<TlsServerConfig>,
   cors: CorsConfig,
+  #[cfg(feature = "experimental")]
+  #[serde(skip_serializing)]
+  decrypt: Option<DataServerDecrypt>,
 }
 
 impl DataServerConfig {
@@ -61,6 +70,8 @@ impl DataServerConfig {
       local_path,
       tls,
       cors,
+      #[cfg(feature = "experimental")]
+      decrypt: None,
     }
   }
 
@@ -88,6 +99,143 @@ impl DataServerConfig {
   pub fn into_tls(self) -> Option<TlsServerConfig> {
     self.tls
   }
+
+  /// Get the C4GH decryption config.
+  #[cfg(feature = "experimental")]
+  pub fn decrypt(&self) -> Option<&DataServerDecrypt> {
+    self.decrypt.as_ref()
+  }
+
+  /// Set the C4GH decryption config.
+  #[cfg(feature = "experimental")]
+  pub fn set_decrypt(&mut self, decrypt: Option<DataServerDecrypt>) {
+    self.decrypt = decrypt;
+  }
+}
+
+/// Decrypt C4GH objects on the fly for trusted clients. Clients that connect from any of the
+/// trusted networks receive plaintext data, and all other clients receive the encrypted objects.
+/// Clients are identified by the IP address of their connection rather than forwarding headers.
+#[cfg(feature = "experimental")]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DataServerDecrypt {
+  keys: C4GHKeys,
+  trusted_clients: Vec<TrustedNetwork>,
+}
+
+#[cfg(feature = "experimental")]
+impl DataServerDecrypt {
+  /// Create a new decryption config.
+  pub fn new(keys: C4GHKeys, trusted_clients: Vec<TrustedNetwork>) -> Self {
+    Self {
+      keys,
+      trusted_clients,
+    }
+  }
+
+  /// Get the C4GH keys.
+  pub fn keys(&self) -> &C4GHKeys {
+    &self.keys
+  }
+
+  /// Get the trusted client networks.
+  pub fn trusted_clients(&self) -> &[TrustedNetwork] {
+    self.trusted_clients.as_slice()
+  }
+
+  /// Check whether a client address is trusted.
+  pub fn is_trusted(&self, addr: IpAddr) -> bool {
+    self
+      .trusted_clients
+      .iter()
+      .any(|network| network.contains(addr))
+  }
+}
+
+/// A network of trusted clients, written as an IP address or in CIDR notation,
+/// e.g. `10.0.0.0/8` or `::1`.
+#[cfg(feature = "experimental")]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct TrustedNetwork {
+  addr: IpAddr,
+  prefix_len: u8,
+}
+
+#[cfg(feature = "experimental")]
+impl TrustedNetwork {
+  /// Create a new network, returning an error if the prefix length is too long for the address.
+  pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self> {
+    let max_len = Self::max_prefix_len(addr);
+    if prefix_len > max_len {
+      return Err(ParseError(format!(
+        "prefix length `{prefix_len}` is longer than `{max_len}` for `{addr}`"
+      )));
+    }
+
+    Ok(Self { addr, prefix_len })
+  }
+
+  /// Check whether the network contains the address. IPv4-mapped IPv6 addresses are
+  /// treated as IPv4 addresses.
+  pub fn contains(&self, addr: IpAddr) -> bool {
+    match (self.addr.to_canonical(), addr.to_canonical()) {
+      (IpAddr::V4(network), IpAddr::V4(addr)) => Self::prefix_matches(
+        network.to_bits().into(),
+        addr.to_bits().into(),
+        32,
+        self.prefix_len,
+      ),
+      (IpAddr::V6(network), IpAddr::V6(addr)) => {
+        Self::prefix_matches(network.to_bits(), addr.to_bits(), 128, self.prefix_len)
+      }
+      _ => false,
+    }
+  }
+
+  fn prefix_matches(network: u128, addr: u128, bits: u8, prefix_len: u8) -> bool {
+    let shift = bits - prefix_len.min(bits);
+    shift == bits || network >> shift == addr >> shift
+  }
+
+  fn max_prefix_len(addr: IpAddr) -> u8 {
+    match addr.to_canonical() {
+      IpAddr::V4(_) => 32,
+      IpAddr::V6(_) => 128,
+    }
+  }
+}
+
+#[cfg(feature = "experimental")]
+impl FromStr for TrustedNetwork {
+  type Err = crate::error::Error;
+
+  fn from_str(network: &str) -> Result<Self> {
+    let invalid = |err: &dyn std::fmt::Display| {
+      ParseError(format!("invalid trusted network `{network}`: {err}"))
+    };
+
+    match network.split_once('/') {
+      Some((addr, prefix_len)) => Self::new(
+        addr.parse().map_err(|err| invalid(&err))?,
+        prefix_len.parse().map_err(|err| invalid(&err))?,
+      ),
+      None => {
+        let addr = network.parse().map_err(|err| invalid(&err))?;
+        Self::new(addr, Self::max_prefix_len(addr))
+      }
+    }
+  }
+}
+
+#[cfg(feature = "experimental")]
+impl TryFrom<String> for TrustedNetwork {
+  type Error = crate::error::Error;
+
+  fn try_from(network: String) -> Result<Self> {
+    network.parse()
+  }
 }
 
 impl Default for DataServerConfig {
@@ -99,6 +247,8 @@ impl Default for DataServerConfig {
       local_path: default_path().into(),
       tls: Default::default(),
       cors: Default::default(),
+      #[cfg(feature = "experimental")]
+      decrypt: None,
     }
   }
 }
@@ -126,4 +276,67 @@ mod tests {
       },
     );
   }
+
+  #[cfg(feature = "experimental")]
+  #[test]
+  fn trusted_network_contains() {
+    let network: TrustedNetwork = "10.1.0.0/16".parse().unwrap();
+
+    assert!(network.contains("10.1.2.3".parse().unwrap()));
+    assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+    assert!(!network.contains("10.2.0.1".parse().unwrap()));
+    assert!(!network.contains("::1".parse().unwrap()));
+  }
+
+  #[cfg(feature = "experimental")]
+  #[test]
+  fn trusted_network_single_address() {
+    let network: TrustedNetwork = "::1".parse().unwrap();
+
+    assert!(network.contains("::1".parse().unwrap()));
+    assert!(!network.contains("::2".parse().unwrap()));
+  }
+
+  #[cfg(feature = "experimental")]
+  #[test]
+  fn trusted_network_any() {
+    let network: TrustedNetwork = "0.0.0.0/0".parse().unwrap();
+
+    assert!(network.contains("192.168.0.1".parse().unwrap()));
+  }
+
+  #[cfg(feature = "experimental")]
+  #[test]
+  fn trusted_network_invalid_prefix() {
+    assert!(matches!(
+      "127.0.0.1/33".parse::<TrustedNetwork>(),
+      Err(ParseError(_))
+    ));
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn data_server_decrypt() {
+    let keys = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys");
+
+    let config: DataServerConfig = toml::from_str(&format!(
+      r#"
+      decrypt.trusted_clients = ["127.0.0.1", "10.0.0.0/8"]
+      decrypt.keys.kind = "File"
+      decrypt.keys.private = "{}"
+      decrypt.keys.public = "{}"
+      "#,
+      keys.join("bob.sec").to_string_lossy(),
+      keys.join("alice.pub").to_string_lossy()
+    ))
+    .unwrap();
+    let decrypt = config.decrypt().unwrap();
+
+    assert!(decrypt.is_trusted("10.0.0.1".parse().unwrap()));
+    assert!(!decrypt.is_trusted("192.168.0.1".parse().unwrap()));
+    assert_eq!(decrypt.keys().clone().keys().await.unwrap().len(), 1);
+  }
 }
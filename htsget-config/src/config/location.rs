@@ -1,15 +1,19 @@
 //! Storage location configuration.
 //!
 
+#[cfg(feature = "url")]
+use crate::config::advanced::callback::CallbackLocation;
+#[cfg(feature = "database")]
+use crate::config::advanced::database::DatabaseLocation;
 use crate::config::advanced::regex_location::RegexLocation;
 use crate::error::{Error::ParseError, Result};
 use crate::storage;
 #[cfg(feature = "experimental")]
 use crate::storage::c4gh::C4GHKeys;
 use crate::storage::file::default_authority;
+use crate::storage::options::LocationOptions;
 use crate::storage::Backend;
 use crate::types::Scheme;
-use cfg_if::cfg_if;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::result;
@@ -49,20 +53,28 @@ impl Default for Locations {
   }
 }
 
-/// Either simple or regex based location
+/// Either simple, regex, database or callback based location
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged, deny_unknown_fields)]
 pub enum LocationEither {
   Simple(Location),
   Regex(RegexLocation),
+  #[cfg(feature = "database")]
+  Database(DatabaseLocation),
+  #[cfg(feature = "url")]
+  Callback(CallbackLocation),
 }
 
 impl LocationEither {
-  /// Get the storage backend.
-  pub fn backend(&self) -> &Backend {
+  /// Get the storage backend. Returns `None` if the backend is only known after resolving an id.
+  pub fn backend(&self) -> Option<&Backend> {
     match self {
-      LocationEither::Simple(location) => location.backend(),
-      LocationEither::Regex(regex_location) => regex_location.backend(),
+      LocationEither::Simple(location) => Some(location.backend()),
+      LocationEither::Regex(regex_location) => Some(regex_location.backend()),
+      #[cfg(feature = "database")]
+      LocationEither::Database(_) => None,
+      #[cfg(feature = "url")]
+      LocationEither::Callback(_) => None,
     }
   }
 
@@ -139,6 +151,8 @@ impl From<LocationsOneOrMany> for Locations {
 #[serde(default, deny_unknown_fields)]
 struct ExtendedLocation {
   location: StringLocation,
+  #[serde(flatten)]
+  options: LocationOptions,
   #[cfg(feature = "experimental")]
   #[serde(skip_serializing)]
   keys: Option<C4GHKeys>,
@@ -182,15 +196,12 @@ impl From<LocationWrapper> for Location {
       LocationWrapper::String(location) => Location::new(location.backend, location.prefix),
       LocationWrapper::Map(location) => Location::new(location.backend, location.prefix),
       LocationWrapper::Extended(location) => {
-        cfg_if! {
-          if #[cfg(feature = "experimental")] {
-            let mut location = location;
-            location.location.backend.set_keys(location.keys);
-            Location::new(location.location.backend, location.location.prefix)
-          } else {
-            Location::new(location.location.backend, location.location.prefix)
-          }
-        }
+        let mut backend = location.location.backend;
+        backend.set_options(location.options);
+        #[cfg(feature = "experimental")]
+        backend.set_keys(location.keys);
+
+        Location::new(backend, location.location.prefix)
       }
     }
   }
@@ -285,6 +296,9 @@ mod tests {
   use super::*;
   use crate::config::tests::test_serialize_and_deserialize;
   use crate::config::Config;
+  use crate::storage::coalesce::Coalesce;
+  use crate::types::Format;
+  use std::path::PathBuf;
 
   #[test]
   fn location_single() {
@@ -304,6 +318,90 @@ mod tests {
     );
   }
 
+  #[test]
+  fn location_naming() {
+    test_serialize_and_deserialize(
+      r#"
+      [[locations]]
+      location = "file://path/prefix1"
+      naming.bam.index = ["{id}.bai"]
+      "#,
+      vec!["id.bai".to_string()],
+      |result: Config| {
+        result.locations()[0]
+          .as_simple()
+          .unwrap()
+          .backend()
+          .options()
+          .naming()
+          .index_keys(Format::Bam, "id")
+      },
+    );
+  }
+
+  #[test]
+  fn location_index_cache() {
+    test_serialize_and_deserialize(
+      r#"
+      [[locations]]
+      location = "file://path/prefix1"
+      index_cache.local_path = "/tmp/htsget/indexes"
+      "#,
+      Some(PathBuf::from("/tmp/htsget/indexes")),
+      |result: Config| {
+        result.locations()[0]
+          .as_simple()
+          .unwrap()
+          .backend()
+          .options()
+          .index_cache()
+          .map(|cache| cache.local_path().to_path_buf())
+      },
+    );
+  }
+
+  #[test]
+  fn location_coalesce() {
+    test_serialize_and_deserialize(
+      r#"
+      [[locations]]
+      location = "file://path/prefix1"
+      coalesce.max_gap_bytes = 65536
+      coalesce.max_urls = 10
+      "#,
+      Some(Coalesce::new(65536, Some(10))),
+      |result: Config| {
+        result.locations()[0]
+          .as_simple()
+          .unwrap()
+          .backend()
+          .options()
+          .coalesce()
+          .cloned()
+      },
+    );
+  }
+
+  #[test]
+  fn location_inline_threshold() {
+    test_serialize_and_deserialize(
+      r#"
+      [[locations]]
+      location = "file://path/prefix1"
+      inline_threshold = 1024
+      "#,
+      Some(1024),
+      |result: Config| {
+        result.locations()[0]
+          .as_simple()
+          .unwrap()
+          .backend()
+          .options()
+          .inline_threshold()
+      },
+    );
+  }
+
   #[test]
   fn location_no_prefix() {
     test_serialize_and_deserialize(
@@ -6,10 +6,11 @@ use std::io;
 use std::path::{Path, PathBuf};
 
 use crate::config::advanced::FormattingStyle;
-use crate::config::data_server::DataServerEnabled;
+use crate::config::data_server::{DataServerConfig, DataServerEnabled};
 use crate::config::location::{Location, LocationEither, Locations};
 use crate::config::parser::from_path;
-use crate::config::service_info::ServiceInfo;
+use crate::config::service_info::{PackageInfo, ServiceInfo};
+use crate::config::services::Services;
 use crate::config::ticket_server::TicketServerConfig;
 use crate::error::Error::{ArgParseError, ParseError, TracingError};
 use crate::error::Result;
@@ -27,6 +28,7 @@ pub mod data_server;
 pub mod location;
 pub mod parser;
 pub mod service_info;
+pub mod services;
 pub mod ticket_server;
 
 /// The usage string for htsget-rs.
@@ -56,6 +58,8 @@ pub struct Config {
   data_server: DataServerEnabled,
   service_info: ServiceInfo,
   locations: Locations,
+  #[serde(skip_serializing_if = "Services::is_empty")]
+  services: Services,
   formatting_style: FormattingStyle,
 }
 
@@ -74,9 +78,16 @@ impl Config {
       data_server,
       service_info,
       locations,
+      services: Default::default(),
     }
   }
 
+  /// Set the services mounted under path prefixes.
+  pub fn with_services(mut self, services: Services) -> Self {
+    self.services = services;
+    self
+  }
+
   /// Get the ticket server config.
   pub fn formatting_style(&self) -> FormattingStyle {
     self.formatting_style
@@ -102,11 +113,28 @@ impl Config {
     &mut self.service_info
   }
 
+  /// Set the service info fields from the package info, for the top-level service info and any
+  /// services which specify their own service info.
+  pub fn set_from_package_info(&mut self, info: PackageInfo) -> Result<()> {
+    for service in self.services.as_mut_slice() {
+      if let Some(service_info) = service.service_info_mut() {
+        service_info.set_from_package_info(info.clone())?;
+      }
+    }
+
+    self.service_info.set_from_package_info(info)
+  }
+
   /// Get the location.
   pub fn locations(&self) -> &[LocationEither] {
     self.locations.as_slice()
   }
 
+  /// Get the services mounted under path prefixes.
+  pub fn services(&self) -> &Services {
+    &self.services
+  }
+
   pub fn into_locations(self) -> Locations {
     self.locations
   }
@@ -172,11 +200,33 @@ impl Config {
 
   /// Set the local resolvers from the data server config.
   pub fn resolvers_from_data_server_config(mut self) -> Result<Self> {
-    self
-      .locations
+    Self::locations_from_data_server_config(&mut self.locations, &self.data_server)?;
+    for service in self.services.as_mut_slice() {
+      Self::locations_from_data_server_config(service.locations_mut(), &self.data_server)?;
+    }
+
+    Ok(self)
+  }
+
+  fn locations_from_data_server_config(
+    locations: &mut Locations,
+    data_server: &DataServerEnabled,
+  ) -> Result<()> {
+    locations
       .as_mut_slice()
       .iter_mut()
       .map(|location| {
+        #[cfg(feature = "database")]
+        if let (LocationEither::Database(database), DataServerEnabled::Some(data_server)) =
+          (&mut *location, data_server)
+        {
+          for backend in database.backends_mut().values_mut() {
+            if let Backend::File(file_location) = backend {
+              *file_location = Self::file_from_data_server(file_location, data_server)?;
+            }
+          }
+        }
+
         if let LocationEither::Simple(simple) = location {
           // Fall through only if the backend is File and default
           let file_location = if let Ok(location) = simple.backend().as_file() {
@@ -185,12 +235,10 @@ impl Config {
             return Ok(());
           };
 
-          if let DataServerEnabled::Some(ref data_server) = self.data_server {
+          if let DataServerEnabled::Some(data_server) = data_server {
             let prefix = simple.prefix().to_string();
 
-            // Don't update the local path as that comes in from the config.
-            let file: File = data_server.try_into()?;
-            let file = file.set_local_path(file_location.local_path().to_string());
+            let file = Self::file_from_data_server(file_location, data_server)?;
 
             *location = LocationEither::Simple(Location::new(Backend::File(file), prefix));
           }
@@ -200,7 +248,31 @@ impl Config {
       })
       .collect::<Result<Vec<()>>>()?;
 
-    Ok(self)
+    Ok(())
+  }
+
+  /// Point a File backend at the data server, keeping the fields that come in from the location.
+  fn file_from_data_server(file_location: &File, data_server: &DataServerConfig) -> Result<File> {
+    // Don't update the local path as that comes in from the config.
+    let file: File = data_server.try_into()?;
+    let mut file = file.set_local_path(file_location.local_path().to_string());
+    file.set_options(file_location.options().clone());
+
+    #[cfg(feature = "experimental")]
+    {
+      // The ticket server needs keys to read encrypted objects, so fall back to the data
+      // server's keys if the location doesn't have any.
+      file.set_keys(
+        file_location
+          .keys()
+          .or_else(|| data_server.decrypt().map(|decrypt| decrypt.keys()))
+          .cloned(),
+      );
+      // A data server which decrypts serves plaintext keys, so tickets should point at them.
+      file.set_plaintext_urls(data_server.decrypt().is_some());
+    }
+
+    Ok(file)
   }
 }
 
@@ -212,6 +284,7 @@ impl Default for Config {
       data_server: DataServerEnabled::Some(Default::default()),
       service_info: Default::default(),
       locations: Default::default(),
+      services: Default::default(),
     }
   }
 }
@@ -545,6 +618,42 @@ pub(crate) mod tests {
     );
   }
 
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn locations_from_data_server_decrypt() {
+    let keys = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys");
+
+    let config = from_str::<Config>(&format!(
+      r#"
+    data_server.addr = "127.0.0.1:8080"
+    data_server.decrypt.trusted_clients = ["127.0.0.1"]
+    data_server.decrypt.keys.kind = "File"
+    data_server.decrypt.keys.private = "{}"
+    data_server.decrypt.keys.public = "{}"
+
+    locations = "file://data"
+    "#,
+      keys.join("bob.sec").to_string_lossy(),
+      keys.join("alice.pub").to_string_lossy()
+    ))
+    .unwrap()
+    .resolvers_from_data_server_config()
+    .unwrap();
+
+    let locations = config.locations.into_inner();
+    let file = locations[0]
+      .as_simple()
+      .unwrap()
+      .backend()
+      .as_file()
+      .unwrap();
+    assert!(file.plaintext_urls());
+    assert_eq!(file.keys().unwrap().clone().keys().await.unwrap().len(), 1);
+  }
+
   #[test]
   fn simple_locations_env() {
     test_config_from_env(
@@ -0,0 +1,302 @@
+//! Configuration for additional ticket services mounted under path prefixes.
+//!
+
+use crate::config::advanced::cors::CorsConfig;
+use crate::config::location::Locations;
+use crate::config::service_info::ServiceInfo;
+use crate::error::{Error::ParseError, Result};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use std::result;
+
+/// Path segments that are used by the ticket server and cannot start a service prefix.
+const RESERVED_SEGMENTS: [&str; 5] = [
+  "reads",
+  "variants",
+  "sequences",
+  "annotations",
+  "openapi.json",
+];
+
+/// A set of ticket services, each with a unique prefix.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(try_from = "Vec<ServiceConfig>", into = "Vec<ServiceConfig>")]
+pub struct Services(Vec<ServiceConfig>);
+
+impl Services {
+  /// Create new services, checking that prefixes are unique.
+  pub fn new(services: Vec<ServiceConfig>) -> Result<Self> {
+    let mut prefixes = HashSet::new();
+    for service in &services {
+      if !prefixes.insert(service.prefix()) {
+        return Err(ParseError(format!(
+          "duplicate service prefix `{}`",
+          service.prefix()
+        )));
+      }
+    }
+
+    Ok(Self(services))
+  }
+
+  /// Get the services as a slice.
+  pub fn as_slice(&self) -> &[ServiceConfig] {
+    self.0.as_slice()
+  }
+
+  /// Get the services as a mutable slice.
+  pub fn as_mut_slice(&mut self) -> &mut [ServiceConfig] {
+    self.0.as_mut_slice()
+  }
+
+  /// Get the owned services.
+  pub fn into_inner(self) -> Vec<ServiceConfig> {
+    self.0
+  }
+
+  /// Check whether there are any services.
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+impl TryFrom<Vec<ServiceConfig>> for Services {
+  type Error = crate::error::Error;
+
+  fn try_from(services: Vec<ServiceConfig>) -> Result<Self> {
+    Self::new(services)
+  }
+}
+
+impl From<Services> for Vec<ServiceConfig> {
+  fn from(services: Services) -> Self {
+    services.0
+  }
+}
+
+/// A ticket service which serves the reads and variants endpoints under a path prefix, using
+/// its own locations. The service info and CORS config default to the top-level values if
+/// they are not set.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceConfig {
+  #[serde(deserialize_with = "deserialize_prefix")]
+  prefix: String,
+  locations: Locations,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  service_info: Option<ServiceInfo>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  cors: Option<CorsConfig>,
+}
+
+impl ServiceConfig {
+  /// Create a new service config.
+  pub fn new(
+    prefix: String,
+    locations: Locations,
+    service_info: Option<ServiceInfo>,
+    cors: Option<CorsConfig>,
+  ) -> Result<Self> {
+    Ok(Self {
+      prefix: validate_prefix(prefix)?,
+      locations,
+      service_info,
+      cors,
+    })
+  }
+
+  /// Get the prefix.
+  pub fn prefix(&self) -> &str {
+    &self.prefix
+  }
+
+  /// Get the locations.
+  pub fn locations(&self) -> &Locations {
+    &self.locations
+  }
+
+  /// Get a mutable reference to the locations.
+  pub fn locations_mut(&mut self) -> &mut Locations {
+    &mut self.locations
+  }
+
+  /// Get the service info, if it was set.
+  pub fn service_info(&self) -> Option<&ServiceInfo> {
+    self.service_info.as_ref()
+  }
+
+  /// Get a mutable reference to the service info, if it was set.
+  pub fn service_info_mut(&mut self) -> Option<&mut ServiceInfo> {
+    self.service_info.as_mut()
+  }
+
+  /// Get the CORS config, if it was set.
+  pub fn cors(&self) -> Option<&CorsConfig> {
+    self.cors.as_ref()
+  }
+
+  /// Get the owned values, using the defaults for the service info and CORS if they were not set.
+  pub fn into_inner_or(
+    self,
+    service_info: &ServiceInfo,
+    cors: &CorsConfig,
+  ) -> (String, Locations, ServiceInfo, CorsConfig) {
+    (
+      self.prefix,
+      self.locations,
+      self.service_info.unwrap_or_else(|| service_info.clone()),
+      self.cors.unwrap_or_else(|| cors.clone()),
+    )
+  }
+}
+
+/// Normalize the prefix so that it starts with a `/` and does not end with one, and check that it
+/// does not conflict with the ticket server routes.
+fn validate_prefix(prefix: String) -> Result<String> {
+  let trimmed = prefix.trim_matches('/');
+  if trimmed.is_empty() {
+    return Err(ParseError("service prefix cannot be empty".to_string()));
+  }
+
+  if trimmed
+    .split('/')
+    .any(|segment| segment.is_empty() || segment.starts_with([':', '*', '{']))
+  {
+    return Err(ParseError(format!("invalid service prefix `{prefix}`")));
+  }
+
+  if trimmed
+    .split('/')
+    .next()
+    .is_some_and(|segment| RESERVED_SEGMENTS.contains(&segment))
+  {
+    return Err(ParseError(format!(
+      "service prefix `{prefix}` conflicts with a ticket server route"
+    )));
+  }
+
+  Ok(format!("/{trimmed}"))
+}
+
+fn deserialize_prefix<'de, D>(deserializer: D) -> result::Result<String, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  validate_prefix(String::deserialize(deserializer)?).map_err(Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::{test_config_from_file, test_serialize_and_deserialize};
+  use crate::config::Config;
+  use serde_json::json;
+
+  #[test]
+  fn services() {
+    test_serialize_and_deserialize(
+      r#"
+      [[services]]
+      prefix = "/cohort-a/"
+      locations = "file://path_a"
+
+      [[services]]
+      prefix = "cohort-b"
+      locations = "file://path_b"
+      cors.max_age = 1
+      "#,
+      vec![
+        ("/cohort-a".to_string(), "path_a".to_string(), None),
+        ("/cohort-b".to_string(), "path_b".to_string(), Some(1)),
+      ],
+      |result: Config| {
+        result
+          .services()
+          .as_slice()
+          .iter()
+          .map(|service| {
+            (
+              service.prefix().to_string(),
+              service.locations().as_slice()[0]
+                .as_simple()
+                .unwrap()
+                .backend()
+                .as_file()
+                .unwrap()
+                .local_path()
+                .to_string(),
+              service.cors().map(|cors| cors.max_age()),
+            )
+          })
+          .collect::<Vec<_>>()
+      },
+    );
+  }
+
+  #[test]
+  fn services_service_info() {
+    test_config_from_file(
+      r#"
+      [[services]]
+      prefix = "/cohort-a"
+      locations = "file://path_a"
+      service_info.environment = "dev"
+      "#,
+      |config| {
+        let service = &config.services().as_slice()[0];
+        assert_eq!(
+          service.service_info().unwrap().as_ref().get("environment"),
+          Some(&json!("dev"))
+        );
+      },
+    );
+  }
+
+  #[test]
+  fn services_duplicate_prefix() {
+    let services = toml::from_str::<Config>(
+      r#"
+      [[services]]
+      prefix = "/cohort-a"
+      locations = "file://path_a"
+
+      [[services]]
+      prefix = "/cohort-a/"
+      locations = "file://path_b"
+      "#,
+    );
+
+    assert!(services.is_err());
+  }
+
+  #[test]
+  fn validate_prefixes() {
+    assert_eq!(
+      validate_prefix("a/b/".to_string()).unwrap(),
+      "/a/b".to_string()
+    );
+    assert!(validate_prefix("/".to_string()).is_err());
+    assert!(validate_prefix("/a//b".to_string()).is_err());
+    assert!(validate_prefix("/*id".to_string()).is_err());
+    assert!(validate_prefix("/reads".to_string()).is_err());
+    assert!(validate_prefix("/variants/a".to_string()).is_err());
+    assert!(validate_prefix("/a/reads".to_string()).is_ok());
+  }
+
+  #[test]
+  fn validate_prefixes_reserved() {
+    for prefix in [
+      "/reads",
+      "/variants",
+      "/sequences",
+      "/annotations/a",
+      "/openapi.json",
+    ] {
+      assert!(
+        validate_prefix(prefix.to_string()).is_err(),
+        "{prefix} should be reserved"
+      );
+    }
+  }
+}
@@ -14,12 +14,25 @@ pub struct TicketServerConfig {
   #[serde(skip_serializing)]
   tls: Option<TlsServerConfig>,
   cors: CorsConfig,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  path_prefix: Option<String>,
 }
 
 impl TicketServerConfig {
   /// Create the ticket server config.
   pub fn new(addr: SocketAddr, tls: Option<TlsServerConfig>, cors: CorsConfig) -> Self {
-    Self { addr, tls, cors }
+    Self {
+      addr,
+      tls,
+      cors,
+      path_prefix: None,
+    }
+  }
+
+  /// Set the path prefix which is stripped from requests before routing.
+  pub fn with_path_prefix(mut self, path_prefix: String) -> Self {
+    self.path_prefix = Some(path_prefix);
+    self
   }
 
   /// Get the socket address.
@@ -37,6 +50,11 @@ impl TicketServerConfig {
     &self.cors
   }
 
+  /// Get the path prefix.
+  pub fn path_prefix(&self) -> Option<&str> {
+    self.path_prefix.as_deref()
+  }
+
   /// Get the owned TLS config.
   pub fn into_tls(self) -> Option<TlsServerConfig> {
     self.tls
@@ -49,6 +67,7 @@ impl Default for TicketServerConfig {
       addr: default_addr().parse().expect("expected valid address"),
       tls: Default::default(),
       cors: Default::default(),
+      path_prefix: None,
     }
   }
 }
@@ -73,4 +92,15 @@ mod tests {
       |result: TicketServerConfig| (result.addr().to_string(), result.cors.max_age()),
     );
   }
+
+  #[test]
+  fn path_prefix() {
+    test_serialize_and_deserialize(
+      r#"
+      path_prefix = "/htsget"
+      "#,
+      Some("/htsget".to_string()),
+      |result: TicketServerConfig| result.path_prefix().map(str::to_string),
+    );
+  }
 }
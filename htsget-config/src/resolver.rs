@@ -96,12 +96,33 @@ impl IdResolver for LocationEither {
           return replace(regex_location);
         }
       }
+      #[cfg(feature = "database")]
+      LocationEither::Database(_) => {
+        // Database lookups are asynchronous, so the id can only be resolved by a request.
+        return None;
+      }
+      #[cfg(feature = "url")]
+      LocationEither::Callback(_) => {
+        // Callback decisions depend on the whole query, so the id can only be resolved by a request.
+        return None;
+      }
     }
 
     None
   }
 }
 
+/// Resolve the request using the storage backend.
+async fn resolve_backend<T: ResolveResponse>(backend: &Backend, query: &Query) -> Result<Response> {
+  match backend {
+    Backend::File(file) => T::from_file(file, query).await,
+    #[cfg(feature = "aws")]
+    Backend::S3(s3) => T::from_s3(s3, query).await,
+    #[cfg(feature = "url")]
+    Backend::Url(url_storage) => T::from_url(url_storage, query).await,
+  }
+}
+
 #[async_trait]
 impl StorageResolver for LocationEither {
   #[instrument(level = "trace", skip(self), ret)]
@@ -109,43 +130,81 @@ impl StorageResolver for LocationEither {
     &self,
     query: &mut Query,
   ) -> Option<Result<Response>> {
+    #[cfg(feature = "database")]
+    if let LocationEither::Database(database_location) = self {
+      if !database_location.query_matches(query) {
+        return None;
+      }
+
+      let (backend, resolved_id) = match database_location.lookup(query.id()).await {
+        Ok(resolved) => resolved?,
+        Err(err) => return Some(Err(err)),
+      };
+
+      query.set_id(resolved_id.into_inner());
+      return Some(resolve_backend::<T>(backend, query).await);
+    }
+
+    #[cfg(feature = "url")]
+    if let LocationEither::Callback(callback_location) = self {
+      let (backend, resolved_id) = match callback_location.lookup(query).await {
+        Ok(resolved) => resolved?,
+        Err(err) => return Some(Err(err)),
+      };
+
+      query.set_id(resolved_id.into_inner());
+      return Some(resolve_backend::<T>(backend, query).await);
+    }
+
     let resolved_id = self.resolve_id(query)?;
     let _matched_id = query.id().to_string();
 
     query.set_id(resolved_id.into_inner());
 
-    match self.backend() {
-      Backend::File(file) => Some(T::from_file(file, query).await),
-      #[cfg(feature = "aws")]
-      Backend::S3(s3) => {
-        let s3 = if let Self::Regex(regex_location) = self {
-          if s3.bucket().is_empty() {
-            let first_match = regex_location
-              .regex()
-              .captures(&_matched_id)?
-              .get(1)?
-              .as_str()
-              .to_string();
-            &s3.clone().with_bucket(first_match)
-          } else {
-            s3
-          }
+    let backend = self.backend()?;
+
+    #[cfg(feature = "aws")]
+    if let Backend::S3(s3) = backend {
+      let s3 = if let Self::Regex(regex_location) = self {
+        if s3.bucket().is_empty() {
+          let first_match = regex_location
+            .regex()
+            .captures(&_matched_id)?
+            .get(1)?
+            .as_str()
+            .to_string();
+          &s3.clone().with_bucket(first_match)
         } else {
           s3
-        };
+        }
+      } else {
+        s3
+      };
 
-        Some(T::from_s3(s3, query).await)
-      }
-      #[cfg(feature = "url")]
-      Backend::Url(url_storage) => Some(T::from_url(url_storage, query).await),
+      return Some(T::from_s3(s3, query).await);
     }
+
+    Some(resolve_backend::<T>(backend, query).await)
   }
 }
 
 impl IdResolver for &[LocationEither] {
+  /// Resolve the id using the first matching location. Locations are tried in order, so this
+  /// stops at the first location that can only resolve ids with a request, because a request
+  /// could match that location before any of the locations after it.
   #[instrument(level = "trace", skip(self), ret)]
   fn resolve_id(&self, query: &Query) -> Option<ResolvedId> {
-    self.iter().find_map(|location| location.resolve_id(query))
+    for location in self.iter() {
+      if location.backend().is_none() {
+        return None;
+      }
+
+      if let Some(resolved_id) = location.resolve_id(query) {
+        return Some(resolved_id);
+      }
+    }
+
+    None
   }
 }
 
@@ -257,6 +316,136 @@ mod tests {
     expected_resolved_request(vec![location.into()], "127.0.0.1:8080/id-1").await;
   }
 
+  #[cfg(feature = "database")]
+  #[tokio::test]
+  async fn resolver_resolve_database_request() {
+    use crate::config::advanced::database::{Database, DatabaseLocation};
+    use std::collections::HashMap;
+
+    let file = storage::file::File::new(
+      Http,
+      Authority::from_static("127.0.0.1:8080"),
+      "data".to_string(),
+    );
+    let database_location = DatabaseLocation::new(
+      Database::new(
+        "sqlite://../data/database/catalogue.db?mode=ro".to_string(),
+        "SELECT backend, key FROM samples WHERE id = $1".to_string(),
+        300,
+        100,
+      ),
+      HashMap::from_iter([("local".to_string(), Backend::File(file))]),
+    );
+    let locations = Locations::new(vec![database_location.into()]);
+
+    assert_eq!(
+      locations
+        .resolve_request::<TestResolveResponse>(&mut Query::new_with_default_request(
+          "sample-bam",
+          Bam
+        ))
+        .await
+        .unwrap()
+        .unwrap(),
+      Response::new(
+        Bam,
+        vec![Url::new("127.0.0.1:8080/bam/htsnexus_test_NA12878")]
+      )
+    );
+    assert!(locations
+      .resolve_request::<TestResolveResponse>(&mut Query::new_with_default_request("missing", Bam))
+      .await
+      .is_none());
+  }
+
+  #[cfg(feature = "database")]
+  #[tokio::test]
+  async fn resolver_resolve_database_request_not_matched() {
+    use crate::config::advanced::allow_guard::{AllowGuard, ReferenceNames};
+    use crate::config::advanced::database::{Database, DatabaseLocation};
+    use crate::types::Format::Vcf;
+    use crate::types::{Class, Fields, Interval, TaggedTypeAll, Tags};
+    use std::collections::HashMap;
+
+    // The url is invalid, so any lookup would return an error.
+    let database_location = DatabaseLocation::new(
+      Database::new(
+        "invalid".to_string(),
+        "SELECT backend, key FROM samples WHERE id = $1".to_string(),
+        300,
+        100,
+      ),
+      HashMap::from_iter([("local".to_string(), Default::default())]),
+    )
+    .with_regex("^sample-".parse().unwrap())
+    .with_guard(AllowGuard::new(
+      ReferenceNames::Tagged(TaggedTypeAll::All),
+      Fields::Tagged(TaggedTypeAll::All),
+      Tags::Tagged(TaggedTypeAll::All),
+      vec![Vcf],
+      vec![Class::Body, Class::Header],
+      Interval::default(),
+    ));
+    let locations = Locations::new(vec![database_location.into()]);
+
+    assert!(locations
+      .resolve_request::<TestResolveResponse>(&mut Query::new_with_default_request("id-1", Vcf))
+      .await
+      .is_none());
+    assert!(locations
+      .resolve_request::<TestResolveResponse>(&mut Query::new_with_default_request(
+        "sample-bam",
+        Bam
+      ))
+      .await
+      .is_none());
+    assert!(locations
+      .resolve_request::<TestResolveResponse>(&mut Query::new_with_default_request(
+        "sample-vcf",
+        Vcf
+      ))
+      .await
+      .unwrap()
+      .is_err());
+  }
+
+  #[cfg(feature = "database")]
+  #[test]
+  fn resolver_array_resolve_id_database() {
+    use crate::config::advanced::database::{Database, DatabaseLocation};
+    use std::collections::HashMap;
+
+    let database_location = DatabaseLocation::new(
+      Database::new(
+        "sqlite://../data/database/catalogue.db?mode=ro".to_string(),
+        "SELECT backend, key FROM samples WHERE id = $1".to_string(),
+        300,
+        100,
+      ),
+      HashMap::new(),
+    );
+    let location = Location::new(Default::default(), "".to_string());
+
+    let resolver = Locations::new(vec![
+      location.clone().into(),
+      database_location.clone().into(),
+    ]);
+    assert_eq!(
+      resolver
+        .as_slice()
+        .resolve_id(&Query::new_with_default_request("id-1", Bam))
+        .unwrap()
+        .into_inner(),
+      "id-1"
+    );
+
+    let resolver = Locations::new(vec![database_location.into(), location.into()]);
+    assert!(resolver
+      .as_slice()
+      .resolve_id(&Query::new_with_default_request("id-1", Bam))
+      .is_none());
+  }
+
   #[cfg(feature = "aws")]
   #[tokio::test]
   async fn resolver_resolve_s3_request_tagged() {
@@ -448,7 +637,7 @@ mod tests {
         );
         let resolver = config.locations().first().unwrap();
         let expected_storage = storage::s3::S3::new("bucket".to_string(), None, false);
-        let Backend::S3(storage) = resolver.backend() else {
+        let Some(Backend::S3(storage)) = resolver.backend() else {
           panic!();
         };
 
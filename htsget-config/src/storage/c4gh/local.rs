@@ -2,8 +2,10 @@
 //!
 
 use crate::error::{Error, Result};
+use crate::storage::c4gh::passphrase::C4GHPassphrase;
 use crate::storage::c4gh::C4GHKeys;
 use crypt4gh::keys::{get_private_key, get_public_key};
+use crypt4gh::Keys;
 use serde::Deserialize;
 use std::path::PathBuf;
 
@@ -13,12 +15,32 @@ use std::path::PathBuf;
 pub struct C4GHLocal {
   private: PathBuf,
   public: PathBuf,
+  passphrase: Option<C4GHPassphrase>,
 }
 
 impl C4GHLocal {
   /// Create a new local C4GH key storage.
   pub fn new(private: PathBuf, public: PathBuf) -> Self {
-    Self { private, public }
+    Self {
+      private,
+      public,
+      passphrase: None,
+    }
+  }
+
+  /// Set the source of the private key passphrase.
+  pub fn with_passphrase(mut self, passphrase: C4GHPassphrase) -> Self {
+    self.passphrase = Some(passphrase);
+    self
+  }
+
+  /// Read the C4GH keys, decrypting the private key if it is protected by a passphrase.
+  pub async fn get_keys(self) -> Result<Vec<Keys>> {
+    let passphrase = C4GHPassphrase::read_or_empty(self.passphrase).await?;
+    let private_key = get_private_key(self.private, Ok(passphrase))?;
+    let recipient_public_key = get_public_key(self.public)?;
+
+    Ok(C4GHKeys::from_key_pair(private_key, recipient_public_key))
   }
 }
 
@@ -26,23 +48,17 @@ impl TryFrom<C4GHLocal> for C4GHKeys {
   type Error = Error;
 
   fn try_from(local: C4GHLocal) -> Result<Self> {
-    let private_key = get_private_key(local.private, Ok("".to_string()))?;
-    let recipient_public_key = get_public_key(local.public)?;
-
-    let handle =
-      tokio::spawn(async move { Ok(C4GHKeys::from_key_pair(private_key, recipient_public_key)) });
-
-    Ok(C4GHKeys::from_join_handle(handle))
+    Ok(C4GHKeys::from_join_handle(tokio::spawn(local.get_keys())))
   }
 }
 
 #[cfg(test)]
 mod tests {
+  use super::*;
   use crate::config::tests::test_config_from_file;
   use crate::config::Config;
   use crate::storage::Backend;
   use std::fs::copy;
-  use std::path::PathBuf;
   use tempfile::TempDir;
 
   fn test_c4gh_storage_config<F>(storage_config: &str, test_fn: F)
@@ -88,11 +104,51 @@ mod tests {
       },
     );
   }
+  #[tokio::test]
+  async fn get_keys_with_passphrase() {
+    let parent = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys");
+
+    let unprotected = C4GHLocal::new(parent.join("bob.sec"), parent.join("alice.pub"))
+      .get_keys()
+      .await
+      .unwrap();
+    let protected = C4GHLocal::new(parent.join("bob.protected.sec"), parent.join("alice.pub"))
+      .with_passphrase(C4GHPassphrase::File {
+        path: parent.join("bob.passphrase"),
+      })
+      .get_keys()
+      .await
+      .unwrap();
+
+    assert_eq!(protected[0].privkey, unprotected[0].privkey);
+    assert_eq!(
+      protected[0].recipient_pubkey,
+      unprotected[0].recipient_pubkey
+    );
+  }
+
+  #[tokio::test]
+  async fn get_keys_with_wrong_passphrase() {
+    let parent = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys");
+
+    let result = C4GHLocal::new(parent.join("bob.protected.sec"), parent.join("alice.pub"))
+      .get_keys()
+      .await;
+
+    assert!(result.is_err());
+  }
+
   #[tokio::test]
   async fn config_local_storage_c4gh() {
     test_c4gh_storage_config(r#"kind = "File""#, |config| {
       assert!(matches!(
-            config.locations().first().unwrap().backend(),
+            config.locations().first().unwrap().backend().unwrap(),
             Backend::File(file) if file.keys().is_some()
       ));
     });
@@ -108,7 +164,7 @@ mod tests {
         "#,
       |config| {
         assert!(matches!(
-              config.locations().first().unwrap().backend(),
+              config.locations().first().unwrap().backend().unwrap(),
               Backend::S3(s3) if s3.keys().is_some()
         ));
       },
@@ -127,7 +183,7 @@ mod tests {
         "#,
       |config| {
         assert!(matches!(
-              config.locations().first().unwrap().backend(),
+              config.locations().first().unwrap().backend().unwrap(),
               Backend::Url(url) if url.keys().is_some()
         ));
       },
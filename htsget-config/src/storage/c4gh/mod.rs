@@ -6,6 +6,8 @@ use crate::error::{Error, Result};
 use crate::storage::c4gh::local::C4GHLocal;
 #[cfg(feature = "aws")]
 use crate::storage::c4gh::secrets_manager::C4GHSecretsManager;
+#[cfg(feature = "url")]
+use crate::storage::c4gh::vault::C4GHVault;
 use crypt4gh::error::Crypt4GHError;
 use futures_util::future::{BoxFuture, Shared};
 use futures_util::FutureExt;
@@ -13,13 +15,19 @@ use serde::Deserialize;
 use tokio::task::{JoinError, JoinHandle};
 
 pub mod local;
+pub mod passphrase;
+pub mod pem;
+
+#[cfg(feature = "url")]
+pub mod vault;
 
 #[cfg(feature = "aws")]
 pub mod secrets_manager;
 
-/// Config for Crypt4GH keys.
+/// Config for Crypt4GH keys. Several key locations can be specified, which are tried in order
+/// when decrypting files.
 #[derive(Deserialize, Debug, Clone)]
-#[serde(try_from = "C4GHKeyLocation", deny_unknown_fields)]
+#[serde(try_from = "C4GHKeyLocations", deny_unknown_fields)]
 pub struct C4GHKeys {
   // Store a cloneable future so that it can be resolved outside serde.
   keys: Shared<BoxFuture<'static, Result<Vec<crypt4gh::Keys>>>>,
@@ -40,12 +48,37 @@ impl C4GHKeys {
     }]
   }
 
+  /// Construct the C4GH keys from PEM formatted keys which are already in memory, decrypting the
+  /// private key with the passphrase if it is protected.
+  pub fn from_pem(
+    private_key: &[u8],
+    recipient_public_key: &[u8],
+    passphrase: &str,
+  ) -> Result<Vec<crypt4gh::Keys>> {
+    Ok(Self::from_key_pair(
+      pem::decode_private_key(private_key, passphrase)?,
+      pem::decode_public_key(recipient_public_key)?,
+    ))
+  }
+
   /// Construct from an existing join handle.
   pub fn from_join_handle(handle: JoinHandle<Result<Vec<crypt4gh::Keys>>>) -> Self {
     Self {
       keys: handle.map(|value| value?).boxed().shared(),
     }
   }
+
+  /// Combine multiple keys, preserving their order.
+  pub fn from_keys(keys: Vec<C4GHKeys>) -> Self {
+    Self::from_join_handle(tokio::spawn(async move {
+      let mut all_keys = vec![];
+      for keys in keys {
+        all_keys.extend(keys.keys().await?);
+      }
+
+      Ok(all_keys)
+    }))
+  }
 }
 
 impl From<JoinError> for Error {
@@ -68,10 +101,36 @@ impl TryFrom<C4GHKeyLocation> for C4GHKeys {
       C4GHKeyLocation::File(file) => file.try_into(),
       #[cfg(feature = "aws")]
       C4GHKeyLocation::SecretsManager(secrets_manager) => secrets_manager.try_into(),
+      #[cfg(feature = "url")]
+      C4GHKeyLocation::Vault(vault) => vault.try_into(),
+    }
+  }
+}
+
+impl TryFrom<C4GHKeyLocations> for C4GHKeys {
+  type Error = Error;
+
+  fn try_from(locations: C4GHKeyLocations) -> Result<Self> {
+    match locations {
+      C4GHKeyLocations::One(location) => location.try_into(),
+      C4GHKeyLocations::Many(locations) => Ok(C4GHKeys::from_keys(
+        locations
+          .into_iter()
+          .map(C4GHKeys::try_from)
+          .collect::<Result<Vec<_>>>()?,
+      )),
     }
   }
 }
 
+/// Either a single location of C4GH keys, or a list of locations.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum C4GHKeyLocations {
+  One(C4GHKeyLocation),
+  Many(Vec<C4GHKeyLocation>),
+}
+
 /// The location of C4GH keys.
 #[derive(Deserialize, Debug, Clone)]
 #[serde(tag = "kind", deny_unknown_fields)]
@@ -82,4 +141,44 @@ pub enum C4GHKeyLocation {
   #[cfg(feature = "aws")]
   #[serde(alias = "secretsmanager", alias = "SECRETSMANAGER")]
   SecretsManager(C4GHSecretsManager),
+  #[cfg(feature = "url")]
+  #[serde(alias = "vault", alias = "VAULT")]
+  Vault(C4GHVault),
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::PathBuf;
+
+  #[tokio::test]
+  async fn keys_from_many_locations() {
+    let parent = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys");
+
+    let keys = C4GHKeys::try_from(C4GHKeyLocations::Many(vec![
+      C4GHKeyLocation::File(C4GHLocal::new(
+        parent.join("bob.sec"),
+        parent.join("alice.pub"),
+      )),
+      C4GHKeyLocation::File(C4GHLocal::new(
+        parent.join("alice.sec"),
+        parent.join("alice.pub"),
+      )),
+    ]))
+    .unwrap()
+    .keys()
+    .await
+    .unwrap();
+
+    let bob = C4GHLocal::new(parent.join("bob.sec"), parent.join("alice.pub"))
+      .get_keys()
+      .await
+      .unwrap();
+
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].privkey, bob[0].privkey);
+  }
 }
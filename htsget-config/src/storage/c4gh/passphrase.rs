@@ -0,0 +1,97 @@
+//! Sources of passphrases for protected C4GH private keys.
+//!
+
+use crate::error::Error::ParseError;
+use crate::error::Result;
+#[cfg(feature = "aws")]
+use crate::storage::c4gh::secrets_manager::C4GHSecretsManager;
+#[cfg(feature = "aws")]
+use aws_config::{load_defaults, BehaviorVersion};
+#[cfg(feature = "aws")]
+use aws_sdk_secretsmanager::Client;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// Where the passphrase of a C4GH private key is read from. The passphrase itself is never
+/// stored in the config, so this is only deserialized.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", deny_unknown_fields)]
+#[non_exhaustive]
+pub enum C4GHPassphrase {
+  /// Read the passphrase from an environment variable.
+  #[serde(alias = "env", alias = "ENV")]
+  Env { variable: String },
+  /// Read the passphrase from a file, ignoring trailing newlines.
+  #[serde(alias = "file", alias = "FILE")]
+  File { path: PathBuf },
+  /// Read the passphrase from an AWS secrets manager secret.
+  #[cfg(feature = "aws")]
+  #[serde(alias = "secretsmanager", alias = "SECRETSMANAGER")]
+  SecretsManager { secret: String },
+}
+
+impl C4GHPassphrase {
+  /// Read the passphrase from its source.
+  pub async fn read(self) -> Result<String> {
+    let passphrase = match self {
+      C4GHPassphrase::Env { variable } => env::var(&variable).map_err(|err| {
+        ParseError(format!(
+          "failed to read C4GH passphrase from `{variable}`: {err}"
+        ))
+      })?,
+      C4GHPassphrase::File { path } => fs::read_to_string(path)?,
+      #[cfg(feature = "aws")]
+      C4GHPassphrase::SecretsManager { secret } => {
+        let client = Client::new(&load_defaults(BehaviorVersion::latest()).await);
+        String::from_utf8(C4GHSecretsManager::get_secret(&client, secret).await?)
+          .map_err(|err| ParseError(format!("C4GH passphrase is not valid UTF-8: {err}")))?
+      }
+    };
+
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+  }
+
+  /// Read the passphrase from an optional source, defaulting to an empty passphrase for
+  /// unprotected keys.
+  pub async fn read_or_empty(passphrase: Option<Self>) -> Result<String> {
+    match passphrase {
+      Some(passphrase) => passphrase.read().await,
+      None => Ok("".to_string()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn read_file() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys/bob.passphrase");
+
+    assert_eq!(
+      C4GHPassphrase::File { path }.read().await.unwrap(),
+      "bob-passphrase"
+    );
+  }
+
+  #[tokio::test]
+  async fn read_missing_env() {
+    let result = C4GHPassphrase::Env {
+      variable: "HTSGET_TEST_MISSING_C4GH_PASSPHRASE".to_string(),
+    }
+    .read()
+    .await;
+
+    assert!(matches!(result, Err(ParseError(_))));
+  }
+
+  #[tokio::test]
+  async fn read_or_empty() {
+    assert_eq!(C4GHPassphrase::read_or_empty(None).await.unwrap(), "");
+  }
+}
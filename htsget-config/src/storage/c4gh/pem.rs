@@ -0,0 +1,180 @@
+//! Parse PEM formatted C4GH keys in memory, without writing them to files first.
+//!
+//! This follows the key format described in the [Crypt4GH key specification][spec].
+//!
+//! [spec]: https://crypt4gh.readthedocs.io/en/latest/keys.html
+//!
+
+use crate::error::Error::ParseError;
+use crate::error::Result;
+use base64::engine::general_purpose;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::Sha256;
+
+const C4GH_MAGIC_WORD: &[u8] = b"c4gh-v1";
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+/// Decode a PEM formatted C4GH private key, decrypting it with the passphrase if it is protected.
+pub fn decode_private_key(pem: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+  let data = decode_pem(pem)?;
+  let mut data = data
+    .strip_prefix(C4GH_MAGIC_WORD)
+    .ok_or_else(|| ParseError("unsupported C4GH private key format".to_string()))?;
+
+  let kdf_name = decode_string(&mut data)?;
+  if kdf_name == b"none" {
+    let cipher_name = decode_string(&mut data)?;
+    if cipher_name != b"none" {
+      return Err(ParseError(
+        "C4GH private key without a kdf must not be encrypted".to_string(),
+      ));
+    }
+
+    return Ok(decode_string(&mut data)?.to_vec());
+  }
+
+  let kdf_options = decode_string(&mut data)?;
+  let cipher_name = decode_string(&mut data)?;
+  if cipher_name != b"chacha20_poly1305" {
+    return Err(ParseError(format!(
+      "unsupported C4GH private key cipher: {}",
+      String::from_utf8_lossy(cipher_name)
+    )));
+  }
+
+  let private_data = decode_string(&mut data)?;
+  if kdf_options.len() < 4 || private_data.len() < NONCE_SIZE {
+    return Err(ParseError("invalid C4GH private key".to_string()));
+  }
+
+  let (rounds, salt) = kdf_options.split_at(4);
+  let rounds = u32::from_be_bytes(rounds.try_into().expect("slice has length 4"));
+  let key = derive_key(kdf_name, passphrase.as_bytes(), salt, rounds)?;
+
+  let (nonce, encrypted_key) = private_data.split_at(NONCE_SIZE);
+  ChaCha20Poly1305::new(Key::from_slice(&key))
+    .decrypt(Nonce::from_slice(nonce), encrypted_key)
+    .map_err(|_| ParseError("failed to decrypt C4GH private key, check the passphrase".to_string()))
+}
+
+/// Decode a PEM formatted C4GH public key.
+pub fn decode_public_key(pem: &[u8]) -> Result<Vec<u8>> {
+  let key = decode_pem(pem)?;
+  if key.len() != KEY_SIZE {
+    return Err(ParseError("invalid C4GH public key".to_string()));
+  }
+
+  Ok(key)
+}
+
+/// Get the base64 decoded data between the PEM boundaries.
+fn decode_pem(pem: &[u8]) -> Result<Vec<u8>> {
+  let pem = String::from_utf8_lossy(pem);
+  let data: String = pem
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+    .collect();
+
+  general_purpose::STANDARD
+    .decode(data)
+    .map_err(|err| ParseError(format!("invalid PEM formatted C4GH key: {err}")))
+}
+
+/// Decode a string which is prefixed by its length as a big-endian u16.
+fn decode_string<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+  let invalid = || ParseError("invalid C4GH private key".to_string());
+
+  let (length, rest) = data.split_first_chunk::<2>().ok_or_else(invalid)?;
+  let length = u16::from_be_bytes(*length) as usize;
+  if rest.len() < length {
+    return Err(invalid());
+  }
+
+  let (value, rest) = rest.split_at(length);
+  *data = rest;
+
+  Ok(value)
+}
+
+/// Derive the key used to encrypt a private key from the passphrase.
+fn derive_key(kdf_name: &[u8], passphrase: &[u8], salt: &[u8], rounds: u32) -> Result<Vec<u8>> {
+  let mut key = vec![0; KEY_SIZE];
+  match kdf_name {
+    b"scrypt" => {
+      let params = scrypt::Params::new(14, 8, 1, KEY_SIZE)
+        .map_err(|err| ParseError(format!("invalid scrypt parameters: {err}")))?;
+      scrypt::scrypt(passphrase, salt, &params, &mut key)
+        .map_err(|err| ParseError(format!("failed to derive C4GH key: {err}")))?;
+    }
+    b"bcrypt" => bcrypt_pbkdf::bcrypt_pbkdf(passphrase, salt, rounds, &mut key)
+      .map_err(|err| ParseError(format!("failed to derive C4GH key: {err}")))?,
+    b"pbkdf2_hmac_sha256" => pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, salt, rounds, &mut key),
+    kdf_name => {
+      return Err(ParseError(format!(
+        "unsupported C4GH private key kdf: {}",
+        String::from_utf8_lossy(kdf_name)
+      )))
+    }
+  }
+
+  Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crypt4gh::keys::{get_private_key, get_public_key};
+  use std::fs::read;
+  use std::path::PathBuf;
+
+  fn keys_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys")
+  }
+
+  #[test]
+  fn decode_unprotected_private_key() {
+    let path = keys_dir().join("bob.sec");
+
+    assert_eq!(
+      decode_private_key(&read(&path).unwrap(), "").unwrap(),
+      get_private_key(path, Ok("".to_string())).unwrap()
+    );
+  }
+
+  #[test]
+  fn decode_protected_private_key() {
+    let path = keys_dir().join("bob.protected.sec");
+
+    assert_eq!(
+      decode_private_key(&read(&path).unwrap(), "bob-passphrase").unwrap(),
+      get_private_key(keys_dir().join("bob.sec"), Ok("".to_string())).unwrap()
+    );
+  }
+
+  #[test]
+  fn decode_protected_private_key_wrong_passphrase() {
+    let path = keys_dir().join("bob.protected.sec");
+
+    assert!(matches!(
+      decode_private_key(&read(path).unwrap(), "wrong"),
+      Err(ParseError(_))
+    ));
+  }
+
+  #[test]
+  fn decode_public_key_matches_file() {
+    let path = keys_dir().join("alice.pub");
+
+    assert_eq!(
+      decode_public_key(&read(&path).unwrap()).unwrap(),
+      get_public_key(path).unwrap()
+    );
+  }
+}
@@ -3,16 +3,13 @@
 
 use crate::error::Error::ParseError;
 use crate::error::{Error, Result};
+use crate::storage::c4gh::passphrase::C4GHPassphrase;
 use crate::storage::c4gh::C4GHKeys;
 use aws_config::{load_defaults, BehaviorVersion};
 use aws_sdk_secretsmanager::error::SdkError;
 use aws_sdk_secretsmanager::Client;
-use crypt4gh::keys::{get_private_key, get_public_key};
 use crypt4gh::Keys;
 use serde::Deserialize;
-use std::fs;
-use std::path::Path;
-use tempfile::TempDir;
 
 /// C4GH secrets manager key storage.
 #[derive(Deserialize, Debug, Clone)]
@@ -20,6 +17,7 @@ use tempfile::TempDir;
 pub struct C4GHSecretsManager {
   private: String,
   public: String,
+  passphrase: Option<C4GHPassphrase>,
   #[serde(skip)]
   client: Option<Client>,
 }
@@ -30,10 +28,17 @@ impl C4GHSecretsManager {
     Self {
       private,
       public,
+      passphrase: None,
       client: None,
     }
   }
 
+  /// Set the source of the private key passphrase.
+  pub fn with_passphrase(mut self, passphrase: C4GHPassphrase) -> Self {
+    self.passphrase = Some(passphrase);
+    self
+  }
+
   /// Set the client.
   pub fn with_client(mut self, client: Client) -> Self {
     self.client = Some(client);
@@ -53,11 +58,6 @@ impl C4GHSecretsManager {
     }
   }
 
-  async fn write_to_file(to: &Path, secret: impl Into<String>, client: &Client) -> Result<()> {
-    let data = Self::get_secret(client, secret).await?;
-    Ok(fs::write(to, data)?)
-  }
-
   /// Retrieve the C4GH keys from secrets manager.
   pub async fn get_keys(self) -> Result<Vec<Keys>> {
     let client = if let Some(client) = self.client {
@@ -66,18 +66,11 @@ impl C4GHSecretsManager {
       Client::new(&load_defaults(BehaviorVersion::latest()).await)
     };
 
-    // Should not have to do this, but the Crypt4GH library expects a path.
-    let tmp = TempDir::new()?;
-    let private_key = tmp.path().join("private_key");
-    Self::write_to_file(&private_key, self.private, &client).await?;
-
-    let recipient_public_key = tmp.path().join("public_key");
-    Self::write_to_file(&recipient_public_key, self.public, &client).await?;
-
-    let private_key = get_private_key(private_key, Ok("".to_string()))?;
-    let recipient_public_key = get_public_key(recipient_public_key)?;
+    let private_key = Self::get_secret(&client, self.private).await?;
+    let recipient_public_key = Self::get_secret(&client, self.public).await?;
+    let passphrase = C4GHPassphrase::read_or_empty(self.passphrase).await?;
 
-    Ok(C4GHKeys::from_key_pair(private_key, recipient_public_key))
+    C4GHKeys::from_pem(&private_key, &recipient_public_key, &passphrase)
   }
 }
 
@@ -0,0 +1,236 @@
+//! Obtain C4GH keys from a HashiCorp Vault KV version 2 secrets engine.
+//!
+
+use crate::error::Error::{IoError, ParseError};
+use crate::error::{Error, Result};
+use crate::storage::c4gh::passphrase::C4GHPassphrase;
+use crate::storage::c4gh::C4GHKeys;
+use crypt4gh::Keys;
+use http::Uri;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+
+/// C4GH keys stored as fields of a single secret in Vault. The Vault token is read from an
+/// environment variable so that it is never part of the config.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct C4GHVault {
+  #[serde(with = "http_serde::uri")]
+  address: Uri,
+  #[serde(default = "default_mount")]
+  mount: String,
+  path: String,
+  #[serde(default = "default_private")]
+  private: String,
+  #[serde(default = "default_public")]
+  public: String,
+  #[serde(default = "default_token_variable")]
+  token_variable: String,
+  passphrase: Option<C4GHPassphrase>,
+  #[serde(skip)]
+  client: Option<Client>,
+}
+
+fn default_mount() -> String {
+  "secret".to_string()
+}
+
+fn default_private() -> String {
+  "private".to_string()
+}
+
+fn default_public() -> String {
+  "public".to_string()
+}
+
+fn default_token_variable() -> String {
+  "VAULT_TOKEN".to_string()
+}
+
+impl C4GHVault {
+  /// Create a new C4GH Vault key storage, using the default mount, field names and token variable.
+  pub fn new(address: Uri, path: String) -> Self {
+    Self {
+      address,
+      mount: default_mount(),
+      path,
+      private: default_private(),
+      public: default_public(),
+      token_variable: default_token_variable(),
+      passphrase: None,
+      client: None,
+    }
+  }
+
+  /// Set the environment variable that the Vault token is read from.
+  pub fn with_token_variable(mut self, token_variable: String) -> Self {
+    self.token_variable = token_variable;
+    self
+  }
+
+  /// Set the source of the private key passphrase.
+  pub fn with_passphrase(mut self, passphrase: C4GHPassphrase) -> Self {
+    self.passphrase = Some(passphrase);
+    self
+  }
+
+  /// Set the client.
+  pub fn with_client(mut self, client: Client) -> Self {
+    self.client = Some(client);
+    self
+  }
+
+  /// Read the data of the secret from Vault.
+  pub async fn get_secret(&self, client: &Client) -> Result<Value> {
+    let token = env::var(&self.token_variable).map_err(|err| {
+      ParseError(format!(
+        "failed to read Vault token from `{}`: {}",
+        self.token_variable, err
+      ))
+    })?;
+
+    let url = format!(
+      "{}/v1/{}/data/{}",
+      self.address.to_string().trim_end_matches('/'),
+      self.mount.trim_matches('/'),
+      self.path.trim_start_matches('/')
+    );
+
+    let response = client
+      .get(url)
+      .header("X-Vault-Token", token)
+      .send()
+      .await
+      .and_then(|response| response.error_for_status())
+      .map_err(|err| IoError(format!("failed to get C4GH keys from Vault: {err}")))?;
+
+    let body = response
+      .bytes()
+      .await
+      .map_err(|err| IoError(format!("reading Vault response: {err}")))?;
+    let mut body: Value = serde_json::from_slice(&body)?;
+
+    body
+      .get_mut("data")
+      .and_then(|data| data.get_mut("data"))
+      .map(Value::take)
+      .ok_or_else(|| ParseError("invalid Vault response: missing secret data".to_string()))
+  }
+
+  /// Retrieve the C4GH keys from Vault.
+  pub async fn get_keys(self) -> Result<Vec<Keys>> {
+    let client = self.client.clone().unwrap_or_default();
+    let secret = self.get_secret(&client).await?;
+
+    let field = |name: &str| {
+      secret[name]
+        .as_str()
+        .map(|value| value.as_bytes().to_vec())
+        .ok_or_else(|| ParseError(format!("missing `{name}` field in Vault secret")))
+    };
+    let private_key = field(&self.private)?;
+    let recipient_public_key = field(&self.public)?;
+    let passphrase = C4GHPassphrase::read_or_empty(self.passphrase).await?;
+
+    C4GHKeys::from_pem(&private_key, &recipient_public_key, &passphrase)
+  }
+}
+
+impl TryFrom<C4GHVault> for C4GHKeys {
+  type Error = Error;
+
+  fn try_from(vault: C4GHVault) -> Result<Self> {
+    Ok(C4GHKeys::from_join_handle(tokio::spawn(vault.get_keys())))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs::read_to_string;
+  use std::path::PathBuf;
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::TcpListener;
+
+  /// A stand-in for a dev-mode Vault server which responds to a single KV version 2 read.
+  async fn vault_stand_in(token: &'static str, data: Value) -> Uri {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+      let (mut stream, _) = listener.accept().await.unwrap();
+      let mut request = vec![];
+      let mut buf = [0; 1024];
+      while !request.ends_with(b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+          break;
+        }
+        request.extend_from_slice(&buf[..n]);
+      }
+      let request = String::from_utf8_lossy(&request).to_lowercase();
+
+      let (status, body) = if request.starts_with("get /v1/secret/data/htsget/keys ")
+        && request.contains(&format!("x-vault-token: {token}"))
+      {
+        ("200 OK", serde_json::json!({ "data": { "data": data } }))
+      } else {
+        (
+          "403 Forbidden",
+          serde_json::json!({ "errors": ["permission denied"] }),
+        )
+      };
+
+      let body = body.to_string();
+      let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+      );
+      stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    format!("http://{address}").parse().unwrap()
+  }
+
+  fn keys_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/c4gh/keys")
+  }
+
+  #[tokio::test]
+  async fn get_keys() {
+    let data = serde_json::json!({
+      "private": read_to_string(keys_dir().join("bob.sec")).unwrap(),
+      "public": read_to_string(keys_dir().join("alice.pub")).unwrap(),
+    });
+    let address = vault_stand_in("vault-test-token", data).await;
+
+    env::set_var("HTSGET_TEST_VAULT_TOKEN", "vault-test-token");
+    let keys: C4GHKeys = C4GHVault::new(address, "htsget/keys".to_string())
+      .with_token_variable("HTSGET_TEST_VAULT_TOKEN".to_string())
+      .try_into()
+      .unwrap();
+    let keys = keys.keys().await.unwrap();
+
+    assert_eq!(keys.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn get_keys_wrong_token() {
+    let address = vault_stand_in("vault-test-token", Value::Null).await;
+
+    env::set_var("HTSGET_TEST_VAULT_WRONG_TOKEN", "wrong-token");
+    let result = C4GHVault::new(address, "htsget/keys".to_string())
+      .with_token_variable("HTSGET_TEST_VAULT_WRONG_TOKEN".to_string())
+      .get_keys()
+      .await;
+
+    assert!(matches!(result, Err(IoError(_))));
+  }
+}
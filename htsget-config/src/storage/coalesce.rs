@@ -0,0 +1,53 @@
+//! Configuration for coalescing byte ranges that are close together.
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// When set on a location, byte ranges which are separated by a gap of at most `max_gap_bytes`
+/// are merged, and the closest byte ranges are merged until there are at most `max_urls` of them.
+/// This returns some extra data in exchange for fewer urls in the ticket.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Coalesce {
+  max_gap_bytes: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  max_urls: Option<usize>,
+}
+
+impl Coalesce {
+  /// Create a new coalesce policy.
+  pub fn new(max_gap_bytes: u64, max_urls: Option<usize>) -> Self {
+    Self {
+      max_gap_bytes,
+      max_urls,
+    }
+  }
+
+  /// Get the largest gap between byte ranges which are merged.
+  pub fn max_gap_bytes(&self) -> u64 {
+    self.max_gap_bytes
+  }
+
+  /// Get the maximum number of byte ranges.
+  pub fn max_urls(&self) -> Option<usize> {
+    self.max_urls
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::test_serialize_and_deserialize;
+
+  #[test]
+  fn coalesce() {
+    test_serialize_and_deserialize(
+      r#"
+      max_gap_bytes = 65536
+      max_urls = 10
+      "#,
+      Coalesce::new(65536, Some(10)),
+      |result: Coalesce| result,
+    );
+  }
+}
@@ -7,6 +7,7 @@ use crate::error::Error::ParseError;
 use crate::error::Result;
 #[cfg(feature = "experimental")]
 use crate::storage::c4gh::C4GHKeys;
+use crate::storage::options::LocationOptions;
 use crate::tls::KeyPairScheme;
 use crate::types::Scheme;
 use http::uri::Authority;
@@ -21,9 +22,14 @@ pub struct File {
   #[serde(with = "http_serde::authority")]
   authority: Authority,
   local_path: String,
+  #[serde(flatten)]
+  options: LocationOptions,
   #[cfg(feature = "experimental")]
   #[serde(skip_serializing)]
   keys: Option<C4GHKeys>,
+  #[cfg(feature = "experimental")]
+  #[serde(skip)]
+  plaintext_urls: bool,
 }
 
 impl File {
@@ -33,8 +39,11 @@ impl File {
       scheme,
       authority,
       local_path,
+      options: Default::default(),
       #[cfg(feature = "experimental")]
       keys: None,
+      #[cfg(feature = "experimental")]
+      plaintext_urls: false,
     }
   }
 
@@ -53,6 +62,16 @@ impl File {
     &self.local_path
   }
 
+  /// Get the location options.
+  pub fn options(&self) -> &LocationOptions {
+    &self.options
+  }
+
+  /// Set the location options.
+  pub fn set_options(&mut self, options: LocationOptions) {
+    self.options = options;
+  }
+
   #[cfg(feature = "experimental")]
   /// Set the C4GH keys.
   pub fn set_keys(&mut self, keys: Option<C4GHKeys>) {
@@ -65,6 +84,19 @@ impl File {
     self.keys.as_ref()
   }
 
+  #[cfg(feature = "experimental")]
+  /// Set whether tickets for encrypted objects point at their plaintext keys. This is set when the
+  /// data server decrypts objects for trusted clients.
+  pub fn set_plaintext_urls(&mut self, plaintext_urls: bool) {
+    self.plaintext_urls = plaintext_urls;
+  }
+
+  #[cfg(feature = "experimental")]
+  /// Whether tickets for encrypted objects point at their plaintext keys.
+  pub fn plaintext_urls(&self) -> bool {
+    self.plaintext_urls
+  }
+
   /// Set the local path.
   pub fn set_local_path(mut self, local_path: String) -> Self {
     self.local_path = local_path;
@@ -0,0 +1,83 @@
+//! Configuration for building missing indexes and caching them locally.
+//!
+
+use crate::types::{HtsGetError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// When set on a location, an index that does not exist is built by scanning the data file, and
+/// written to the `local_path` directory. Later queries for the same id read the cached index.
+/// BAI indexes are built for BAM files, tabix indexes for VCF files, and CRAI indexes for CRAM
+/// files. CSI indexes are not built, so BCF files, and queries that use a CSI index, require the
+/// index to exist in storage.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexCache {
+  local_path: PathBuf,
+}
+
+impl IndexCache {
+  /// Create a new index cache.
+  pub fn new(local_path: PathBuf) -> Self {
+    Self { local_path }
+  }
+
+  /// Get the directory that built indexes are written to.
+  pub fn local_path(&self) -> &Path {
+    &self.local_path
+  }
+
+  /// Get the path that the index with the key is cached at, for a data file that is `file_size`
+  /// bytes long. The size is part of the path, so that an index is rebuilt when its data file
+  /// changes. Keys that are absolute, or that contain `..`, are rejected so that the path always
+  /// stays under the `local_path`.
+  pub fn path(&self, key: &str, file_size: u64) -> Result<PathBuf> {
+    let key_path = Path::new(key);
+    if key_path.file_name().is_none()
+      || !key_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+      return Err(HtsGetError::invalid_input(format!(
+        "invalid index cache key: {key}"
+      )));
+    }
+
+    Ok(self.local_path.join(format!("{key}.{file_size}")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::test_serialize_and_deserialize;
+
+  #[test]
+  fn index_cache() {
+    test_serialize_and_deserialize(
+      r#"
+      local_path = "/tmp/htsget/indexes"
+      "#,
+      PathBuf::from("/tmp/htsget/indexes/sample.bam.bai.1024"),
+      |result: IndexCache| result.path("sample.bam.bai", 1024).unwrap(),
+    );
+  }
+
+  #[test]
+  fn index_cache_nested_key() {
+    let cache = IndexCache::new("indexes".into());
+    assert_eq!(
+      cache.path("data/sample.bam.bai", 1024).unwrap(),
+      PathBuf::from("indexes/data/sample.bam.bai.1024")
+    );
+  }
+
+  #[test]
+  fn index_cache_rejects_traversal() {
+    let cache = IndexCache::new("indexes".into());
+    assert!(cache.path("../sample.bam.bai", 1024).is_err());
+    assert!(cache.path("data/../../sample.bam.bai", 1024).is_err());
+    assert!(cache.path("/etc/sample.bam.bai", 1024).is_err());
+    assert!(cache.path("", 1024).is_err());
+  }
+}
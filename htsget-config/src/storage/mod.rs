@@ -7,6 +7,7 @@ use crate::error::Result;
 #[cfg(feature = "experimental")]
 use crate::storage::c4gh::C4GHKeys;
 use crate::storage::file::File;
+use crate::storage::options::LocationOptions;
 #[cfg(feature = "aws")]
 use crate::storage::s3::S3;
 #[cfg(feature = "url")]
@@ -15,7 +16,11 @@ use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "experimental")]
 pub mod c4gh;
+pub mod coalesce;
 pub mod file;
+pub mod index_cache;
+pub mod naming;
+pub mod options;
 #[cfg(feature = "aws")]
 pub mod s3;
 #[cfg(feature = "url")]
@@ -84,6 +89,28 @@ impl Backend {
     }
   }
 
+  /// Get the location options.
+  pub fn options(&self) -> &LocationOptions {
+    match self {
+      Backend::File(file) => file.options(),
+      #[cfg(feature = "aws")]
+      Backend::S3(s3) => s3.options(),
+      #[cfg(feature = "url")]
+      Backend::Url(url) => url.options(),
+    }
+  }
+
+  /// Set the location options.
+  pub fn set_options(&mut self, options: LocationOptions) {
+    match self {
+      Backend::File(file) => file.set_options(options),
+      #[cfg(feature = "aws")]
+      Backend::S3(s3) => s3.set_options(options),
+      #[cfg(feature = "url")]
+      Backend::Url(url) => url.set_options(options),
+    }
+  }
+
   /// Set the C4GH keys.
   #[cfg(feature = "experimental")]
   pub fn set_keys(&mut self, keys: Option<C4GHKeys>) {
@@ -118,7 +145,7 @@ pub(crate) mod tests {
       "#,
       |config| {
         assert!(matches!(
-          config.locations().first().unwrap().backend(),
+          config.locations().first().unwrap().backend().unwrap(),
           Backend::File { .. }
         ));
       },
@@ -131,7 +158,7 @@ pub(crate) mod tests {
       vec![("HTSGET_LOCATIONS", "[{backend={ kind=File }}]")],
       |config| {
         assert!(matches!(
-          config.locations().first().unwrap().backend(),
+          config.locations().first().unwrap().backend().unwrap(),
           Backend::File { .. }
         ));
       },
@@ -149,7 +176,7 @@ pub(crate) mod tests {
       "#,
       |config| {
         assert!(matches!(
-          config.locations().first().unwrap().backend(),
+          config.locations().first().unwrap().backend().unwrap(),
           Backend::S3(..)
         ));
       },
@@ -163,7 +190,7 @@ pub(crate) mod tests {
       vec![("HTSGET_LOCATIONS", "[{backend={ kind=S3 }}]")],
       |config| {
         assert!(matches!(
-          config.locations().first().unwrap().backend(),
+          config.locations().first().unwrap().backend().unwrap(),
           Backend::S3(..)
         ));
       },
@@ -0,0 +1,220 @@
+//! Naming conventions for the data, index and GZI files of an id.
+//!
+
+use crate::types::Format;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// The placeholder in a naming template which is replaced by the resolved id.
+pub const ID_PLACEHOLDER: &str = "{id}";
+
+/// Naming templates for the data, index and GZI files of a single format. Each template is a key
+/// where `{id}` is replaced by the resolved id, and alternatives are probed in order. An empty list
+/// uses the default file ending of the format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatNaming {
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  file: Vec<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  index: Vec<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  gzi: Vec<String>,
+}
+
+impl FormatNaming {
+  /// Create new format naming templates.
+  pub fn new(file: Vec<String>, index: Vec<String>, gzi: Vec<String>) -> Self {
+    Self { file, index, gzi }
+  }
+
+  /// Get the data file templates.
+  pub fn file(&self) -> &[String] {
+    &self.file
+  }
+
+  /// Get the index file templates.
+  pub fn index(&self) -> &[String] {
+    &self.index
+  }
+
+  /// Get the GZI file templates.
+  pub fn gzi(&self) -> &[String] {
+    &self.gzi
+  }
+
+  /// Check whether any templates are set.
+  pub fn is_empty(&self) -> bool {
+    self.file.is_empty() && self.index.is_empty() && self.gzi.is_empty()
+  }
+}
+
+/// Naming templates for each format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Naming {
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  bam: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  cram: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  vcf: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  bcf: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  fasta: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  bed: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  gff3: FormatNaming,
+  #[serde(skip_serializing_if = "FormatNaming::is_empty")]
+  gtf: FormatNaming,
+}
+
+impl Naming {
+  /// Set the naming templates of a format.
+  pub fn with_format(mut self, format: Format, naming: FormatNaming) -> Self {
+    match format {
+      Format::Bam => self.bam = naming,
+      Format::Cram => self.cram = naming,
+      Format::Vcf => self.vcf = naming,
+      Format::Bcf => self.bcf = naming,
+      Format::Fasta => self.fasta = naming,
+      Format::Bed => self.bed = naming,
+      Format::Gff3 => self.gff3 = naming,
+      Format::Gtf => self.gtf = naming,
+    }
+    self
+  }
+
+  /// Get the naming templates of a format.
+  pub fn format(&self, format: Format) -> &FormatNaming {
+    match format {
+      Format::Bam => &self.bam,
+      Format::Cram => &self.cram,
+      Format::Vcf => &self.vcf,
+      Format::Bcf => &self.bcf,
+      Format::Fasta => &self.fasta,
+      Format::Bed => &self.bed,
+      Format::Gff3 => &self.gff3,
+      Format::Gtf => &self.gtf,
+    }
+  }
+
+  /// Check whether any templates are set.
+  pub fn is_empty(&self) -> bool {
+    self.bam.is_empty()
+      && self.cram.is_empty()
+      && self.vcf.is_empty()
+      && self.bcf.is_empty()
+      && self.fasta.is_empty()
+      && self.bed.is_empty()
+      && self.gff3.is_empty()
+      && self.gtf.is_empty()
+  }
+
+  /// Get the keys that the data file could be stored at, in probe order.
+  pub fn file_keys(&self, format: Format, id: &str) -> Vec<String> {
+    render(self.format(format).file(), id).unwrap_or_else(|| vec![format.fmt_file(id)])
+  }
+
+  /// Get the keys that the index file could be stored at, in probe order. By default, BAM and
+  /// VCF files use a BAI or tabix index, and fall back to a CSI index.
+  pub fn index_keys(&self, format: Format, id: &str) -> Vec<String> {
+    render(self.format(format).index(), id).unwrap_or_else(|| match format {
+      Format::Bam | Format::Vcf => vec![Ok(format.fmt_index(id)), format.fmt_csi_index(id)]
+        .into_iter()
+        .flatten()
+        .collect(),
+      _ => vec![format.fmt_index(id)],
+    })
+  }
+
+  /// Get the keys that the GZI file could be stored at, in probe order.
+  pub fn gzi_keys(&self, format: Format, id: &str) -> io::Result<Vec<String>> {
+    match render(self.format(format).gzi(), id) {
+      Some(keys) => Ok(keys),
+      None => Ok(vec![format.fmt_gzi(id)?]),
+    }
+  }
+}
+
+/// Replace the id placeholder in the templates, returning `None` if there are no templates.
+fn render(templates: &[String], id: &str) -> Option<Vec<String>> {
+  if templates.is_empty() {
+    return None;
+  }
+
+  Some(
+    templates
+      .iter()
+      .map(|template| template.replace(ID_PLACEHOLDER, id))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::test_serialize_and_deserialize;
+
+  #[test]
+  fn naming() {
+    test_serialize_and_deserialize(
+      r#"
+      bam.index = ["{id}.bai", "{id}.bam.bai"]
+      vcf.file = ["{id}.vcf.bgz"]
+      vcf.index = ["{id}.vcf.bgz.csi"]
+      "#,
+      (
+        vec!["id.bai".to_string(), "id.bam.bai".to_string()],
+        vec!["id.vcf.bgz".to_string()],
+        vec!["id.vcf.bgz.csi".to_string()],
+      ),
+      |result: Naming| {
+        (
+          result.index_keys(Format::Bam, "id"),
+          result.file_keys(Format::Vcf, "id"),
+          result.index_keys(Format::Vcf, "id"),
+        )
+      },
+    );
+  }
+
+  #[test]
+  fn naming_defaults() {
+    let naming = Naming::default();
+
+    assert_eq!(naming.file_keys(Format::Bam, "id"), vec!["id.bam"]);
+    assert_eq!(
+      naming.index_keys(Format::Bam, "id"),
+      vec!["id.bam.bai", "id.bam.csi"]
+    );
+    assert_eq!(
+      naming.index_keys(Format::Vcf, "id"),
+      vec!["id.vcf.gz.tbi", "id.vcf.gz.csi"]
+    );
+    assert_eq!(naming.index_keys(Format::Cram, "id"), vec!["id.cram.crai"]);
+    assert_eq!(
+      naming.gzi_keys(Format::Vcf, "id").unwrap(),
+      vec!["id.vcf.gz.gzi"]
+    );
+    assert!(naming.gzi_keys(Format::Cram, "id").is_err());
+  }
+
+  #[test]
+  fn naming_with_format() {
+    let naming = Naming::default().with_format(
+      Format::Cram,
+      FormatNaming::new(
+        vec!["{id}.cram".to_string()],
+        vec!["{id}.crai".to_string()],
+        vec![],
+      ),
+    );
+
+    assert_eq!(naming.index_keys(Format::Cram, "id"), vec!["id.crai"]);
+    assert_eq!(naming.index_keys(Format::Bam, "id").len(), 2);
+    assert!(!naming.is_empty());
+  }
+}
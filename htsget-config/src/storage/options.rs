@@ -0,0 +1,90 @@
+//! Options which can be set on any location, independent of its storage backend.
+//!
+
+use crate::storage::coalesce::Coalesce;
+use crate::storage::index_cache::IndexCache;
+use crate::storage::naming::Naming;
+use serde::{Deserialize, Serialize};
+
+/// Options that control how the ids of a location are searched and returned. These are
+/// flattened into the location config, so they are set next to the other backend fields.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct LocationOptions {
+  #[serde(skip_serializing_if = "Naming::is_empty")]
+  naming: Naming,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  index_cache: Option<IndexCache>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  coalesce: Option<Coalesce>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  inline_threshold: Option<u64>,
+}
+
+impl LocationOptions {
+  /// Set the naming templates.
+  pub fn with_naming(mut self, naming: Naming) -> Self {
+    self.naming = naming;
+    self
+  }
+
+  /// Get the naming templates.
+  pub fn naming(&self) -> &Naming {
+    &self.naming
+  }
+
+  /// Set the index cache.
+  pub fn with_index_cache(mut self, index_cache: Option<IndexCache>) -> Self {
+    self.index_cache = index_cache;
+    self
+  }
+
+  /// Get the index cache.
+  pub fn index_cache(&self) -> Option<&IndexCache> {
+    self.index_cache.as_ref()
+  }
+
+  /// Set the byte range coalescing policy.
+  pub fn with_coalesce(mut self, coalesce: Option<Coalesce>) -> Self {
+    self.coalesce = coalesce;
+    self
+  }
+
+  /// Get the byte range coalescing policy.
+  pub fn coalesce(&self) -> Option<&Coalesce> {
+    self.coalesce.as_ref()
+  }
+
+  /// Set the size in bytes at or below which byte ranges are returned inline as data urls.
+  pub fn with_inline_threshold(mut self, inline_threshold: Option<u64>) -> Self {
+    self.inline_threshold = inline_threshold;
+    self
+  }
+
+  /// Get the size in bytes at or below which byte ranges are returned inline as data urls.
+  pub fn inline_threshold(&self) -> Option<u64> {
+    self.inline_threshold
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::tests::test_serialize_and_deserialize;
+
+  #[test]
+  fn location_options() {
+    test_serialize_and_deserialize(
+      r#"
+      index_cache.local_path = "indexes"
+      coalesce.max_gap_bytes = 1024
+      inline_threshold = 512
+      "#,
+      LocationOptions::default()
+        .with_index_cache(Some(IndexCache::new("indexes".into())))
+        .with_coalesce(Some(Coalesce::new(1024, None)))
+        .with_inline_threshold(Some(512)),
+      |result: LocationOptions| result,
+    );
+  }
+}
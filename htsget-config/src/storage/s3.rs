@@ -1,17 +1,76 @@
 //! Configuration for storage on AWS S3.
 //!
 
+use crate::error::{Error, Result};
 #[cfg(feature = "experimental")]
 use crate::storage::c4gh::C4GHKeys;
+use crate::storage::options::LocationOptions;
+use http::Uri;
 use serde::{Deserialize, Serialize};
 
+/// The fields of the S3 storage config, before they are validated.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct S3Fields {
+  bucket: String,
+  endpoint: Option<String>,
+  path_style: bool,
+  #[serde(flatten)]
+  options: LocationOptions,
+  #[serde(with = "http_serde::option::uri")]
+  proxy: Option<Uri>,
+  proxy_secret: Option<String>,
+  #[cfg(feature = "experimental")]
+  keys: Option<C4GHKeys>,
+}
+
+impl TryFrom<S3Fields> for S3 {
+  type Error = Error;
+
+  fn try_from(fields: S3Fields) -> Result<Self> {
+    if fields.proxy.is_some() != fields.proxy_secret.is_some() {
+      return Err(Error::ParseError(
+        "`proxy` and `proxy_secret` must be set together".to_string(),
+      ));
+    }
+
+    #[cfg(feature = "experimental")]
+    if fields.proxy.is_some() && fields.keys.is_some() {
+      return Err(Error::ParseError(
+        "`proxy` cannot be used with Crypt4GH `keys` because proxied objects are not decrypted"
+          .to_string(),
+      ));
+    }
+
+    Ok(Self {
+      bucket: fields.bucket,
+      endpoint: fields.endpoint,
+      path_style: fields.path_style,
+      options: fields.options,
+      proxy: fields.proxy,
+      proxy_secret: fields.proxy_secret,
+      #[cfg(feature = "experimental")]
+      keys: fields.keys,
+    })
+  }
+}
+
 /// Configuration struct for S3 storage.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
-#[serde(default, deny_unknown_fields)]
+#[serde(default, deny_unknown_fields, try_from = "S3Fields")]
 pub struct S3 {
   bucket: String,
   endpoint: Option<String>,
   path_style: bool,
+  #[serde(flatten)]
+  options: LocationOptions,
+  #[serde(
+    with = "http_serde::option::uri",
+    skip_serializing_if = "Option::is_none"
+  )]
+  proxy: Option<Uri>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  proxy_secret: Option<String>,
   #[cfg(feature = "experimental")]
   #[serde(skip_serializing)]
   keys: Option<C4GHKeys>,
@@ -24,6 +83,9 @@ impl S3 {
       bucket,
       endpoint,
       path_style,
+      options: Default::default(),
+      proxy: None,
+      proxy_secret: None,
       #[cfg(feature = "experimental")]
       keys: None,
     }
@@ -62,6 +124,34 @@ impl S3 {
     self
   }
 
+  /// Get the location options.
+  pub fn options(&self) -> &LocationOptions {
+    &self.options
+  }
+
+  /// Set the location options.
+  pub fn set_options(&mut self, options: LocationOptions) {
+    self.options = options;
+  }
+
+  /// Get the url of the data endpoint which proxies objects in this bucket.
+  pub fn proxy(&self) -> Option<&Uri> {
+    self.proxy.as_ref()
+  }
+
+  /// Get the secret used to sign urls of the proxy.
+  pub fn proxy_secret(&self) -> Option<&str> {
+    self.proxy_secret.as_deref()
+  }
+
+  /// Set the url of the data endpoint which proxies objects in this bucket, and the secret used
+  /// to sign its urls. Tickets point at this url instead of presigned urls.
+  pub fn with_proxy(mut self, proxy: Uri, secret: String) -> Self {
+    self.proxy = Some(proxy);
+    self.proxy_secret = Some(secret);
+    self
+  }
+
   #[cfg(feature = "experimental")]
   /// Set the C4GH keys.
   pub fn set_keys(&mut self, keys: Option<C4GHKeys>) {
@@ -98,4 +188,54 @@ mod tests {
       },
     );
   }
+
+  #[test]
+  fn s3_backend_proxy() {
+    test_serialize_and_deserialize(
+      r#"
+      bucket = "bucket"
+      proxy = "https://example.com/data/bucket"
+      proxy_secret = "secret"
+      "#,
+      (
+        "https://example.com/data/bucket".to_string(),
+        "secret".to_string(),
+      ),
+      |result: S3| {
+        (
+          result.proxy().unwrap().to_string(),
+          result.proxy_secret().unwrap().to_string(),
+        )
+      },
+    );
+  }
+
+  #[test]
+  fn s3_backend_proxy_without_secret() {
+    let result = toml::from_str::<S3>(
+      r#"
+      bucket = "bucket"
+      proxy = "https://example.com/data/bucket"
+      "#,
+    );
+
+    assert!(result.is_err());
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn s3_backend_proxy_with_keys() {
+    let result = toml::from_str::<S3>(
+      r#"
+      bucket = "bucket"
+      proxy = "https://example.com/data/bucket"
+      proxy_secret = "secret"
+      keys.kind = "File"
+      keys.private = "data/c4gh/keys/bob.sec"
+      keys.public = "data/c4gh/keys/alice.pub"
+      "#,
+    );
+
+    assert!(result.is_err());
+  }
 }
@@ -4,6 +4,7 @@
 use crate::config::advanced;
 #[cfg(feature = "experimental")]
 use crate::storage::c4gh::C4GHKeys;
+use crate::storage::options::LocationOptions;
 use http::Uri;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -20,6 +21,8 @@ pub struct Url {
   header_blacklist: Vec<String>,
   #[serde(skip_serializing)]
   client: Client,
+  #[serde(flatten)]
+  options: LocationOptions,
   #[cfg(feature = "experimental")]
   #[serde(skip_serializing)]
   keys: Option<C4GHKeys>,
@@ -40,6 +43,7 @@ impl Url {
       forward_headers,
       header_blacklist,
       client,
+      options: Default::default(),
       #[cfg(feature = "experimental")]
       keys: None,
     }
@@ -70,6 +74,16 @@ impl Url {
     self.client.clone()
   }
 
+  /// Get the location options.
+  pub fn options(&self) -> &LocationOptions {
+    &self.options
+  }
+
+  /// Set the location options.
+  pub fn set_options(&mut self, options: LocationOptions) {
+    self.options = options;
+  }
+
   #[cfg(feature = "experimental")]
   /// Set the C4GH keys.
   pub fn set_keys(&mut self, keys: Option<C4GHKeys>) {
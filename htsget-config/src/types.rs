@@ -4,15 +4,17 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::io::ErrorKind::Other;
-use std::{fmt, io, result};
+use std::{fmt, io, iter, result};
 
 #[cfg(feature = "experimental")]
 use crate::encryption_scheme::EncryptionScheme;
 use crate::error::Error;
 use crate::error::Error::ParseError;
+use crate::storage::options::LocationOptions;
 use http::HeaderMap;
 use noodles::core::region::Interval as NoodlesInterval;
 use noodles::core::Position;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::instrument;
@@ -21,8 +23,9 @@ use tracing::instrument;
 pub type Result<T> = result::Result<T, HtsGetError>;
 
 /// An enumeration with all the possible formats.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, JsonSchema)]
 #[serde(rename_all(serialize = "UPPERCASE"), deny_unknown_fields)]
+#[schemars(rename_all = "UPPERCASE")]
 pub enum Format {
   #[default]
   #[serde(alias = "bam", alias = "BAM")]
@@ -33,9 +36,16 @@ pub enum Format {
   Vcf,
   #[serde(alias = "bcf", alias = "BCF")]
   Bcf,
+  #[serde(alias = "fasta", alias = "FASTA")]
+  Fasta,
+  #[serde(alias = "bed", alias = "BED")]
+  Bed,
+  #[serde(alias = "gff3", alias = "GFF3")]
+  Gff3,
+  #[serde(alias = "gtf", alias = "GTF")]
+  Gtf,
 }
 
-/// Todo allow these to be configurable.
 impl Format {
   /// Get the file ending for the format.
   pub fn file_ending(&self) -> &str {
@@ -44,6 +54,10 @@ impl Format {
       Format::Cram => ".cram",
       Format::Vcf => ".vcf.gz",
       Format::Bcf => ".bcf",
+      Format::Fasta => ".fa.gz",
+      Format::Bed => ".bed.gz",
+      Format::Gff3 => ".gff3.gz",
+      Format::Gtf => ".gtf.gz",
     }
   }
 
@@ -59,6 +73,10 @@ impl Format {
       Format::Cram => ".cram.crai",
       Format::Vcf => ".vcf.gz.tbi",
       Format::Bcf => ".bcf.csi",
+      Format::Fasta => ".fa.gz.fai",
+      Format::Bed => ".bed.gz.tbi",
+      Format::Gff3 => ".gff3.gz.tbi",
+      Format::Gtf => ".gtf.gz.tbi",
     }
   }
 
@@ -77,6 +95,10 @@ impl Format {
       )),
       Format::Vcf => Ok(".vcf.gz.gzi"),
       Format::Bcf => Ok(".bcf.gzi"),
+      Format::Fasta => Ok(".fa.gz.gzi"),
+      Format::Bed => Ok(".bed.gz.gzi"),
+      Format::Gff3 => Ok(".gff3.gz.gzi"),
+      Format::Gtf => Ok(".gtf.gz.gzi"),
     }
   }
 
@@ -85,6 +107,31 @@ impl Format {
     Ok(format!("{id}{}", self.gzi_index_file_ending()?))
   }
 
+  /// Get the CSI index file ending for this format.
+  pub fn csi_index_file_ending(&self) -> io::Result<&str> {
+    match self {
+      Format::Bam => Ok(".bam.csi"),
+      Format::Cram => Err(io::Error::new(
+        Other,
+        "CRAM does not support CSI".to_string(),
+      )),
+      Format::Vcf => Ok(".vcf.gz.csi"),
+      Format::Bcf => Ok(".bcf.csi"),
+      Format::Fasta => Err(io::Error::new(
+        Other,
+        "FASTA does not support CSI".to_string(),
+      )),
+      Format::Bed => Ok(".bed.gz.csi"),
+      Format::Gff3 => Ok(".gff3.gz.csi"),
+      Format::Gtf => Ok(".gtf.gz.csi"),
+    }
+  }
+
+  /// Get the CSI index file name including its ending.
+  pub fn fmt_csi_index(&self, id: &str) -> io::Result<String> {
+    Ok(format!("{id}{}", self.csi_index_file_ending()?))
+  }
+
   /// Check if the id points at an index file.
   pub fn is_index(id: &str) -> bool {
     id.ends_with(".bai")
@@ -92,6 +139,7 @@ impl Format {
       || id.ends_with(".tbi")
       || id.ends_with(".csi")
       || id.ends_with(".gzi")
+      || id.ends_with(".fai")
   }
 }
 
@@ -108,13 +156,18 @@ impl Display for Format {
       Format::Cram => write!(f, "CRAM"),
       Format::Vcf => write!(f, "VCF"),
       Format::Bcf => write!(f, "BCF"),
+      Format::Fasta => write!(f, "FASTA"),
+      Format::Bed => write!(f, "BED"),
+      Format::Gff3 => write!(f, "GFF3"),
+      Format::Gtf => write!(f, "GTF"),
     }
   }
 }
 
 /// Class component of htsget response.
-#[derive(Copy, Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default)]
+#[derive(Copy, Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default, JsonSchema)]
 #[serde(rename_all(serialize = "lowercase"), deny_unknown_fields)]
+#[schemars(rename_all = "lowercase")]
 pub enum Class {
   #[serde(alias = "header", alias = "HEADER")]
   Header,
@@ -228,7 +281,7 @@ impl Display for Scheme {
 }
 
 /// Tagged Any allow type for cors config.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub enum TaggedTypeAll {
   #[serde(alias = "all", alias = "ALL")]
@@ -236,7 +289,7 @@ pub enum TaggedTypeAll {
 }
 
 /// Possible values for the fields parameter.
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(untagged, deny_unknown_fields)]
 pub enum Fields {
   /// Include all fields
@@ -252,7 +305,7 @@ impl Default for Fields {
 }
 
 /// Possible values for the tags parameter.
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(untagged, deny_unknown_fields)]
 pub enum Tags {
   /// Include all tags
@@ -268,7 +321,7 @@ impl Default for Tags {
 }
 
 /// The no tags parameter.
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct NoTags(pub Option<HashSet<String>>);
 
@@ -322,11 +375,16 @@ pub struct Query {
   reference_name: Option<String>,
   /// The start and end positions are 0-based. [start, end)
   interval: Interval,
+  /// Additional regions which are searched alongside the reference name and interval, such as
+  /// those from a POST request with multiple regions.
+  regions: Vec<(String, Interval)>,
   fields: Fields,
   tags: Tags,
   no_tags: NoTags,
   /// The raw HTTP request information.
   request: Request,
+  /// The options of the location that the query resolved to.
+  options: LocationOptions,
   #[cfg(feature = "experimental")]
   encryption_scheme: Option<EncryptionScheme>,
 }
@@ -389,6 +447,12 @@ impl Query {
     self
   }
 
+  /// Add a region which is searched alongside the reference name and interval.
+  pub fn with_region(mut self, reference_name: impl Into<String>, interval: Interval) -> Self {
+    self.regions.push((reference_name.into(), interval));
+    self
+  }
+
   /// Set the interval.
   pub fn with_fields(mut self, fields: Fields) -> Self {
     self.fields = fields;
@@ -434,6 +498,28 @@ impl Query {
     self.interval
   }
 
+  /// Additional regions.
+  pub fn regions(&self) -> &[(String, Interval)] {
+    &self.regions
+  }
+
+  /// Split this query into one query for each region, starting with the reference name and
+  /// interval of this query.
+  pub fn region_queries(&self) -> Vec<Query> {
+    let query = Self {
+      regions: vec![],
+      ..self.clone()
+    };
+
+    iter::once(query.clone())
+      .chain(self.regions.iter().map(|(reference_name, interval)| Self {
+        reference_name: Some(reference_name.clone()),
+        interval: *interval,
+        ..query.clone()
+      }))
+      .collect()
+  }
+
   /// Fields.
   pub fn fields(&self) -> &Fields {
     &self.fields
@@ -454,6 +540,17 @@ impl Query {
     &self.request
   }
 
+  /// Set the options of the location that the query resolved to.
+  pub fn with_options(mut self, options: LocationOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  /// Get the options of the location that the query resolved to.
+  pub fn options(&self) -> &LocationOptions {
+    &self.options
+  }
+
   /// Set the encryption scheme.
   #[cfg(feature = "experimental")]
   pub fn with_encryption_scheme(mut self, encryption_scheme: EncryptionScheme) -> Self {
@@ -483,6 +580,9 @@ pub enum HtsGetError {
   #[error("invalid range: {0}")]
   InvalidRange(String),
 
+  #[error("permission denied: {0}")]
+  PermissionDenied(String),
+
   #[error("io error: {0}")]
   IoError(String),
 
@@ -514,6 +614,11 @@ impl HtsGetError {
     Self::InvalidRange(message.into())
   }
 
+  /// Create a `PermissionDenied` error.
+  pub fn permission_denied<S: Into<String>>(message: S) -> Self {
+    Self::PermissionDenied(message.into())
+  }
+
   /// Create an `IoError` error.
   pub fn io_error<S: Into<String>>(message: S) -> Self {
     Self::IoError(message.into())
@@ -543,7 +648,7 @@ impl From<io::Error> for HtsGetError {
 }
 
 /// The headers that need to be supplied when requesting data from a url.
-#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Headers(HashMap<String, String>);
 
@@ -613,7 +718,7 @@ impl TryFrom<&HeaderMap> for Headers {
 }
 
 /// A url from which raw data can be retrieved.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Url {
   pub url: String,
@@ -664,7 +769,7 @@ impl Url {
 }
 
 /// Wrapped json response for htsget.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct JsonResponse {
   pub htsget: Response,
@@ -684,7 +789,7 @@ impl From<Response> for JsonResponse {
 }
 
 /// The response for a HtsGet query.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Response {
   pub format: Format,
@@ -853,6 +958,28 @@ mod tests {
     assert_eq!(result.interval().end(), Some(0));
   }
 
+  #[test]
+  fn query_region_queries() {
+    let query = Query::new_with_default_request("NA12878", Format::Bam)
+      .with_reference_name("chr1")
+      .with_start(1)
+      .with_end(5)
+      .with_region("chr2", Interval::new(Some(10), None));
+
+    assert_eq!(
+      query.region_queries(),
+      vec![
+        Query::new_with_default_request("NA12878", Format::Bam)
+          .with_reference_name("chr1")
+          .with_start(1)
+          .with_end(5),
+        Query::new_with_default_request("NA12878", Format::Bam)
+          .with_reference_name("chr2")
+          .with_start(10)
+      ]
+    );
+  }
+
   #[test]
   fn query_with_fields() {
     let result = Query::new_with_default_request("NA12878", Format::Bam).with_fields(Fields::List(
@@ -911,6 +1038,12 @@ mod tests {
     assert_eq!(result, "BCF");
   }
 
+  #[test]
+  fn format_from_gff3() {
+    let result = String::from(Format::Gff3);
+    assert_eq!(result, "GFF3");
+  }
+
   #[test]
   fn headers_with_header() {
     let header = Headers::new(HashMap::new()).with_header("Range", "bytes=0-1023");
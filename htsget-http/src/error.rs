@@ -1,4 +1,5 @@
 use http::StatusCode;
+use schemars::JsonSchema;
 use serde::Serialize;
 use thiserror::Error;
 
@@ -30,14 +31,14 @@ pub enum HtsGetError {
 
 /// A helper struct implementing [serde's Serialize trait](Serialize) to allow
 /// easily converting HtsGetErrors to JSON
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct JsonHtsGetError {
   error: String,
   message: String,
 }
 
 /// The "htsget" container wrapping the actual error response above
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct WrappedHtsGetError {
   htsget: JsonHtsGetError,
 }
@@ -76,6 +77,7 @@ impl From<HtsGetSearchError> for HtsGetError {
       HtsGetSearchError::UnsupportedFormat(err) => Self::UnsupportedFormat(err),
       HtsGetSearchError::InvalidInput(err) => Self::InvalidInput(err),
       HtsGetSearchError::InvalidRange(err) => Self::InvalidRange(err),
+      HtsGetSearchError::PermissionDenied(err) => Self::PermissionDenied(err),
       HtsGetSearchError::IoError(err) | HtsGetSearchError::ParseError(err) => Self::NotFound(err),
       HtsGetSearchError::InternalError(err) => Self::InternalError(err),
     }
@@ -1,16 +1,11 @@
-use futures::stream::FuturesOrdered;
-use futures::StreamExt;
-use tokio::select;
 use tracing::debug;
 use tracing::instrument;
 
-use htsget_config::types::{JsonResponse, Request, Response};
+use htsget_config::types::{JsonResponse, Request};
 use htsget_search::HtsGet;
 
 use crate::HtsGetError::InvalidInput;
-use crate::{
-  convert_to_query, match_format, merge_responses, Endpoint, HtsGetError, PostRequest, Result,
-};
+use crate::{convert_to_query, match_format, Endpoint, PostRequest, Result};
 
 /// Gets a JSON response for a GET request. The GET request parameters must
 /// be in a HashMap. The "id" field is the only mandatory one. The rest can be
@@ -37,7 +32,7 @@ pub async fn get(
 /// The parameters can be consulted [here](https://samtools.github.io/hts-specs/htsget.html)
 #[instrument(level = "debug", skip_all, ret)]
 pub async fn post(
-  searcher: impl HtsGet + Clone + Send + Sync + 'static,
+  searcher: impl HtsGet + Send + Sync + 'static,
   body: PostRequest,
   request: Request,
   endpoint: Endpoint,
@@ -48,26 +43,13 @@ pub async fn post(
     ));
   }
 
-  let queries = body.get_queries(request, &endpoint)?;
-
-  debug!(endpoint = ?endpoint, queries = ?queries, "getting POST response");
+  let query = body.get_query(request, &endpoint)?;
 
-  let mut futures = FuturesOrdered::new();
-  for query in queries {
-    let owned_searcher = searcher.clone();
-    futures.push_back(tokio::spawn(
-      async move { owned_searcher.search(query).await },
-    ));
-  }
-  let mut responses: Vec<Response> = Vec::new();
-  loop {
-    select! {
-      Some(next) = futures.next() => responses.push(next.map_err(|err| HtsGetError::InternalError(err.to_string()))?.map_err(HtsGetError::from)?),
-      else => break
-    }
-  }
+  debug!(endpoint = ?endpoint, query = ?query, "getting POST response");
 
-  Ok(JsonResponse::from(
-    merge_responses(responses).expect("expected at least one response"),
-  ))
+  searcher
+    .search(query)
+    .await
+    .map_err(Into::into)
+    .map(JsonResponse::from)
 }
@@ -1,28 +1,41 @@
 use cfg_if::cfg_if;
 pub use error::{HtsGetError, Result};
 pub use htsget_config::config::Config;
-use htsget_config::types::Format::{Bam, Bcf, Cram, Vcf};
-use htsget_config::types::{Format, Query, Request, Response};
+use htsget_config::types::Format::{Bam, Bcf, Bed, Cram, Fasta, Gff3, Gtf, Vcf};
+use htsget_config::types::{Format, Query, Request};
+use http::HeaderMap;
 pub use http_core::{get, post};
+pub use openapi::{openapi_json, OPENAPI_PATH};
 pub use post_request::{PostRequest, Region};
 use query_builder::QueryBuilder;
+use schemars::JsonSchema;
 pub use service_info::get_service_info_json;
 pub use service_info::{Htsget, ServiceInfo, Type};
 use std::result;
 use std::str::FromStr;
 
+/// The prefix of headers which hold the authorizer context of a request. These can be used by a
+/// location guard, so they are only trusted when set by the server, such as by `htsget-lambda`.
+pub const AUTHORIZER_HEADER_PREFIX: &str = "x-htsget-authorizer-";
+
 mod error;
 mod http_core;
+mod openapi;
 mod post_request;
 mod query_builder;
 mod service_info;
 
 /// A enum to distinguish between the two endpoint defined in the
-/// [HtsGet specification](https://samtools.github.io/hts-specs/htsget.html)
-#[derive(Debug, PartialEq, Eq)]
+/// [HtsGet specification](https://samtools.github.io/hts-specs/htsget.html), the
+/// sequences endpoint which serves reference sequences from FASTA files, and the annotations
+/// endpoint which serves tabix-indexed BED, GFF3 and GTF files.
+#[derive(Debug, PartialEq, Eq, JsonSchema)]
+#[schemars(rename_all = "lowercase")]
 pub enum Endpoint {
   Reads,
   Variants,
+  Sequences,
+  Annotations,
 }
 
 impl FromStr for Endpoint {
@@ -32,6 +45,8 @@ impl FromStr for Endpoint {
     match s {
       "reads" => Ok(Self::Reads),
       "variants" => Ok(Self::Variants),
+      "sequences" => Ok(Self::Sequences),
+      "annotations" => Ok(Self::Annotations),
       _ => Err(()),
     }
   }
@@ -44,16 +59,36 @@ pub fn match_format(endpoint: &Endpoint, format: Option<impl Into<String>>) -> R
   match (endpoint, format) {
     (Endpoint::Reads, None) => Ok(Bam),
     (Endpoint::Variants, None) => Ok(Vcf),
+    (Endpoint::Sequences, None) => Ok(Fasta),
+    (Endpoint::Annotations, None) => Ok(Bed),
     (Endpoint::Reads, Some(s)) if s == "bam" => Ok(Bam),
     (Endpoint::Reads, Some(s)) if s == "cram" => Ok(Cram),
     (Endpoint::Variants, Some(s)) if s == "vcf" => Ok(Vcf),
     (Endpoint::Variants, Some(s)) if s == "bcf" => Ok(Bcf),
+    (Endpoint::Sequences, Some(s)) if s == "fasta" => Ok(Fasta),
+    (Endpoint::Annotations, Some(s)) if s == "bed" => Ok(Bed),
+    (Endpoint::Annotations, Some(s)) if s == "gff3" => Ok(Gff3),
+    (Endpoint::Annotations, Some(s)) if s == "gtf" => Ok(Gtf),
     (_, Some(format)) => Err(HtsGetError::UnsupportedFormat(format!(
       "{format} isn't a supported format for this endpoint"
     ))),
   }
 }
 
+/// Remove any authorizer headers sent by the client, so that they cannot be used to pass a
+/// location guard. Servers should call this on every incoming request before adding their own
+/// authorizer context.
+pub fn remove_authorizer_headers(headers: &mut HeaderMap) {
+  let names: Vec<_> = headers
+    .keys()
+    .filter(|name| name.as_str().starts_with(AUTHORIZER_HEADER_PREFIX))
+    .cloned()
+    .collect();
+  for name in names {
+    headers.remove(name);
+  }
+}
+
 fn convert_to_query(request: Request, format: Format) -> Result<Query> {
   let query = request.query().clone();
 
@@ -73,13 +108,6 @@ fn convert_to_query(request: Request, format: Format) -> Result<Query> {
   }
 }
 
-fn merge_responses(responses: Vec<Response>) -> Option<Response> {
-  responses.into_iter().reduce(|mut acc, mut response| {
-    acc.urls.append(&mut response.urls);
-    acc
-  })
-}
-
 #[cfg(test)]
 mod tests {
   use std::collections::HashMap;
@@ -95,6 +123,20 @@ mod tests {
 
   use super::*;
 
+  #[test]
+  fn remove_client_authorizer_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-htsget-authorizer-group", "cohort".parse().unwrap());
+    headers.append("x-htsget-authorizer-group", "admin".parse().unwrap());
+    headers.insert("x-htsget-authorizer-sub", "user-1".parse().unwrap());
+    headers.insert("authorization", "Bearer token".parse().unwrap());
+
+    remove_authorizer_headers(&mut headers);
+
+    assert_eq!(headers.len(), 1);
+    assert_eq!(headers["authorization"], "Bearer token");
+  }
+
   #[test]
   fn match_with_invalid_format() {
     assert!(matches!(
@@ -119,6 +161,18 @@ mod tests {
     ));
   }
 
+  #[test]
+  fn match_with_annotations_format() {
+    assert!(matches!(
+      match_format(&Endpoint::Annotations, None::<String>).unwrap(),
+      Bed,
+    ));
+    assert!(matches!(
+      match_format(&Endpoint::Annotations, Some("gff3".to_string())).unwrap(),
+      Gff3,
+    ));
+  }
+
   #[tokio::test]
   async fn get_request() {
     let request = HashMap::new();
@@ -241,6 +295,38 @@ mod tests {
     );
   }
 
+  #[tokio::test]
+  async fn post_request_with_overlapping_regions() {
+    let request = Request::new_with_id("vcf/sample1-bcbio-cancer".to_string());
+    let body = PostRequest {
+      format: Some("VCF".to_string()),
+      class: None,
+      fields: None,
+      tags: None,
+      notags: None,
+      regions: Some(vec![
+        Region {
+          reference_name: "chrM".to_string(),
+          start: Some(149),
+          end: Some(200),
+        },
+        Region {
+          reference_name: "chrM".to_string(),
+          start: Some(150),
+          end: Some(300),
+        },
+      ]),
+    };
+
+    let mut expected_response_headers = Headers::default();
+    expected_response_headers.insert("Range".to_string(), "bytes=0-3493".to_string());
+
+    assert_eq!(
+      post(get_searcher(), body, request, Endpoint::Variants).await,
+      Ok(expected_vcf_json_response(expected_response_headers))
+    );
+  }
+
   fn expected_vcf_json_response(headers: Headers) -> JsonResponse {
     JsonResponse::from(Response::new(
       Vcf,
@@ -0,0 +1,300 @@
+//! Generates an OpenAPI document describing the htsget ticket server.
+//!
+
+use std::str::FromStr;
+
+use htsget_config::config;
+use htsget_config::types::{Class, Format, JsonResponse};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::{schema_for, JsonSchema};
+use serde_json::{json, Map, Value};
+
+use crate::error::WrappedHtsGetError;
+use crate::{match_format, Endpoint, PostRequest, ServiceInfo};
+
+const OPENAPI_VERSION: &str = "3.0.3";
+const DEFAULT_TITLE: &str = "htsget-rs";
+const HTSGET_SPECIFICATION: &str = "https://samtools.github.io/hts-specs/htsget.html";
+
+/// The path that the OpenAPI document is served at.
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+/// Generate the OpenAPI document for the ticket server endpoints. The endpoints, formats and
+/// schemas are derived from the same types that are used to serialize and deserialize requests and
+/// responses. The title and version are taken from the service info config.
+pub fn openapi_json(service_info_config: &config::service_info::ServiceInfo) -> Value {
+  let info_field = |key: &str| {
+    service_info_config
+      .as_ref()
+      .get(key)
+      .and_then(Value::as_str)
+      .map(ToString::to_string)
+  };
+  let title = info_field("name").unwrap_or_else(|| DEFAULT_TITLE.to_string());
+  let version = info_field("version").unwrap_or_default();
+
+  let mut generator = SchemaGenerator::new(SchemaSettings::openapi3());
+
+  let post_request = schema_ref::<PostRequest>(&mut generator);
+  let json_response = schema_ref::<JsonResponse>(&mut generator);
+  let error = schema_ref::<WrappedHtsGetError>(&mut generator);
+  let service_info = schema_ref::<ServiceInfo>(&mut generator);
+  let class = schema_ref::<Class>(&mut generator);
+
+  let schemas: Map<String, Value> = generator
+    .take_definitions()
+    .into_iter()
+    .map(|(name, schema)| (name, json!(schema)))
+    .collect();
+
+  let formats = enum_values::<Format>();
+  let mut paths = Map::new();
+  for endpoint in enum_values::<Endpoint>() {
+    let Ok(variant) = Endpoint::from_str(&endpoint) else {
+      continue;
+    };
+    let formats: Vec<_> = formats
+      .iter()
+      .filter(|format| match_format(&variant, Some(format.as_str())).is_ok())
+      .map(String::as_str)
+      .collect();
+
+    paths.insert(
+      format!("/{endpoint}/service-info"),
+      service_info_path(&endpoint, &service_info),
+    );
+    paths.insert(
+      format!("/{endpoint}/{{id}}"),
+      ticket_path(
+        &endpoint,
+        &formats,
+        &class,
+        &post_request,
+        &json_response,
+        &error,
+      ),
+    );
+  }
+
+  json!({
+    "openapi": OPENAPI_VERSION,
+    "info": {
+      "title": title,
+      "version": version,
+      "description": "A server implementing the htsget protocol.",
+    },
+    "externalDocs": {
+      "url": HTSGET_SPECIFICATION,
+    },
+    "paths": paths,
+    "components": {
+      "schemas": schemas,
+    },
+  })
+}
+
+/// Add the schema of `T` to the generator and return a reference to it.
+fn schema_ref<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+  json!(generator.subschema_for::<T>())
+}
+
+/// Get the values of an enum from its schema.
+fn enum_values<T: JsonSchema>() -> Vec<String> {
+  json!(schema_for!(T).schema)["enum"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(Value::as_str)
+    .map(ToString::to_string)
+    .collect()
+}
+
+fn service_info_path(endpoint: &str, service_info: &Value) -> Value {
+  let operation = |method: &str| {
+    json!({
+      "summary": format!("Get the service info for the {endpoint} endpoint"),
+      "operationId": format!("{method}_{endpoint}_service_info"),
+      "tags": [endpoint],
+      "responses": {
+        "200": json_content("The service info", service_info),
+      },
+    })
+  };
+
+  json!({
+    "get": operation("get"),
+    "post": operation("post"),
+  })
+}
+
+fn ticket_path(
+  endpoint: &str,
+  formats: &[&str],
+  class: &Value,
+  post_request: &Value,
+  json_response: &Value,
+  error: &Value,
+) -> Value {
+  let responses = json!({
+    "200": json_content("The htsget ticket", json_response),
+    "400": json_content("UnsupportedFormat, InvalidInput or InvalidRange", error),
+    "401": json_content("InvalidAuthentication", error),
+    "403": json_content("PermissionDenied", error),
+    "404": json_content("NotFound", error),
+    "413": json_content("PayloadTooLarge", error),
+    "500": json_content("InternalError", error),
+  });
+
+  let string = json!({ "type": "string" });
+  let position = json!({ "type": "integer", "format": "uint32", "minimum": 0 });
+  let id = json!({
+    "name": "id",
+    "in": "path",
+    "required": true,
+    "schema": string,
+  });
+
+  json!({
+    "get": {
+      "summary": format!("Get a ticket for the {endpoint} endpoint"),
+      "operationId": format!("get_{endpoint}"),
+      "tags": [endpoint],
+      "parameters": [
+        id,
+        query_parameter("format", json!({ "type": "string", "enum": formats })),
+        query_parameter("class", class.clone()),
+        query_parameter("referenceName", string.clone()),
+        query_parameter("start", position.clone()),
+        query_parameter("end", position),
+        query_parameter("fields", string.clone()),
+        query_parameter("tags", string.clone()),
+        query_parameter("notags", string),
+      ],
+      "responses": responses,
+    },
+    "post": {
+      "summary": format!("Get a ticket for the {endpoint} endpoint using a request body"),
+      "operationId": format!("post_{endpoint}"),
+      "tags": [endpoint],
+      "parameters": [id],
+      "requestBody": {
+        "required": true,
+        "content": {
+          "application/json": {
+            "schema": post_request,
+          },
+        },
+      },
+      "responses": responses,
+    },
+  })
+}
+
+fn query_parameter(name: &str, schema: Value) -> Value {
+  json!({
+    "name": name,
+    "in": "query",
+    "required": false,
+    "schema": schema,
+  })
+}
+
+fn json_content(description: &str, schema: &Value) -> Value {
+  json!({
+    "description": description,
+    "content": {
+      "application/json": {
+        "schema": schema,
+      },
+    },
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+
+  #[test]
+  fn openapi_json_paths() {
+    let openapi = openapi_json(&Default::default());
+    let paths = openapi["paths"].as_object().unwrap();
+
+    assert!(paths.contains_key("/reads/{id}"));
+    assert!(paths.contains_key("/reads/service-info"));
+    assert!(paths.contains_key("/variants/{id}"));
+    assert!(paths.contains_key("/variants/service-info"));
+    assert!(paths.contains_key("/sequences/{id}"));
+    assert!(paths.contains_key("/sequences/service-info"));
+    assert!(paths.contains_key("/annotations/{id}"));
+    assert!(paths.contains_key("/annotations/service-info"));
+    assert_eq!(
+      openapi["paths"]["/reads/{id}"]["post"]["requestBody"]["content"]["application/json"]
+        ["schema"]["$ref"],
+      "#/components/schemas/PostRequest"
+    );
+  }
+
+  #[test]
+  fn openapi_json_info() {
+    let service_info = config::service_info::ServiceInfo::new(HashMap::from_iter([
+      ("name".to_string(), json!("name")),
+      ("version".to_string(), json!("1.0.0")),
+    ]));
+    let openapi = openapi_json(&service_info);
+
+    assert_eq!(openapi["info"]["title"], "name");
+    assert_eq!(openapi["info"]["version"], "1.0.0");
+  }
+
+  #[test]
+  fn openapi_json_schemas() {
+    let openapi = openapi_json(&Default::default());
+    let schemas = openapi["components"]["schemas"].as_object().unwrap();
+
+    for schema in [
+      "PostRequest",
+      "Region",
+      "JsonResponse",
+      "Response",
+      "Url",
+      "Format",
+      "Class",
+      "WrappedHtsGetError",
+      "JsonHtsGetError",
+    ] {
+      assert!(schemas.contains_key(schema), "missing schema {schema}");
+    }
+
+    assert_eq!(
+      schemas["Format"]["enum"],
+      json!(["BAM", "CRAM", "VCF", "BCF", "FASTA", "BED", "GFF3", "GTF"])
+    );
+    assert!(schemas["Region"]["properties"]
+      .as_object()
+      .unwrap()
+      .contains_key("referenceName"));
+    assert!(schemas["PostRequest"]["properties"]["format"]
+      .to_string()
+      .contains("#/components/schemas/Format"));
+  }
+
+  #[test]
+  fn openapi_json_formats() {
+    let openapi = openapi_json(&Default::default());
+    let formats = |endpoint: &str| {
+      openapi["paths"][format!("/{endpoint}/{{id}}")]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|parameter| parameter["name"] == "format")
+        .unwrap()["schema"]["enum"]
+        .clone()
+    };
+
+    assert_eq!(formats("reads"), json!(["BAM", "CRAM"]));
+    assert_eq!(formats("variants"), json!(["VCF", "BCF"]));
+    assert_eq!(formats("sequences"), json!(["FASTA"]));
+    assert_eq!(formats("annotations"), json!(["BED", "GFF3", "GTF"]));
+  }
+}
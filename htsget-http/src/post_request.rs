@@ -1,16 +1,19 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 
-use htsget_config::types::{Format, Query, Request};
+use htsget_config::types::{Class, Format, Query, Request};
 
 use crate::{match_format, Endpoint, QueryBuilder, Result};
 
 /// A struct to represent a POST request according to the
 /// [HtsGet specification](https://samtools.github.io/hts-specs/htsget.html). It implements
 /// [Deserialize] to make it more ergonomic. Each `PostRequest` can contain several regions.
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
 pub struct PostRequest {
+  #[schemars(with = "Option<Format>")]
   pub format: Option<String>,
+  #[schemars(with = "Option<Class>")]
   pub class: Option<String>,
   pub fields: Option<Vec<String>>,
   pub tags: Option<Vec<String>>,
@@ -20,7 +23,7 @@ pub struct PostRequest {
 
 /// A struct that contains the data to quest for a specific region. It is only meant to be use
 /// alongside a `PostRequest`
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct Region {
   #[serde(rename = "referenceName")]
   pub reference_name: String,
@@ -29,26 +32,36 @@ pub struct Region {
 }
 
 impl PostRequest {
-  /// Converts the `PostRequest` into one or more equivalent [Queries](Query)
+  /// Converts the `PostRequest` into an equivalent [Query]. All regions are part of the same
+  /// query so that they are searched together.
   #[instrument(level = "trace", skip_all, ret)]
-  pub(crate) fn get_queries(self, request: Request, endpoint: &Endpoint) -> Result<Vec<Query>> {
+  pub(crate) fn get_query(self, request: Request, endpoint: &Endpoint) -> Result<Query> {
     let format = match_format(endpoint, self.format.clone())?;
 
-    if let Some(ref regions) = self.regions {
-      regions
-        .iter()
-        .map(|region| {
-          Ok(
-            self
-              .get_base_query_builder(request.clone(), format)?
-              .with_reference_name(Some(region.reference_name.clone()))
-              .with_range_from_u32(region.start, region.end)?
-              .build(),
-          )
-        })
-        .collect::<Result<Vec<Query>>>()
-    } else {
-      Ok(vec![self.get_base_query_builder(request, format)?.build()])
+    let mut queries = self
+      .regions
+      .iter()
+      .flatten()
+      .map(|region| {
+        Ok(
+          self
+            .get_base_query_builder(request.clone(), format)?
+            .with_reference_name(Some(region.reference_name.clone()))
+            .with_range_from_u32(region.start, region.end)?
+            .build(),
+        )
+      })
+      .collect::<Result<Vec<Query>>>()?
+      .into_iter();
+
+    match queries.next() {
+      Some(query) => Ok(queries.fold(query, |query, region| {
+        query.with_region(
+          region.reference_name().unwrap_or_default(),
+          region.interval(),
+        )
+      })),
+      None => Ok(self.get_base_query_builder(request, format)?.build()),
     }
   }
 
@@ -62,7 +75,7 @@ impl PostRequest {
 
 #[cfg(test)]
 mod tests {
-  use htsget_config::types::{Class, Format};
+  use htsget_config::types::{Class, Format, Interval};
 
   use super::*;
 
@@ -79,9 +92,9 @@ mod tests {
         notags: None,
         regions: None,
       }
-      .get_queries(request.clone(), &Endpoint::Variants)
+      .get_query(request.clone(), &Endpoint::Variants)
       .unwrap(),
-      vec![Query::new("id", Format::Vcf, request).with_class(Class::Header)]
+      Query::new("id", Format::Vcf, request).with_class(Class::Header)
     );
   }
 
@@ -102,13 +115,13 @@ mod tests {
           end: Some(153),
         }]),
       }
-      .get_queries(request.clone(), &Endpoint::Variants)
+      .get_query(request.clone(), &Endpoint::Variants)
       .unwrap(),
-      vec![Query::new("id", Format::Vcf, request)
+      Query::new("id", Format::Vcf, request)
         .with_class(Class::Header)
         .with_reference_name("20".to_string())
         .with_start(150)
-        .with_end(153)]
+        .with_end(153)
     );
   }
 
@@ -136,20 +149,33 @@ mod tests {
           }
         ]),
       }
-      .get_queries(request.clone(), &Endpoint::Variants)
+      .get_query(request.clone(), &Endpoint::Variants)
+      .unwrap(),
+      Query::new("id", Format::Vcf, request)
+        .with_class(Class::Header)
+        .with_reference_name("20".to_string())
+        .with_start(150)
+        .with_end(153)
+        .with_region("11", Interval::new(Some(152), Some(154)))
+    );
+  }
+
+  #[test]
+  fn post_request_with_empty_regions() {
+    let request = Request::new_with_id("id".to_string());
+
+    assert_eq!(
+      PostRequest {
+        format: Some("VCF".to_string()),
+        class: None,
+        fields: None,
+        tags: None,
+        notags: None,
+        regions: Some(vec![]),
+      }
+      .get_query(request.clone(), &Endpoint::Variants)
       .unwrap(),
-      vec![
-        Query::new("id", Format::Vcf, request.clone())
-          .with_class(Class::Header)
-          .with_reference_name("20".to_string())
-          .with_start(150)
-          .with_end(153),
-        Query::new("id", Format::Vcf, request)
-          .with_class(Class::Header)
-          .with_reference_name("11".to_string())
-          .with_start(152)
-          .with_end(154)
-      ]
+      Query::new("id", Format::Vcf, request)
     );
   }
 }
@@ -85,16 +85,20 @@ impl QueryBuilder {
       self.query = self.query.with_end(end);
     }
 
-    if (self.query.interval().start().is_some() || self.query.interval().end().is_some())
-      && self
-        .query
-        .reference_name()
-        .filter(|name| *name != "*")
-        .is_none()
-    {
-      return Err(HtsGetError::InvalidInput(
-        "reference name must be specified with start or end range".to_string(),
-      ));
+    if self.query.interval().start().is_some() || self.query.interval().end().is_some() {
+      match self.query.reference_name() {
+        None => {
+          return Err(HtsGetError::InvalidInput(
+            "reference name must be specified with start or end range".to_string(),
+          ))
+        }
+        Some("*") => {
+          return Err(HtsGetError::InvalidInput(
+            "start or end range cannot be specified with unplaced unmapped reads".to_string(),
+          ))
+        }
+        _ => {}
+      }
     }
 
     if let (Some(start), Some(end)) = &(self.query.interval().start(), self.query.interval().end())
@@ -262,6 +266,19 @@ mod tests {
     ));
   }
 
+  #[test]
+  fn query_with_range_and_unmapped_reads() {
+    let request = Request::new_with_id("ValidId".to_string());
+
+    assert!(matches!(
+      QueryBuilder::new(request, Bam)
+        .with_reference_name(Some("*"))
+        .with_range(Some("3"), Some("5"))
+        .unwrap_err(),
+      HtsGetError::InvalidInput(_)
+    ));
+  }
+
   #[test]
   fn query_with_invalid_start() {
     let request = Request::new_with_id("ValidId".to_string());
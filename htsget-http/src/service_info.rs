@@ -1,6 +1,7 @@
 use htsget_config::config;
 use htsget_config::types::Format;
 use htsget_search::HtsGet;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -11,13 +12,15 @@ use crate::Endpoint;
 
 const READS_FORMATS: [&str; 2] = ["BAM", "CRAM"];
 const VARIANTS_FORMATS: [&str; 2] = ["VCF", "BCF"];
+const SEQUENCES_FORMATS: [&str; 1] = ["FASTA"];
+const ANNOTATIONS_FORMATS: [&str; 3] = ["BED", "GFF3", "GTF"];
 
 const HTSGET_GROUP: &str = "org.ga4gh";
 const HTSGET_ARTIFACT: &str = "htsget";
 const HTSGET_VERSION: &str = "1.3.0";
 
 /// A struct representing the information that should be present in a service-info response.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceInfo {
   #[serde(flatten)]
@@ -27,7 +30,7 @@ pub struct ServiceInfo {
   pub htsget: Htsget,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Type {
   pub group: String,
@@ -45,7 +48,7 @@ impl Default for Type {
   }
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Htsget {
   pub datatype: String,
@@ -66,6 +69,8 @@ impl ServiceInfo {
       datatype: match endpoint {
         Endpoint::Reads => "reads",
         Endpoint::Variants => "variants",
+        Endpoint::Sequences => "sequences",
+        Endpoint::Annotations => "annotations",
       }
       .to_string(),
       formats: supported_formats
@@ -74,6 +79,8 @@ impl ServiceInfo {
         .filter(|format| match endpoint {
           Endpoint::Reads => READS_FORMATS.contains(&format.as_str()),
           Endpoint::Variants => VARIANTS_FORMATS.contains(&format.as_str()),
+          Endpoint::Sequences => SEQUENCES_FORMATS.contains(&format.as_str()),
+          Endpoint::Annotations => ANNOTATIONS_FORMATS.contains(&format.as_str()),
         })
         .collect(),
       fields_parameters_effective: fields_effective,
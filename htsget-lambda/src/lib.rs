@@ -1,3 +1,302 @@
-//! htsget-lambda no longer has any library functions. Please use `htsget-axum` for similar
-//! functionality on routers and logic.
+//! Adapts Lambda events to the routes of the `htsget-axum` ticket server. Requests can come from
+//! API Gateway REST APIs, Lambda function URLs or application load balancer target groups. Please
+//! use `htsget-axum` for functionality on routers and logic.
 //!
+
+use htsget_http::remove_authorizer_headers;
+pub use htsget_http::AUTHORIZER_HEADER_PREFIX;
+use lambda_http::http::{HeaderName, HeaderValue, Uri};
+use lambda_http::{Request, RequestExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use tower::util::MapRequest;
+use tracing::warn;
+
+/// Route requests to the service using the path that the event was received with, stripping the
+/// path prefix if it is set.
+pub fn route_requests<S>(
+  service: S,
+  path_prefix: Option<String>,
+) -> MapRequest<S, impl FnMut(Request) -> Request + Clone> {
+  MapRequest::new(service, move |request| {
+    route_request(request, path_prefix.as_deref())
+  })
+}
+
+/// Rewrite the URI of the request so that it can be routed. This uses the raw path of the event
+/// so that API Gateway stages are never part of the path, and joins multi-value query string
+/// parameters into comma-separated values. The authorizer context is forwarded as headers.
+pub fn route_request(mut request: Request, path_prefix: Option<&str>) -> Request {
+  forward_authorizer(&mut request);
+
+  let path = match request.raw_http_path() {
+    "" => request.uri().path(),
+    path => path,
+  };
+  let path = path_prefix
+    .map(|prefix| prefix.trim_end_matches('/'))
+    .and_then(|prefix| path.strip_prefix(prefix))
+    .filter(|stripped| stripped.is_empty() || stripped.starts_with('/'))
+    .unwrap_or(path);
+  let path = if path.is_empty() { "/" } else { path };
+
+  let path_and_query = match request.uri().query().map(join_query) {
+    Some(query) if !query.is_empty() => format!("{path}?{query}"),
+    _ => path.to_string(),
+  };
+
+  match Uri::builder().path_and_query(path_and_query).build() {
+    Ok(uri) => *request.uri_mut() = uri,
+    Err(err) => warn!(err = %err, uri = %request.uri(), "failed to rewrite request uri"),
+  }
+
+  request
+}
+
+/// Forward the claims or context of an API Gateway Lambda or JWT authorizer as headers prefixed
+/// with `x-htsget-authorizer-`. Any headers with this prefix sent by the client are removed first,
+/// so that they can only come from the authorizer.
+pub fn forward_authorizer(request: &mut Request) {
+  remove_authorizer_headers(request.headers_mut());
+
+  let Some(context) = request
+    .request_context_ref()
+    .and_then(|context| serde_json::to_value(context).ok())
+  else {
+    return;
+  };
+
+  let mut claims = vec![];
+  authorizer_claims(&context["authorizer"], &mut claims);
+  for (key, value) in claims {
+    let name = HeaderName::try_from(format!("{AUTHORIZER_HEADER_PREFIX}{}", key.to_lowercase()));
+    match (name, HeaderValue::from_str(&value)) {
+      (Ok(name), Ok(value)) => {
+        request.headers_mut().append(name, value);
+      }
+      _ => warn!(key = %key, "skipping authorizer claim which is not a valid header"),
+    }
+  }
+}
+
+/// Collect the claims of the authorizer context. REST APIs place Lambda authorizer context at the
+/// top level and Cognito claims under `claims`, and HTTP APIs place them under `lambda` or
+/// `jwt.claims`.
+fn authorizer_claims(value: &Value, claims: &mut Vec<(String, String)>) {
+  let Value::Object(object) = value else {
+    return;
+  };
+
+  for (key, value) in object {
+    match (key.as_str(), value) {
+      ("claims" | "jwt" | "lambda", Value::Object(_)) => authorizer_claims(value, claims),
+      (_, Value::String(value)) => claims.push((key.to_string(), value.to_string())),
+      (_, Value::Number(_) | Value::Bool(_)) => claims.push((key.to_string(), value.to_string())),
+      (_, Value::Array(values)) => claims.push((
+        key.to_string(),
+        values
+          .iter()
+          .filter_map(|value| value.as_str())
+          .collect::<Vec<_>>()
+          .join(","),
+      )),
+      _ => {}
+    }
+  }
+}
+
+/// Join the values of repeated query string parameters with commas, keeping the order of keys.
+fn join_query(query: &str) -> String {
+  let mut keys = vec![];
+  let mut values: HashMap<&str, Vec<&str>> = HashMap::new();
+  for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+    values
+      .entry(key)
+      .or_insert_with(|| {
+        keys.push(key);
+        vec![]
+      })
+      .push(value);
+  }
+
+  keys
+    .into_iter()
+    .map(|key| format!("{}={}", key, values[key].join(",")))
+    .collect::<Vec<_>>()
+    .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use htsget_axum::server::ticket::TicketServer;
+  use htsget_test::http::default_test_config;
+  use lambda_http::http::StatusCode;
+  use lambda_http::request::from_str;
+  use serde_json::Value;
+  use std::fs::read_to_string;
+  use std::path::PathBuf;
+  use tower::ServiceExt;
+
+  fn event(name: &str) -> Request {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("data/events")
+      .join(name);
+
+    from_str(&read_to_string(path).unwrap()).unwrap()
+  }
+
+  fn query(request: &Request) -> HashMap<String, String> {
+    request
+      .uri()
+      .query()
+      .unwrap_or_default()
+      .split('&')
+      .filter_map(|pair| pair.split_once('='))
+      .map(|(key, value)| (key.to_string(), value.to_string()))
+      .collect()
+  }
+
+  async fn ticket(request: Request, path_prefix: Option<&str>) -> (StatusCode, Value) {
+    let config = default_test_config();
+    let service_info = config.service_info().clone();
+    let cors = config.ticket_server().cors().clone();
+    let services = config.services().clone();
+    let router =
+      TicketServer::router_with_services(config.into_locations(), service_info, cors, services);
+
+    let response = route_requests(router, path_prefix.map(str::to_string))
+      .oneshot(request)
+      .await
+      .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+
+    (status, serde_json::from_slice(&body).unwrap())
+  }
+
+  #[test]
+  fn join_query_multiple_values() {
+    assert_eq!(
+      join_query("fields=QNAME&format=BAM&fields=FLAG"),
+      "fields=QNAME,FLAG&format=BAM"
+    );
+  }
+
+  #[test]
+  fn join_query_without_value() {
+    assert_eq!(join_query("class&&format=BAM"), "class=&format=BAM");
+  }
+
+  #[test]
+  fn route_rest_api_stage() {
+    let request = route_request(event("rest_api_stage_get.json"), None);
+
+    assert_eq!(request.uri().path(), "/reads/1-bam/htsnexus_test_NA12878");
+    assert_eq!(query(&request)["fields"], "QNAME,FLAG");
+  }
+
+  #[test]
+  fn route_alb_prefix() {
+    let request = route_request(event("alb_get.json"), Some("/htsget/"));
+
+    assert_eq!(request.uri().path(), "/reads/1-bam/htsnexus_test_NA12878");
+    assert_eq!(query(&request)["fields"], "QNAME,FLAG");
+  }
+
+  #[test]
+  fn route_prefix_not_matching() {
+    let request = route_request(event("alb_get.json"), Some("/htsge"));
+
+    assert_eq!(
+      request.uri().path(),
+      "/htsget/reads/1-bam/htsnexus_test_NA12878"
+    );
+  }
+
+  #[test]
+  fn forward_rest_api_authorizer() {
+    let request = route_request(event("rest_api_authorizer_get.json"), None);
+    let headers = request.headers();
+
+    assert_eq!(headers["x-htsget-authorizer-principalid"], "user-1");
+    assert_eq!(headers["x-htsget-authorizer-group"], "cohort");
+    assert_eq!(
+      headers.get_all("x-htsget-authorizer-group").iter().count(),
+      1
+    );
+  }
+
+  #[test]
+  fn forward_http_api_jwt_authorizer() {
+    let request = route_request(event("http_api_jwt_get.json"), None);
+    let headers = request.headers();
+
+    assert_eq!(headers["x-htsget-authorizer-sub"], "user-1");
+    assert_eq!(headers["x-htsget-authorizer-group"], "cohort");
+    assert_eq!(headers["x-htsget-authorizer-scopes"], "htsget/read");
+  }
+
+  #[test]
+  fn forward_without_authorizer() {
+    let request = route_request(event("alb_get.json"), None);
+
+    assert!(!request
+      .headers()
+      .keys()
+      .any(|name| name.as_str().starts_with(AUTHORIZER_HEADER_PREFIX)));
+  }
+
+  #[test]
+  fn route_function_url() {
+    let request = route_request(event("function_url_post.json"), None);
+
+    assert_eq!(request.uri().path(), "/reads/1-bam/htsnexus_test_NA12878");
+    assert_eq!(request.uri().query(), None);
+  }
+
+  #[tokio::test]
+  async fn ticket_rest_api_get() {
+    let (status, body) = ticket(event("event_get.json"), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["htsget"]["format"], "VCF");
+  }
+
+  #[tokio::test]
+  async fn ticket_rest_api_post() {
+    let (status, body) = ticket(event("event_parameterized_post.json"), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["htsget"]["format"], "VCF");
+  }
+
+  #[tokio::test]
+  async fn ticket_rest_api_stage() {
+    let (status, body) = ticket(event("rest_api_stage_get.json"), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["htsget"]["format"], "BAM");
+  }
+
+  #[tokio::test]
+  async fn ticket_alb() {
+    let (status, body) = ticket(event("alb_get.json"), Some("/htsget")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["htsget"]["format"], "BAM");
+  }
+
+  #[tokio::test]
+  async fn ticket_function_url_base64_post() {
+    let (status, body) = ticket(event("function_url_post.json"), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["htsget"]["format"], "BAM");
+  }
+}
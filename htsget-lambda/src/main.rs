@@ -1,9 +1,19 @@
+#[cfg(feature = "aws")]
+use htsget_axum::server::configure_cors;
+#[cfg(feature = "aws")]
+use htsget_axum::server::proxy;
 use htsget_axum::server::ticket::TicketServer;
+#[cfg(feature = "aws")]
+use htsget_config::config::location::LocationEither;
 use htsget_config::config::Config;
+#[cfg(feature = "aws")]
+use htsget_config::storage::Backend;
 use htsget_config::{command, package_info};
+use htsget_lambda::route_requests;
+#[cfg(feature = "aws")]
+use lambda_http::run_with_streaming_response;
 use lambda_http::{run, Error};
 use rustls::crypto::aws_lc_rs;
-use std::env::set_var;
 use std::io;
 use tracing::debug;
 
@@ -13,26 +23,57 @@ async fn main() -> Result<(), Error> {
     .install_default()
     .map_err(|_| io::Error::other("setting crypto provider"))?;
 
-  // Ignore the API gateway stage.
-  // See https://github.com/awslabs/aws-lambda-rust-runtime/tree/main/lambda-http#integration-with-api-gateway-stages
-  set_var("AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH", "true");
-
   if let Some(path) = Config::parse_args_with_command(command!())? {
     let mut config = Config::from_path(&path)?;
 
     config.setup_tracing()?;
 
-    let service_info = config.service_info_mut();
-    service_info.set_from_package_info(package_info!())?;
+    config.set_from_package_info(package_info!())?;
 
     debug!(config = ?config, "config parsed");
 
     let service_info = config.service_info().clone();
     let cors = config.ticket_server().cors().clone();
-    let router = TicketServer::router(config.into_locations(), service_info, cors);
+    let services = config.services().clone();
+    let path_prefix = config.ticket_server().path_prefix().map(str::to_string);
+
+    #[cfg(feature = "aws")]
+    let proxy = {
+      let locations = config.locations().iter().chain(
+        services
+          .as_slice()
+          .iter()
+          .flat_map(|service| service.locations().as_slice()),
+      );
+
+      if locations.clone().any(is_proxied) {
+        Some(
+          proxy::router(locations, path_prefix.as_deref())
+            .await?
+            .layer(configure_cors(cors.clone())),
+        )
+      } else {
+        None
+      }
+    };
+
+    let router =
+      TicketServer::router_with_services(config.into_locations(), service_info, cors, services);
 
-    run(router).await
+    // Stream responses when serving data through the function, otherwise buffer ticket responses.
+    #[cfg(feature = "aws")]
+    if let Some(proxy) = proxy {
+      return run_with_streaming_response(route_requests(router.merge(proxy), path_prefix)).await;
+    }
+
+    run(route_requests(router, path_prefix)).await
   } else {
     Ok(())
   }
 }
+
+/// Whether the location serves S3 objects through the function.
+#[cfg(feature = "aws")]
+fn is_proxied(location: &LocationEither) -> bool {
+  matches!(location.backend(), Some(Backend::S3(s3)) if s3.proxy().is_some())
+}
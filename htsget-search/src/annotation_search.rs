@@ -0,0 +1,379 @@
+//! Module providing the search capability for bgzipped annotation files indexed with tabix, such as
+//! BED, GFF3 and GTF.
+//!
+
+use async_trait::async_trait;
+use futures_util::stream::FuturesOrdered;
+use noodles::bgzf;
+use noodles::bgzf::VirtualPosition;
+use noodles::csi::binning_index::index::reference_sequence::index::LinearIndex;
+use noodles::csi::binning_index::index::{Index, ReferenceSequence};
+use noodles::tabix;
+use tokio::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead};
+use tracing::{instrument, trace};
+
+use htsget_config::types::HtsGetError;
+
+use crate::search::{find_first, BgzfSearch, Search, SearchKeys};
+use crate::{Format, Query, Result};
+use htsget_storage::types::BytesPosition;
+use htsget_storage::{Storage, Streamable};
+
+type AsyncReader = bgzf::AsyncReader<Streamable>;
+
+/// The header of an annotation file, which consists of the lines at the start of the file that
+/// tabix skips, either because they are within the line skip count or start with the comment prefix.
+type Header = Vec<u8>;
+
+/// The comment prefix used when the index has no tabix header. This is the meta character used by
+/// the tabix presets for BED, GFF3 and GTF files.
+const DEFAULT_LINE_COMMENT_PREFIX: u8 = b'#';
+
+/// Read the header lines, which are the first `line_skip_count` lines of the file followed by any
+/// lines starting with the `line_comment_prefix`.
+async fn read_header_lines<R: AsyncBufRead + Unpin>(
+  reader: &mut R,
+  line_comment_prefix: u8,
+  line_skip_count: u32,
+) -> io::Result<Header> {
+  let mut header = Vec::new();
+  for _ in 0..line_skip_count {
+    if reader.read_until(b'\n', &mut header).await? == 0 {
+      return Ok(header);
+    }
+  }
+
+  while reader.fill_buf().await?.first() == Some(&line_comment_prefix) {
+    reader.read_until(b'\n', &mut header).await?;
+  }
+
+  Ok(header)
+}
+
+/// Allows searching through bgzipped annotation files which use a tabix index.
+pub struct AnnotationSearch {
+  storage: Storage,
+  keys: SearchKeys,
+  format: Format,
+}
+
+#[async_trait]
+impl BgzfSearch<LinearIndex, AsyncReader, Header> for AnnotationSearch {
+  async fn read_bytes(reader: &mut AsyncReader) -> Option<usize> {
+    reader.read_until(b'\n', &mut Vec::new()).await.ok()
+  }
+
+  fn virtual_position(&self, reader: &AsyncReader) -> VirtualPosition {
+    reader.virtual_position()
+  }
+}
+
+#[async_trait]
+impl Search<ReferenceSequence<LinearIndex>, Index<LinearIndex>, AsyncReader, Header>
+  for AnnotationSearch
+{
+  fn init_reader(inner: Streamable) -> AsyncReader {
+    bgzf::AsyncReader::new(inner)
+  }
+
+  async fn read_header(reader: &mut AsyncReader) -> io::Result<Header> {
+    read_header_lines(reader, DEFAULT_LINE_COMMENT_PREFIX, 0).await
+  }
+
+  async fn read_header_with_index(
+    reader: &mut AsyncReader,
+    index: &Index<LinearIndex>,
+  ) -> io::Result<Header> {
+    match index.header() {
+      Some(header) => {
+        read_header_lines(
+          reader,
+          header.line_comment_prefix(),
+          header.line_skip_count(),
+        )
+        .await
+      }
+      None => Self::read_header(reader).await,
+    }
+  }
+
+  async fn read_index_inner<T: AsyncRead + Unpin + Send>(
+    inner: T,
+  ) -> io::Result<Index<LinearIndex>> {
+    tabix::AsyncReader::new(inner).read_index().await
+  }
+
+  #[instrument(level = "trace", skip(self, index, query))]
+  async fn get_byte_ranges_for_reference_name(
+    &self,
+    reference_name: String,
+    index: &Index<LinearIndex>,
+    _header: &Header,
+    query: &Query,
+  ) -> Result<Vec<BytesPosition>> {
+    trace!("getting byte ranges for reference name");
+    // Annotation files have no reference sequence dictionary in their header, so the names
+    // in the tabix header are the only source of reference names.
+    let mut futures = FuturesOrdered::new();
+    for (index, name) in index
+      .header()
+      .ok_or_else(|| HtsGetError::parse_error("no tabix header found in index"))?
+      .reference_sequence_names()
+      .iter()
+      .enumerate()
+    {
+      let owned_name = name.to_owned();
+      let owned_reference_name = reference_name.clone();
+      futures.push_back(tokio::spawn(async move {
+        if owned_name == owned_reference_name {
+          Some(index)
+        } else {
+          None
+        }
+      }));
+    }
+
+    let ref_seq_id = find_first(
+      &format!("reference name not found in index: {reference_name}"),
+      futures,
+    )
+    .await?;
+
+    self
+      .get_byte_ranges_for_reference_sequence_bgzf(query, ref_seq_id, index)
+      .await
+  }
+
+  fn get_storage(&self) -> &Storage {
+    &self.storage
+  }
+
+  fn get_keys(&self) -> &SearchKeys {
+    &self.keys
+  }
+
+  fn mut_keys(&mut self) -> &mut SearchKeys {
+    &mut self.keys
+  }
+
+  fn mut_storage(&mut self) -> &mut Storage {
+    &mut self.storage
+  }
+
+  fn get_format(&self) -> Format {
+    self.format
+  }
+}
+
+impl AnnotationSearch {
+  /// Create the annotation search for a BED, GFF3 or GTF format.
+  pub fn new(storage: Storage, format: Format) -> Self {
+    Self {
+      storage,
+      keys: Default::default(),
+      format,
+    }
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use htsget_config::types::Class::Body;
+  use htsget_test::http::concat::ConcatResponse;
+  use std::future::Future;
+
+  use super::*;
+  use crate::from_storage::tests::with_local_storage_fn;
+  use crate::{Class::Header, Headers, HtsGetError::NotFound, Response, Url};
+
+  const ANNOTATIONS_LOCATION: &str = "data/annotations";
+
+  #[tokio::test]
+  async fn read_header_lines_with_skip_count() {
+    let mut data: &[u8] = b"browser position chr1\ntrack name=sample\n#comment\nchr1\t0\t10\n";
+    let header = read_header_lines(&mut data, b'#', 2).await.unwrap();
+
+    assert_eq!(
+      header,
+      b"browser position chr1\ntrack name=sample\n#comment\n"
+    );
+    assert_eq!(data, b"chr1\t0\t10\n");
+  }
+
+  #[tokio::test]
+  async fn read_header_lines_with_comment_prefix() {
+    let mut data: &[u8] = b"%comment\n#not a comment\n";
+    let header = read_header_lines(&mut data, b'%', 0).await.unwrap();
+
+    assert_eq!(header, b"%comment\n");
+    assert_eq!(data, b"#not a comment\n");
+  }
+
+  #[tokio::test]
+  async fn search_all_bed() {
+    with_local_storage(|storage| async move {
+      let mut search = AnnotationSearch::new(storage, Format::Bed);
+      let query = Query::new_with_default_request("sample", Format::Bed);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bed,
+        vec![Url::new(expected_url("sample.bed.gz"))
+          .with_headers(Headers::default().with_header("Range", "bytes=0-75054"))],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        "sample.bed.gz".to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_with_seq_range_bed() {
+    with_local_storage(|storage| async move {
+      let mut search = AnnotationSearch::new(storage, Format::Bed);
+      let query = Query::new_with_default_request("sample", Format::Bed)
+        .with_reference_name("chr1")
+        .with_start(0)
+        .with_end(10000);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bed,
+        vec![
+          Url::new(expected_url("sample.bed.gz"))
+            .with_headers(Headers::default().with_header("Range", "bytes=0-32940")),
+          Url::new(expected_url("sample.bed.gz"))
+            .with_headers(Headers::default().with_header("Range", "bytes=75027-75054")),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        "sample.bed.gz".to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_without_seq_range_gff3() {
+    with_local_storage(|storage| async move {
+      let mut search = AnnotationSearch::new(storage, Format::Gff3);
+      let query =
+        Query::new_with_default_request("sample", Format::Gff3).with_reference_name("chr2");
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Gff3,
+        vec![
+          Url::new(expected_url("sample.gff3.gz"))
+            .with_headers(Headers::default().with_header("Range", "bytes=0-12293"))
+            .with_class(Header),
+          Url::new(expected_url("sample.gff3.gz"))
+            .with_headers(Headers::default().with_header("Range", "bytes=59457-78659"))
+            .with_class(Body),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        "sample.gff3.gz".to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_with_seq_range_gtf() {
+    with_local_storage(|storage| async move {
+      let mut search = AnnotationSearch::new(storage, Format::Gtf);
+      let query = Query::new_with_default_request("sample", Format::Gtf)
+        .with_reference_name("chr2")
+        .with_start(100000)
+        .with_end(150000);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Gtf,
+        vec![
+          Url::new(expected_url("sample.gtf.gz"))
+            .with_headers(Headers::default().with_header("Range", "bytes=0-11045"))
+            .with_class(Header),
+          Url::new(expected_url("sample.gtf.gz"))
+            .with_headers(Headers::default().with_header("Range", "bytes=64085-80621"))
+            .with_class(Body),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        "sample.gtf.gz".to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_header_bed() {
+    with_local_storage(|storage| async move {
+      let mut search = AnnotationSearch::new(storage, Format::Bed);
+      let query = Query::new_with_default_request("sample", Format::Bed).with_class(Header);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bed,
+        vec![Url::new(expected_url("sample.bed.gz"))
+          .with_headers(Headers::default().with_header("Range", "bytes=0-16754"))
+          .with_class(Header)],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        "sample.bed.gz".to_string(),
+        (response.unwrap(), Header).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_non_existent_reference_name() {
+    with_local_storage(|storage| async move {
+      let mut search = AnnotationSearch::new(storage, Format::Bed);
+      let query =
+        Query::new_with_default_request("sample", Format::Bed).with_reference_name("chr3");
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      assert!(matches!(response, Err(NotFound(_))));
+
+      None
+    })
+    .await;
+  }
+
+  async fn with_local_storage<F, Fut>(test: F)
+  where
+    F: FnOnce(Storage) -> Fut,
+    Fut: Future<Output = Option<(String, ConcatResponse)>>,
+  {
+    with_local_storage_fn(test, ANNOTATIONS_LOCATION, &[]).await
+  }
+
+  fn expected_url(name: &str) -> String {
+    format!("http://127.0.0.1:8081/{name}")
+  }
+}
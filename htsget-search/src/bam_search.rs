@@ -1,46 +1,168 @@
-//! Module providing the search capability using BAM/BAI files
+//! Module providing the search capability using BAM files, indexed with either BAI or CSI.
 //!
 
+use std::marker::PhantomData;
+use std::path::Path;
+
 use async_trait::async_trait;
-use noodles::bam;
 use noodles::bam::bai;
-use noodles::bam::bai::Index;
-use noodles::bgzf;
 use noodles::bgzf::VirtualPosition;
-use noodles::csi::binning_index::index::reference_sequence::index::LinearIndex;
-use noodles::csi::binning_index::index::ReferenceSequence;
+use noodles::csi::binning_index::index::reference_sequence::bin::Chunk;
+use noodles::csi::binning_index::index::reference_sequence::index::{BinnedIndex, LinearIndex};
+use noodles::csi::binning_index::index::{reference_sequence, Index, ReferenceSequence};
+use noodles::csi::binning_index::Indexer;
+use noodles::csi::binning_index::ReferenceSequence as _;
 use noodles::csi::BinningIndex;
+use noodles::sam::alignment::Record as _;
 use noodles::sam::Header;
+use noodles::{bam, bgzf, csi};
 use tokio::io;
 use tokio::io::{AsyncRead, BufReader};
 use tracing::{instrument, trace};
 
-use crate::search::{BgzfSearch, Search, SearchAll, SearchReads};
+use crate::search::{first_existing_key, BgzfSearch, Search, SearchAll, SearchKeys, SearchReads};
 use crate::Class::Body;
 use crate::HtsGetError;
 use crate::{Format, Query, Result};
-use htsget_storage::types::BytesPosition;
-use htsget_storage::{Storage, Streamable};
+use htsget_storage::types::{BytesPosition, GetOptions};
+use htsget_storage::{Storage, StorageTrait, Streamable};
 
 type AsyncReader = bam::AsyncReader<bgzf::AsyncReader<Streamable>>;
 
-/// Allows searching through bam files.
-pub struct BamSearch {
+/// An index type that can be used to search BAM files.
+#[async_trait]
+pub trait BamIndex: reference_sequence::Index + Send + Sync + Sized + 'static {
+  /// Get the default key of the index for the id.
+  fn index_key(id: &str) -> io::Result<String>;
+
+  /// Check whether the key refers to this type of index.
+  fn is_index_key(key: &str) -> bool;
+
+  /// Read the index.
+  async fn read_index<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<Self>>;
+
+  /// Build the index by reading all records, returning `None` if this type of index cannot be built.
+  async fn build_index(
+    reader: &mut AsyncReader,
+    header: &Header,
+  ) -> io::Result<Option<Index<Self>>>;
+
+  /// Write the index to the path.
+  fn write_index(path: &Path, index: &Index<Self>) -> io::Result<()>;
+}
+
+#[async_trait]
+impl BamIndex for LinearIndex {
+  fn index_key(id: &str) -> io::Result<String> {
+    Ok(Format::Bam.fmt_index(id))
+  }
+
+  fn is_index_key(key: &str) -> bool {
+    !BinnedIndex::is_index_key(key)
+  }
+
+  async fn read_index<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<Self>> {
+    let mut reader = bai::AsyncReader::new(BufReader::new(inner));
+    reader.read_index().await
+  }
+
+  async fn build_index(
+    reader: &mut AsyncReader,
+    header: &Header,
+  ) -> io::Result<Option<Index<Self>>> {
+    let mut indexer = Indexer::default();
+    let mut record = bam::Record::default();
+
+    let mut chunk_start = reader.get_ref().virtual_position();
+    while reader.read_record(&mut record).await? != 0 {
+      let chunk_end = reader.get_ref().virtual_position();
+
+      let alignment_context = match (
+        record.reference_sequence_id().transpose()?,
+        record.alignment_start().transpose()?,
+        record.alignment_end().transpose()?,
+      ) {
+        (Some(id), Some(start), Some(end)) => Some((id, start, end, !record.flags().is_unmapped())),
+        _ => None,
+      };
+
+      indexer.add_record(alignment_context, Chunk::new(chunk_start, chunk_end))?;
+      chunk_start = chunk_end;
+    }
+
+    Ok(Some(indexer.build(header.reference_sequences().len())))
+  }
+
+  fn write_index(path: &Path, index: &Index<Self>) -> io::Result<()> {
+    bai::fs::write(path, index)
+  }
+}
+
+#[async_trait]
+impl BamIndex for BinnedIndex {
+  fn index_key(id: &str) -> io::Result<String> {
+    Format::Bam.fmt_csi_index(id)
+  }
+
+  fn is_index_key(key: &str) -> bool {
+    key.ends_with(".csi")
+  }
+
+  async fn read_index<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<Self>> {
+    csi::AsyncReader::new(inner).read_index().await
+  }
+
+  async fn build_index(
+    _reader: &mut AsyncReader,
+    _header: &Header,
+  ) -> io::Result<Option<Index<Self>>> {
+    Ok(None)
+  }
+
+  fn write_index(path: &Path, index: &Index<Self>) -> io::Result<()> {
+    csi::fs::write(path, index)
+  }
+}
+
+/// Allows searching through bam files. By default, this uses a BAI index.
+pub struct BamSearch<I = LinearIndex> {
   storage: Storage,
+  keys: SearchKeys,
+  index: PhantomData<I>,
 }
 
+/// Allows searching through bam files using a CSI index.
+pub type BamCsiSearch = BamSearch<BinnedIndex>;
+
 #[async_trait]
-impl BgzfSearch<LinearIndex, AsyncReader, Header> for BamSearch {
+impl<I: BamIndex> BgzfSearch<I, AsyncReader, Header> for BamSearch<I> {
   #[instrument(level = "trace", skip(self, index))]
   async fn get_byte_ranges_for_unmapped(
     &self,
     query: &Query,
-    index: &Index,
+    index: &Index<I>,
   ) -> Result<Vec<BytesPosition>> {
     trace!("getting byte ranges for unmapped reads");
-    let last_interval = index.last_first_record_start_position();
-    let start = match last_interval {
-      Some(start) => start,
+    if index.unplaced_unmapped_record_count() == Some(0) {
+      return Ok(vec![]);
+    }
+
+    // Unplaced unmapped reads are stored after all placed reads, so they start where the last
+    // placed read ends.
+    let last_placed_end = index
+      .reference_sequences()
+      .iter()
+      .flat_map(|ref_seq| {
+        ref_seq
+          .bins()
+          .values()
+          .flat_map(|bin| bin.chunks())
+          .map(|chunk| chunk.end())
+          .chain(ref_seq.metadata().map(|metadata| metadata.end_position()))
+      })
+      .max();
+    let start = match last_placed_end {
+      Some(end) => end,
       None => {
         VirtualPosition::try_from((self.get_header_end_offset(index).await?, 0)).map_err(|err| {
           HtsGetError::InvalidInput(format!(
@@ -66,7 +188,7 @@ impl BgzfSearch<LinearIndex, AsyncReader, Header> for BamSearch {
 }
 
 #[async_trait]
-impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for BamSearch {
+impl<I: BamIndex> Search<ReferenceSequence<I>, Index<I>, AsyncReader, Header> for BamSearch<I> {
   fn init_reader(inner: Streamable) -> AsyncReader {
     AsyncReader::new(inner)
   }
@@ -75,16 +197,51 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for BamS
     reader.read_header().await
   }
 
-  async fn read_index_inner<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index> {
-    let mut reader = bai::AsyncReader::new(BufReader::new(inner));
-    reader.read_index().await
+  async fn read_index_inner<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<I>> {
+    I::read_index(inner).await
+  }
+
+  #[instrument(level = "debug", skip(self))]
+  async fn build_index(&self, query: &Query) -> Result<Option<Index<I>>> {
+    let file = self
+      .get_storage()
+      .get(
+        &self.file_key(query).await?,
+        GetOptions::new_with_default_range(query.request().headers()),
+      )
+      .await?;
+
+    let mut reader = Self::init_reader(file);
+    let header = Self::read_header(&mut reader).await?;
+
+    Ok(I::build_index(&mut reader, &header).await?)
+  }
+
+  fn write_index(path: &Path, index: &Index<I>) -> io::Result<()> {
+    I::write_index(path, index)
+  }
+
+  async fn resolve_index_key(&self, query: &Query) -> Result<String> {
+    let keys: Vec<String> = query
+      .options()
+      .naming()
+      .index_keys(Format::Bam, query.id())
+      .into_iter()
+      .filter(|key| I::is_index_key(key))
+      .collect();
+
+    if keys.is_empty() {
+      Ok(I::index_key(query.id())?)
+    } else {
+      first_existing_key(self.get_storage(), keys, query).await
+    }
   }
 
   #[instrument(level = "trace", skip(self, index, header, query))]
   async fn get_byte_ranges_for_reference_name(
     &self,
     reference_name: String,
-    index: &Index,
+    index: &Index<I>,
     header: &Header,
     query: &Query,
   ) -> Result<Vec<BytesPosition>> {
@@ -98,6 +255,14 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for BamS
     &self.storage
   }
 
+  fn get_keys(&self) -> &SearchKeys {
+    &self.keys
+  }
+
+  fn mut_keys(&mut self) -> &mut SearchKeys {
+    &mut self.keys
+  }
+
   fn mut_storage(&mut self) -> &mut Storage {
     &mut self.storage
   }
@@ -108,7 +273,9 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for BamS
 }
 
 #[async_trait]
-impl SearchReads<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for BamSearch {
+impl<I: BamIndex> SearchReads<ReferenceSequence<I>, Index<I>, AsyncReader, Header>
+  for BamSearch<I>
+{
   async fn get_reference_sequence_from_name<'a>(
     &self,
     header: &'a Header,
@@ -120,17 +287,19 @@ impl SearchReads<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for
   async fn get_byte_ranges_for_unmapped_reads(
     &self,
     query: &Query,
-    bai_index: &Index,
+    index: &Index<I>,
   ) -> Result<Vec<BytesPosition>> {
-    self.get_byte_ranges_for_unmapped(query, bai_index).await
+    self.get_byte_ranges_for_unmapped(query, index).await
   }
 
   async fn get_byte_ranges_for_reference_sequence(
     &self,
     ref_seq_id: usize,
     query: &Query,
-    index: &Index,
+    index: &Index<I>,
   ) -> Result<Vec<BytesPosition>> {
+    // The bin sizes and number of levels are read from the index, so this handles both the
+    // fixed BAI binning scheme and CSI indexes with larger `min_shift` or `depth` values.
     self
       .get_byte_ranges_for_reference_sequence_bgzf(query, ref_seq_id, index)
       .await
@@ -138,9 +307,38 @@ impl SearchReads<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for
 }
 
 impl BamSearch {
-  /// Create the bam search.
+  /// Create the bam search using a BAI index.
   pub fn new(storage: Storage) -> Self {
-    Self { storage }
+    Self {
+      storage,
+      keys: Default::default(),
+      index: PhantomData,
+    }
+  }
+}
+
+impl BamCsiSearch {
+  /// Create the bam search using a CSI index.
+  pub fn new_csi(storage: Storage) -> Self {
+    Self {
+      storage,
+      keys: Default::default(),
+      index: PhantomData,
+    }
+  }
+
+  /// Check whether an index key refers to a CSI index.
+  pub fn uses_csi_index(key: &str) -> bool {
+    BinnedIndex::is_index_key(key)
+  }
+}
+
+impl<I> BamSearch<I> {
+  /// Set the index key of the query if it has already been resolved, so that it is not probed
+  /// again.
+  pub fn with_index_key(mut self, query: &Query, index_key: Option<String>) -> Self {
+    self.keys = SearchKeys::with_index_key(query, index_key);
+    self
   }
 }
 
@@ -150,17 +348,28 @@ pub(crate) mod tests {
   #[cfg(feature = "aws")]
   use crate::from_storage::tests::with_aws_storage_fn;
   use crate::from_storage::tests::with_local_storage_fn;
+  use crate::search::existing_index_key;
   use crate::{Class::Body, Class::Header, Headers, HtsGetError::NotFound, Response, Url};
+  use htsget_config::storage::options::LocationOptions;
   use htsget_test::http::concat::ConcatResponse;
   use std::future::Future;
+  use std::{env, fs};
+  use tempfile::TempDir;
   #[cfg(feature = "experimental")]
   use {
-    crate::from_storage::tests::with_local_storage_c4gh,
-    htsget_storage::c4gh::storage::C4GHStorage, htsget_test::c4gh::get_decryption_keys,
+    crate::from_storage::tests::{
+      with_local_storage_c4gh, with_local_storage_c4gh_encrypted_index,
+    },
+    htsget_storage::c4gh::storage::C4GHStorage,
+    htsget_test::c4gh::get_decryption_keys,
   };
 
   const DATA_LOCATION: &str = "data/bam";
   const INDEX_FILE_LOCATION: &str = "htsnexus_test_NA12878.bam.bai";
+  const GZI_FILE_LOCATION: &str = "htsnexus_test_NA12878.bam.gzi";
+  const CSI_DATA_LOCATION: &str = "data/csi";
+  const CSI_INDEX_FILE_LOCATION: &str = "htsnexus_test_NA12878.bam.csi";
+  const MAPPED_DATA_LOCATION: &str = "data/mapped";
   pub(crate) const BAM_FILE_NAME: &str = "htsnexus_test_NA12878.bam";
 
   #[tokio::test]
@@ -199,7 +408,7 @@ pub(crate) mod tests {
             .with_headers(Headers::default().with_header("Range", "bytes=0-4667"))
             .with_class(Header),
           Url::new(expected_url())
-            .with_headers(Headers::default().with_header("Range", "bytes=2060795-2596798"))
+            .with_headers(Headers::default().with_header("Range", "bytes=2112141-2596798"))
             .with_class(Body),
         ],
       ));
@@ -210,6 +419,37 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_unmapped_reads_without_unmapped_reads() {
+    with_local_storage_fn(
+      |storage| async move {
+        let mut search = BamSearch::new(storage);
+        let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+          .with_reference_name("*");
+        let response = search.search(query).await;
+        println!("{response:#?}");
+
+        let expected_response = Ok(Response::new(
+          Format::Bam,
+          vec![
+            Url::new(expected_url())
+              .with_headers(Headers::default().with_header("Range", "bytes=0-4667"))
+              .with_class(Header),
+            Url::new(expected_url())
+              .with_headers(Headers::default().with_header("Range", "bytes=83741-83768"))
+              .with_class(Body),
+          ],
+        ));
+        assert_eq!(response, expected_response);
+
+        Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+      },
+      MAPPED_DATA_LOCATION,
+      &[],
+    )
+    .await;
+  }
+
   #[tokio::test]
   async fn search_reference_name_without_seq_range_chr11() {
     with_local_storage(|storage| async move {
@@ -298,6 +538,33 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_reference_name_with_overlapping_regions() {
+    with_local_storage(|storage| async move {
+      let mut search = BamSearch::new(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+        .with_reference_name("11")
+        .with_start(5015000)
+        .with_end(5050000)
+        .with_region("11", Default::default());
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bam,
+        vec![
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=0-996014")),
+          expected_eof_url().set_class(None),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+    })
+    .await;
+  }
+
   #[tokio::test]
   async fn search_reference_name_no_end_position() {
     with_local_storage(|storage| async move {
@@ -420,6 +687,48 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_header_inline() {
+    with_local_storage(|storage| async move {
+      let mut search = BamSearch::new(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+        .with_class(Header)
+        .with_options(LocationOptions::default().with_inline_threshold(Some(4668)));
+      let response = search.search(query).await.unwrap();
+      println!("{response:#?}");
+
+      assert_eq!(response.urls.len(), 1);
+      assert!(response.urls[0].url.starts_with("data:;base64,"));
+      assert_eq!(response.urls[0].class, Some(Header));
+
+      Some((BAM_FILE_NAME.to_string(), (response, Header).into()))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_header_above_inline_threshold() {
+    with_local_storage(|storage| async move {
+      let mut search = BamSearch::new(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+        .with_class(Header)
+        .with_options(LocationOptions::default().with_inline_threshold(Some(4667)));
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bam,
+        vec![Url::new(expected_url())
+          .with_headers(Headers::default().with_header("Range", "bytes=0-4667"))
+          .with_class(Header)],
+      ));
+      assert_eq!(response, expected_response);
+
+      None
+    })
+    .await;
+  }
+
   #[tokio::test]
   async fn search_header_with_no_mapped_reads() {
     with_local_storage(|storage| async move {
@@ -591,6 +900,112 @@ pub(crate) mod tests {
     .await
   }
 
+  #[tokio::test]
+  async fn search_all_reads_csi() {
+    with_local_storage_csi(|storage| async move {
+      let mut search = BamCsiSearch::new_csi(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bam,
+        vec![Url::new(expected_url())
+          .with_headers(Headers::default().with_header("Range", "bytes=0-2596798"))],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_unmapped_reads_csi() {
+    with_local_storage_csi(|storage| async move {
+      let mut search = BamCsiSearch::new_csi(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+        .with_reference_name("*");
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bam,
+        vec![
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=0-4667"))
+            .with_class(Header),
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=2112141-2596798"))
+            .with_class(Body),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_with_seq_range_csi() {
+    with_local_storage_csi(|storage| async move {
+      let mut search = BamCsiSearch::new_csi(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+        .with_reference_name("11")
+        .with_start(5015000)
+        .with_end(5050000);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Bam,
+        vec![
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=0-4667"))
+            .with_class(Header),
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=256721-647345"))
+            .with_class(Body),
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=824361-842100"))
+            .with_class(Body),
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=977196-996014"))
+            .with_class(Body),
+          expected_eof_url(),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn uses_csi_index() {
+    with_local_storage_csi(|storage| async move {
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam);
+      assert!(existing_index_key(&storage, &query)
+        .await
+        .is_some_and(|key| BamCsiSearch::uses_csi_index(&key)));
+
+      None
+    })
+    .await;
+
+    with_local_storage(|storage| async move {
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam);
+      assert!(!existing_index_key(&storage, &query)
+        .await
+        .is_some_and(|key| BamCsiSearch::uses_csi_index(&key)));
+
+      None
+    })
+    .await;
+  }
+
   #[cfg(feature = "experimental")]
   #[tokio::test]
   async fn search_all_c4gh() {
@@ -610,6 +1025,32 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn search_range_c4gh_encrypted_index() {
+    with_local_storage_c4gh_encrypted_index(
+      |storage| async move {
+        let storage = C4GHStorage::new(get_decryption_keys().await, storage);
+        let mut search = BamSearch::new(Storage::new(storage));
+        let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+          .with_reference_name("11")
+          .with_start(5015000)
+          .with_end(5050000);
+        let response = search.search(query).await.unwrap();
+
+        println!("{:#?}", response);
+
+        Some((
+          "htsnexus_test_NA12878.bam.c4gh".to_string(),
+          (response, Body).into(),
+        ))
+      },
+      "htsnexus_test_NA12878.bam.c4gh",
+      "htsnexus_test_NA12878.bam.bai",
+    )
+    .await;
+  }
+
   #[cfg(feature = "experimental")]
   #[tokio::test]
   async fn search_all_range_c4gh() {
@@ -640,6 +1081,30 @@ pub(crate) mod tests {
     with_local_storage_fn(test, DATA_LOCATION, &[]).await
   }
 
+  /// Run the test with the BAM file indexed using CSI rather than BAI.
+  pub(crate) async fn with_local_storage_csi<F, Fut>(test: F)
+  where
+    F: FnOnce(Storage) -> Fut,
+    Fut: Future<Output = Option<(String, ConcatResponse)>>,
+  {
+    let tmp_dir = TempDir::new().unwrap();
+    let data_path = env::current_dir().unwrap().parent().unwrap().to_path_buf();
+
+    for (location, file_name) in [
+      (DATA_LOCATION, BAM_FILE_NAME),
+      (DATA_LOCATION, GZI_FILE_LOCATION),
+      (CSI_DATA_LOCATION, CSI_INDEX_FILE_LOCATION),
+    ] {
+      fs::copy(
+        data_path.join(location).join(file_name),
+        tmp_dir.path().join(file_name),
+      )
+      .unwrap();
+    }
+
+    with_local_storage_fn(test, tmp_dir.path().to_str().unwrap(), &[]).await
+  }
+
   pub(crate) fn expected_url() -> String {
     "http://127.0.0.1:8081/htsnexus_test_NA12878.bam".to_string()
   }
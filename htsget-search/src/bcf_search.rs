@@ -14,7 +14,7 @@ use tokio::io;
 use tokio::io::AsyncRead;
 use tracing::{instrument, trace};
 
-use crate::search::{find_first, BgzfSearch, Search};
+use crate::search::{find_first, BgzfSearch, Search, SearchKeys};
 use crate::{Format, Query, Result};
 use htsget_storage::types::BytesPosition;
 use htsget_storage::{Storage, Streamable};
@@ -24,6 +24,7 @@ type AsyncReader = bcf::AsyncReader<bgzf::AsyncReader<Streamable>>;
 /// Allows searching through bcf files.
 pub struct BcfSearch {
   storage: Storage,
+  keys: SearchKeys,
 }
 
 #[async_trait]
@@ -90,6 +91,14 @@ impl Search<ReferenceSequence<BinnedIndex>, Index, AsyncReader, Header> for BcfS
     &self.storage
   }
 
+  fn get_keys(&self) -> &SearchKeys {
+    &self.keys
+  }
+
+  fn mut_keys(&mut self) -> &mut SearchKeys {
+    &mut self.keys
+  }
+
   fn mut_storage(&mut self) -> &mut Storage {
     &mut self.storage
   }
@@ -102,7 +111,10 @@ impl Search<ReferenceSequence<BinnedIndex>, Index, AsyncReader, Header> for BcfS
 impl BcfSearch {
   /// Create the bcf search.
   pub fn new(storage: Storage) -> Self {
-    Self { storage }
+    Self {
+      storage,
+      keys: Default::default(),
+    }
   }
 }
 
@@ -1,8 +1,11 @@
 //! This module provides search capabilities for CRAM files.
 //!
 
+use std::collections::{BTreeMap, HashMap};
+use std::iter;
 use std::marker::PhantomData;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
 use futures::StreamExt;
@@ -12,18 +15,20 @@ use noodles::cram;
 use noodles::cram::crai;
 use noodles::cram::crai::{Index, Record};
 use noodles::sam::Header;
-use tokio::io::{AsyncRead, BufReader};
-use tokio::{io, select};
+use tempfile::NamedTempFile;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::{io, select, task};
 use tracing::{instrument, trace};
 
 use htsget_config::types::Class::Header as HtsGetHeader;
 use htsget_config::types::Interval;
 
-use crate::search::{Search, SearchAll, SearchReads};
+use crate::search::{Search, SearchAll, SearchKeys, SearchReads};
 use crate::Class::Body;
 use crate::{ConcurrencyError, ParsedHeader};
 use crate::{Format, HtsGetError, Query, Result};
-use htsget_storage::types::{BytesPosition, DataBlock};
+use htsget_storage::types::{BytesPosition, DataBlock, GetOptions};
 use htsget_storage::{Storage, Streamable};
 
 // § 9 End of file container <https://samtools.github.io/hts-specs/CRAMv3.pdf>.
@@ -33,11 +38,66 @@ static CRAM_EOF: &[u8] = &[
   0x01, 0x00, 0xee, 0x63, 0x01, 0x4b,
 ];
 
+/// The maximum number of bytes read when parsing a container header.
+const MAX_CONTAINER_HEADER_SIZE: u64 = 1024;
+/// The maximum number of bytes read when parsing a slice header block.
+const MAX_SLICE_HEADER_SIZE: u64 = 1024;
+
 type AsyncReader = cram::AsyncReader<BufReader<Streamable>>;
 
 /// Allows searching through cram files.
 pub struct CramSearch {
   storage: Storage,
+  keys: SearchKeys,
+  /// Containers which only have some of their slices returned, keyed by their offset.
+  partial_containers: Mutex<BTreeMap<u64, PartialContainer>>,
+}
+
+/// A container which only has some of its slices returned, with the parsed data needed to
+/// rewrite its header.
+struct PartialContainer {
+  header: ContainerHeader,
+  /// The position of the end of the container.
+  end: u64,
+  /// The position of the data after the container header.
+  data_start: u64,
+  compression_header: Vec<u8>,
+  /// The landmarks and lengths of all slices in the container.
+  slices: Vec<(u64, u64)>,
+  /// The headers of the returned slices, keyed by their landmark.
+  slice_headers: HashMap<u64, SliceHeader>,
+}
+
+impl PartialContainer {
+  /// Get the rewritten container header, followed by the compression header, for a byte range
+  /// which starts at one of the slices in this container.
+  fn header_for(&self, start: u64, end: u64) -> Option<Vec<u8>> {
+    let first = self
+      .slices
+      .iter()
+      .position(|(landmark, _)| self.data_start + landmark == start)?;
+    let slices: Vec<_> = self.slices[first..]
+      .iter()
+      .take_while(|(landmark, length)| self.data_start + landmark + length <= end)
+      .copied()
+      .collect();
+    let headers = slices
+      .iter()
+      .map(|(landmark, _)| self.slice_headers.get(landmark).cloned())
+      .collect::<Option<Vec<_>>>()?;
+    if headers.is_empty() {
+      return None;
+    }
+
+    let mut data = self
+      .header
+      .with_slices(self.slices[0].0, &slices, &headers)
+      .ok()?
+      .encode();
+    data.extend(&self.compression_header);
+
+    Some(data)
+  }
 }
 
 #[async_trait]
@@ -152,6 +212,34 @@ impl Search<PhantomData<Self>, Index, AsyncReader, Header> for CramSearch {
     crai::AsyncReader::new(inner).read_index().await
   }
 
+  /// Build a CRAI index by scanning the containers of the CRAM file. The file is copied to a
+  /// temporary file first, because indexing reads the slices of each container.
+  #[instrument(level = "debug", skip(self))]
+  async fn build_index(&self, query: &Query) -> Result<Option<Index>> {
+    let mut file = self
+      .get_storage()
+      .get(
+        &self.file_key(query).await?,
+        GetOptions::new_with_default_range(query.request().headers()),
+      )
+      .await?;
+
+    let tmp_file = NamedTempFile::new()?;
+    let mut writer = File::create(tmp_file.path()).await?;
+    io::copy(&mut file, &mut writer).await?;
+    writer.flush().await?;
+
+    let index = task::spawn_blocking(move || cram::fs::index(tmp_file.path()))
+      .await
+      .map_err(ConcurrencyError::new)??;
+
+    Ok(Some(index))
+  }
+
+  fn write_index(path: &Path, index: &Index) -> io::Result<()> {
+    crai::fs::write(path, index)
+  }
+
   async fn get_byte_ranges_for_reference_name(
     &self,
     reference_name: String,
@@ -168,6 +256,14 @@ impl Search<PhantomData<Self>, Index, AsyncReader, Header> for CramSearch {
     &self.storage
   }
 
+  fn get_keys(&self) -> &SearchKeys {
+    &self.keys
+  }
+
+  fn mut_keys(&mut self) -> &mut SearchKeys {
+    &mut self.keys
+  }
+
   fn mut_storage(&mut self) -> &mut Storage {
     &mut self.storage
   }
@@ -175,12 +271,114 @@ impl Search<PhantomData<Self>, Index, AsyncReader, Header> for CramSearch {
   fn get_format(&self) -> Format {
     Format::Cram
   }
+
+  fn update_data_blocks(&self, blocks: Vec<DataBlock>) -> Vec<DataBlock> {
+    let partial_containers = self
+      .partial_containers
+      .lock()
+      .unwrap_or_else(|err| err.into_inner());
+    if partial_containers.is_empty() {
+      return blocks;
+    }
+
+    // Ranges may have been coalesced over slices or parts of containers that were not selected,
+    // so they are widened to whole containers where a header cannot be rewritten for them.
+    let mut normalised: Vec<DataBlock> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+      let block = match block {
+        DataBlock::Range(range) => {
+          DataBlock::Range(Self::normalise_range(&partial_containers, range))
+        }
+        data => data,
+      };
+
+      match (normalised.last_mut(), block) {
+        (Some(DataBlock::Range(last)), DataBlock::Range(range)) if last.overlaps(&range) => {
+          last.merge_with(&range);
+        }
+        (_, block) => normalised.push(block),
+      }
+    }
+
+    // Byte ranges which start inside a container need a rewritten container header. Ranges
+    // from several regions may have been merged, so the header is built from all slices that
+    // the merged range covers.
+    normalised
+      .into_iter()
+      .flat_map(|block| {
+        let header = match &block {
+          DataBlock::Range(range) => range.get_start().and_then(|start| {
+            let (_, partial) = partial_containers.range(..start).next_back()?;
+            partial.header_for(start, range.get_end().unwrap_or(u64::MAX))
+          }),
+          DataBlock::Data(..) => None,
+        };
+
+        header
+          .map(|header| DataBlock::Data(header, Some(Body)))
+          .into_iter()
+          .chain(iter::once(block))
+      })
+      .collect()
+  }
 }
 
 impl CramSearch {
+  /// Widen a range until it either starts at a slice that a container header can be rewritten
+  /// for, or starts at a container offset, and does not end part way through a container which
+  /// it contains the header of.
+  fn normalise_range(
+    partial_containers: &BTreeMap<u64, PartialContainer>,
+    mut range: BytesPosition,
+  ) -> BytesPosition {
+    let Some(mut start) = range.get_start() else {
+      return range;
+    };
+    let mut end = range.get_end().unwrap_or(u64::MAX);
+    if end <= start {
+      return range;
+    }
+
+    loop {
+      let mut changed = false;
+
+      if let Some((offset, partial)) = partial_containers.range(..start).next_back() {
+        if start < partial.end && partial.header_for(start, end).is_none() {
+          start = *offset;
+          changed = true;
+        }
+      }
+
+      if let Some(partial_end) = partial_containers
+        .range(start..end)
+        .map(|(_, partial)| partial.end)
+        .filter(|partial_end| *partial_end > end)
+        .max()
+      {
+        end = partial_end;
+        changed = true;
+      }
+
+      if !changed {
+        break;
+      }
+    }
+
+    range = range.with_start(start);
+    if range.get_end().is_some() {
+      range = range.with_end(end);
+    }
+
+    range
+  }
+
   /// Create the cram search.
   pub fn new(storage: Storage) -> Self {
-    Self { storage }
+    Self {
+      storage,
+      keys: Default::default(),
+      partial_containers: Default::default(),
+    }
   }
 
   /// Get bytes ranges using the index.
@@ -195,53 +393,211 @@ impl CramSearch {
     F: Fn(&Record) -> bool + Send + Sync + 'static,
   {
     trace!("getting bytes range from index");
+    if crai_index.is_empty() {
+      return Err(HtsGetError::InvalidInput(
+        "No entries found in `CRAI`".to_string(),
+      ));
+    }
+
+    // Multi-reference slices and containers with multiple slices have several entries with the
+    // same container offset, so a container ends at the next distinct offset.
+    let mut offsets: Vec<u64> = crai_index.iter().map(|record| record.offset()).collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    let offsets = Arc::new(offsets);
+    let eof = self.position_at_eof(query).await?;
+
     // This could be improved by using some sort of index mapping.
     let mut futures = FuturesOrdered::new();
-    for (record, next) in crai_index.iter().zip(crai_index.iter().skip(1)) {
+    for record in crai_index {
       let owned_record = record.clone();
-      let owned_next = next.clone();
+      let owned_offsets = offsets.clone();
       let owned_predicate = predicate.clone();
       let range = query.interval();
       futures.push_back(tokio::spawn(async move {
         if owned_predicate(&owned_record) {
-          Self::bytes_ranges_for_record(range, &owned_record, owned_next.offset())
+          let next = owned_offsets
+            .get(owned_offsets.partition_point(|offset| *offset <= owned_record.offset()))
+            .copied()
+            .unwrap_or(eof);
+          Self::bytes_ranges_for_record(range, &owned_record, next)
+            .map(|range| range.map(|range| (owned_record, range)))
         } else {
           Ok(None)
         }
       }));
     }
 
-    let mut byte_ranges = Vec::new();
+    // Group the matching records by the container that they belong to.
+    let mut containers: BTreeMap<u64, (BytesPosition, Vec<Record>)> = BTreeMap::new();
     loop {
       select! {
         Some(next) = futures.next() => {
-          if let Some(range) = next.map_err(ConcurrencyError::new).map_err(HtsGetError::from)?? {
-            byte_ranges.push(range);
+          if let Some((record, range)) = next.map_err(ConcurrencyError::new).map_err(HtsGetError::from)?? {
+            containers
+              .entry(record.offset())
+              .or_insert_with(|| (range, vec![]))
+              .1
+              .push(record);
           }
         },
         else => break
       }
     }
 
-    match crai_index.last() {
-      None => {
-        return Err(HtsGetError::InvalidInput(
-          "No entries found in `CRAI`".to_string(),
-        ));
-      }
-      Some(last) if predicate(last) => {
-        if let Some(range) =
-          Self::bytes_ranges_for_record(query.interval(), last, self.position_at_eof(query).await?)?
-        {
-          byte_ranges.push(range);
+    let mut byte_ranges = Vec::with_capacity(containers.len());
+    for (offset, (container, records)) in containers {
+      match self
+        .slice_byte_range(query, crai_index, offset, &container, &records)
+        .await
+      {
+        Ok(Some(range)) => byte_ranges.push(range),
+        Ok(None) => byte_ranges.push(container),
+        Err(err) => {
+          trace!(offset, "falling back to whole container: {}", err);
+          byte_ranges.push(container);
         }
       }
-      _ => {}
     }
 
     Ok(byte_ranges)
   }
 
+  /// Get a byte range which only covers the slices of the container at `offset` that are needed
+  /// by the `records`. The parsed container is stored so that a rewritten container header can
+  /// be returned before the byte range. Returns `None` if the whole container is needed.
+  async fn slice_byte_range(
+    &self,
+    query: &Query,
+    crai_index: &[Record],
+    offset: u64,
+    container: &BytesPosition,
+    records: &[Record],
+  ) -> Result<Option<BytesPosition>> {
+    // Encrypted byte ranges cannot be combined with rewritten container headers.
+    #[cfg(feature = "experimental")]
+    if query.encryption_scheme().is_some() {
+      return Ok(None);
+    }
+
+    let mut slices: Vec<(u64, u64)> = crai_index
+      .iter()
+      .filter(|record| record.offset() == offset)
+      .map(|record| (record.landmark(), record.slice_length()))
+      .collect();
+    slices.sort_unstable();
+    slices.dedup();
+
+    let landmarks = records.iter().map(Record::landmark);
+    let position = |landmark: u64| slices.iter().position(|slice| slice.0 == landmark);
+    let (Some(first), Some(last)) = (
+      landmarks.clone().min().and_then(position),
+      landmarks.max().and_then(position),
+    ) else {
+      return Ok(None);
+    };
+    if first == 0 && last == slices.len() - 1 {
+      return Ok(None);
+    }
+
+    let container_end = container
+      .get_end()
+      .ok_or_else(|| HtsGetError::invalid_input("container without an end position"))?;
+    let key = self.file_key(query).await?;
+
+    let bytes = self
+      .read_bytes(
+        query,
+        &key,
+        offset,
+        container_end.min(offset + MAX_CONTAINER_HEADER_SIZE),
+      )
+      .await?;
+    let (header, header_size) = ContainerHeader::read(&bytes)?;
+    if !header
+      .landmarks
+      .iter()
+      .map(|landmark| u64::try_from(*landmark).ok())
+      .eq(slices.iter().map(|slice| Some(slice.0)))
+    {
+      return Err(HtsGetError::parse_error(
+        "container landmarks do not match the index",
+      ));
+    }
+
+    let data_start = offset + header_size as u64;
+    let compression_header = self
+      .read_bytes(query, &key, data_start, data_start + slices[0].0)
+      .await?;
+
+    let selected = &slices[first..=last];
+    let mut slice_headers = Vec::with_capacity(selected.len());
+    for (landmark, length) in selected {
+      let start = data_start + landmark;
+      let bytes = self
+        .read_bytes(
+          query,
+          &key,
+          start,
+          start + (*length).min(MAX_SLICE_HEADER_SIZE),
+        )
+        .await?;
+      slice_headers.push(SliceHeader::read(&bytes)?);
+    }
+
+    let (first_landmark, _) = selected[0];
+    let (last_landmark, last_length) = selected[selected.len() - 1];
+    let start = data_start + first_landmark;
+    let end = data_start + last_landmark + last_length;
+
+    self
+      .partial_containers
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .entry(offset)
+      .or_insert_with(|| PartialContainer {
+        header,
+        end: container_end,
+        data_start,
+        compression_header,
+        slices: slices.clone(),
+        slice_headers: HashMap::new(),
+      })
+      .slice_headers
+      .extend(
+        selected
+          .iter()
+          .map(|(landmark, _)| *landmark)
+          .zip(slice_headers),
+      );
+
+    Ok(Some(
+      BytesPosition::default()
+        .with_start(start)
+        .with_end(end)
+        .with_class(Body),
+    ))
+  }
+
+  /// Read the bytes between `start` and `end` from the file.
+  async fn read_bytes(&self, query: &Query, key: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+    let reader = self
+      .get_storage()
+      .get(
+        key,
+        GetOptions::new(
+          BytesPosition::default().with_start(start).with_end(end),
+          query.request().headers(),
+        ),
+      )
+      .await?;
+
+    let mut bytes = vec![];
+    reader.take(end - start).read_to_end(&mut bytes).await?;
+
+    Ok(bytes)
+  }
+
   /// Gets bytes ranges for a specific index entry.
   pub fn bytes_ranges_for_record(
     seq_range: Interval,
@@ -270,11 +626,286 @@ impl CramSearch {
   }
 }
 
+/// The fields of a CRAM 3 container header, § 7.3 <https://samtools.github.io/hts-specs/CRAMv3.pdf>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ContainerHeader {
+  length: i32,
+  reference_sequence_id: i32,
+  alignment_start: i32,
+  alignment_span: i32,
+  record_count: i32,
+  record_counter: i64,
+  bases: i64,
+  block_count: i32,
+  landmarks: Vec<i32>,
+}
+
+impl ContainerHeader {
+  /// Read the container header from the start of the bytes, returning the header and its size.
+  /// The CRC32 of the header must match.
+  fn read(bytes: &[u8]) -> io::Result<(Self, usize)> {
+    let mut buf = bytes;
+    let length = read_i32_le(&mut buf)?;
+    let reference_sequence_id = read_itf8(&mut buf)?;
+    let alignment_start = read_itf8(&mut buf)?;
+    let alignment_span = read_itf8(&mut buf)?;
+    let record_count = read_itf8(&mut buf)?;
+    let record_counter = read_ltf8(&mut buf)?;
+    let bases = read_ltf8(&mut buf)?;
+    let block_count = read_itf8(&mut buf)?;
+    let landmarks = (0..read_itf8(&mut buf)?)
+      .map(|_| read_itf8(&mut buf))
+      .collect::<io::Result<Vec<_>>>()?;
+
+    let crc32_offset = bytes.len() - buf.len();
+    let crc32 = read_i32_le(&mut buf)? as u32;
+    if crc32 != crc32fast::hash(&bytes[..crc32_offset]) {
+      return Err(invalid_data("container header checksum mismatch"));
+    }
+
+    Ok((
+      Self {
+        length,
+        reference_sequence_id,
+        alignment_start,
+        alignment_span,
+        record_count,
+        record_counter,
+        bases,
+        block_count,
+        landmarks,
+      },
+      crc32_offset + 4,
+    ))
+  }
+
+  /// Encode the container header, including its CRC32.
+  fn encode(&self) -> Vec<u8> {
+    let mut buf = self.length.to_le_bytes().to_vec();
+    write_itf8(&mut buf, self.reference_sequence_id);
+    write_itf8(&mut buf, self.alignment_start);
+    write_itf8(&mut buf, self.alignment_span);
+    write_itf8(&mut buf, self.record_count);
+    write_ltf8(&mut buf, self.record_counter);
+    write_ltf8(&mut buf, self.bases);
+    write_itf8(&mut buf, self.block_count);
+    write_itf8(&mut buf, self.landmarks.len() as i32);
+    for landmark in &self.landmarks {
+      write_itf8(&mut buf, *landmark);
+    }
+
+    let crc32 = crc32fast::hash(&buf);
+    buf.extend(crc32.to_le_bytes());
+    buf
+  }
+
+  /// Rewrite this container header so that it only contains the `slices`, which are pairs of
+  /// landmarks and lengths, with their parsed `headers`.
+  fn with_slices(
+    &self,
+    compression_header_size: u64,
+    slices: &[(u64, u64)],
+    headers: &[SliceHeader],
+  ) -> io::Result<Self> {
+    let to_i32 = |value: u64| i32::try_from(value).map_err(|_| invalid_data("slice too large"));
+
+    let mut landmarks = Vec::with_capacity(slices.len());
+    let mut length = compression_header_size;
+    for (_, slice_length) in slices {
+      landmarks.push(to_i32(length)?);
+      length += slice_length;
+    }
+
+    let reference_sequence_id = headers[0].reference_sequence_id;
+    let (reference_sequence_id, alignment_start, alignment_span) = if headers
+      .iter()
+      .all(|header| header.reference_sequence_id == reference_sequence_id)
+      && reference_sequence_id != MULTI_REFERENCE_ID
+    {
+      let start = headers
+        .iter()
+        .map(|header| header.alignment_start)
+        .min()
+        .unwrap_or_default();
+      let end = headers
+        .iter()
+        .map(|header| header.alignment_start + header.alignment_span)
+        .max()
+        .unwrap_or_default();
+      (reference_sequence_id, start, end - start)
+    } else {
+      (MULTI_REFERENCE_ID, 0, 0)
+    };
+
+    Ok(Self {
+      length: to_i32(length)?,
+      reference_sequence_id,
+      alignment_start,
+      alignment_span,
+      record_count: headers.iter().map(|header| header.record_count).sum(),
+      record_counter: headers[0].record_counter,
+      // Slice headers do not record the number of bases, so it cannot be recomputed without
+      // decoding the slices. The full container's count is kept as an upper bound, which is
+      // informational only: readers such as htslib and noodles do not validate it against the
+      // decoded records.
+      bases: self.bases,
+      block_count: headers
+        .iter()
+        .map(|header| 1 + header.block_count)
+        .sum::<i32>()
+        + 1,
+      landmarks,
+    })
+  }
+}
+
+/// The reference sequence id used by containers and slices with multiple references.
+const MULTI_REFERENCE_ID: i32 = -2;
+
+/// The fields of a slice header needed to rewrite a container header, § 8.5
+/// <https://samtools.github.io/hts-specs/CRAMv3.pdf>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SliceHeader {
+  reference_sequence_id: i32,
+  alignment_start: i32,
+  alignment_span: i32,
+  record_count: i32,
+  record_counter: i64,
+  block_count: i32,
+}
+
+impl SliceHeader {
+  /// The raw compression method.
+  const RAW: u8 = 0;
+  /// The block content type of a slice header.
+  const MAPPED_SLICE: u8 = 2;
+
+  /// Read the slice header from the start of the bytes of a slice.
+  fn read(bytes: &[u8]) -> io::Result<Self> {
+    let mut buf = bytes;
+    let (method, content_type) = match buf {
+      [method, content_type, rest @ ..] => {
+        buf = rest;
+        (*method, *content_type)
+      }
+      _ => return Err(invalid_data("slice header block too short")),
+    };
+    if method != Self::RAW || content_type != Self::MAPPED_SLICE {
+      return Err(invalid_data("unsupported slice header block"));
+    }
+
+    // Content id, compressed size and raw size.
+    for _ in 0..3 {
+      read_itf8(&mut buf)?;
+    }
+
+    Ok(Self {
+      reference_sequence_id: read_itf8(&mut buf)?,
+      alignment_start: read_itf8(&mut buf)?,
+      alignment_span: read_itf8(&mut buf)?,
+      record_count: read_itf8(&mut buf)?,
+      record_counter: read_ltf8(&mut buf)?,
+      block_count: read_itf8(&mut buf)?,
+    })
+  }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(buf: &mut &[u8]) -> io::Result<u8> {
+  let (first, rest) = buf
+    .split_first()
+    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+  *buf = rest;
+  Ok(*first)
+}
+
+fn read_i32_le(buf: &mut &[u8]) -> io::Result<i32> {
+  let mut bytes = [0; 4];
+  for byte in &mut bytes {
+    *byte = read_u8(buf)?;
+  }
+  Ok(i32::from_le_bytes(bytes))
+}
+
+/// Read an ITF8 encoded integer, § 2.3 <https://samtools.github.io/hts-specs/CRAMv3.pdf>.
+fn read_itf8(buf: &mut &[u8]) -> io::Result<i32> {
+  let first = read_u8(buf)?;
+  let extra = first.leading_ones().min(4);
+
+  let mut value = u32::from(first & (0xff >> (extra + 1).min(4)));
+  for _ in 0..extra.min(3) {
+    value = (value << 8) | u32::from(read_u8(buf)?);
+  }
+  if extra == 4 {
+    value = (value << 4) | u32::from(read_u8(buf)? & 0x0f);
+  }
+
+  Ok(value as i32)
+}
+
+/// Write an ITF8 encoded integer.
+fn write_itf8(buf: &mut Vec<u8>, value: i32) {
+  let value = value as u32;
+  match value {
+    ..0x80 => buf.push(value as u8),
+    ..0x4000 => buf.extend([0x80 | (value >> 8) as u8, value as u8]),
+    ..0x200000 => buf.extend([0xc0 | (value >> 16) as u8, (value >> 8) as u8, value as u8]),
+    ..0x10000000 => buf.extend([
+      0xe0 | (value >> 24) as u8,
+      (value >> 16) as u8,
+      (value >> 8) as u8,
+      value as u8,
+    ]),
+    _ => buf.extend([
+      0xf0 | (value >> 28) as u8,
+      (value >> 20) as u8,
+      (value >> 12) as u8,
+      (value >> 4) as u8,
+      (value & 0x0f) as u8,
+    ]),
+  }
+}
+
+/// Read an LTF8 encoded integer, § 2.3 <https://samtools.github.io/hts-specs/CRAMv3.pdf>.
+fn read_ltf8(buf: &mut &[u8]) -> io::Result<i64> {
+  let first = read_u8(buf)?;
+  let extra = first.leading_ones();
+
+  let mut value = u64::from(first & 0xffu8.checked_shr(extra + 1).unwrap_or(0));
+  for _ in 0..extra {
+    value = (value << 8) | u64::from(read_u8(buf)?);
+  }
+
+  Ok(value as i64)
+}
+
+/// Write an LTF8 encoded integer.
+fn write_ltf8(buf: &mut Vec<u8>, value: i64) {
+  let value = value as u64;
+  let extra = (0..8)
+    .find(|extra| value >> (7 * (extra + 1)) == 0)
+    .unwrap_or(8);
+
+  if extra == 8 {
+    buf.push(0xff);
+  } else {
+    let prefix = !(0xffu8 >> extra);
+    buf.push(prefix | (value >> (8 * extra)) as u8);
+  }
+  for shift in (0..extra).rev() {
+    buf.push((value >> (8 * shift)) as u8);
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use std::future::Future;
 
   use htsget_test::http::concat::ConcatResponse;
+  use htsget_test::util::default_dir_data;
 
   use super::*;
   #[cfg(feature = "aws")]
@@ -290,6 +921,7 @@ mod tests {
   const DATA_LOCATION: &str = "data/cram";
   const INDEX_FILE_LOCATION: &str = "htsnexus_test_NA12878.cram.crai";
   const CRAM_FILE_NAME: &str = "htsnexus_test_NA12878.cram";
+  const MAPPED_DATA_LOCATION: &str = "data/mapped";
 
   #[tokio::test]
   async fn search_all_reads() {
@@ -338,6 +970,339 @@ mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_unmapped_reads_without_unmapped_reads() {
+    with_local_storage_fn(
+      |storage| async move {
+        let mut search = CramSearch::new(storage);
+        let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Cram)
+          .with_reference_name("*");
+        let response = search.search(query).await;
+        println!("{response:#?}");
+
+        let expected_response = Ok(Response::new(
+          Format::Cram,
+          vec![
+            Url::new(expected_url())
+              .with_headers(Headers::default().with_header("Range", "bytes=0-6133"))
+              .with_class(Header),
+            Url::new(expected_url())
+              .with_headers(Headers::default().with_header("Range", "bytes=480538-480575"))
+              .with_class(Body),
+          ],
+        ));
+        assert_eq!(response, expected_response);
+
+        Some((CRAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+      },
+      MAPPED_DATA_LOCATION,
+      &[],
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn bytes_ranges_from_index_multi_reference_container() {
+    with_local_storage(|storage| async move {
+      let search = CramSearch::new(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Cram)
+        .with_reference_name("*");
+      // The first container holds a multi-reference slice with placed and unmapped reads.
+      let index = vec![
+        Record::new(Some(10), Position::new(4999976), 68584, 6134, 953, 473425),
+        Record::new(None, None, 0, 6134, 953, 473425),
+        Record::new(
+          Some(19),
+          Position::new(5999958),
+          75350,
+          625728,
+          1063,
+          481723,
+        ),
+      ];
+
+      let unmapped = search
+        .bytes_ranges_from_index(
+          &query,
+          &index,
+          Arc::new(|record: &Record| record.reference_sequence_id().is_none()),
+        )
+        .await;
+      assert_eq!(
+        unmapped,
+        Ok(vec![BytesPosition::default()
+          .with_start(6134)
+          .with_end(625728)
+          .with_class(Body)])
+      );
+
+      let placed = search
+        .bytes_ranges_from_index(
+          &query,
+          &index,
+          Arc::new(|record: &Record| record.reference_sequence_id() == Some(19)),
+        )
+        .await;
+      assert_eq!(
+        placed,
+        Ok(vec![BytesPosition::default()
+          .with_start(625728)
+          .with_end(1672410)
+          .with_class(Body)])
+      );
+
+      None
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn bytes_ranges_from_index_mismatched_slices() {
+    with_local_storage(|storage| async move {
+      let search = CramSearch::new(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Cram)
+        .with_reference_name("11");
+      // The index has a second slice that the container header does not, so the whole
+      // container is returned.
+      let index = vec![
+        Record::new(Some(10), Position::new(4999976), 34292, 6134, 953, 236712),
+        Record::new(
+          Some(10),
+          Position::new(5034268),
+          34292,
+          6134,
+          237665,
+          236713,
+        ),
+        Record::new(Some(10), Position::new(5068484), 31481, 480538, 850, 144313),
+      ];
+
+      let ranges = search
+        .bytes_ranges_from_index(
+          &query,
+          &index,
+          Arc::new(|record: &Record| record.landmark() == 953),
+        )
+        .await;
+      assert_eq!(
+        ranges,
+        Ok(vec![BytesPosition::default()
+          .with_start(6134)
+          .with_end(480538)
+          .with_class(Body)])
+      );
+
+      None
+    })
+    .await;
+  }
+
+  #[test]
+  fn container_header_read_and_encode() {
+    let cram = std::fs::read(default_dir_data().join("cram").join(CRAM_FILE_NAME)).unwrap();
+    let bytes = &cram[6134..6134 + MAX_CONTAINER_HEADER_SIZE as usize];
+
+    let (header, size) = ContainerHeader::read(bytes).unwrap();
+    assert_eq!(
+      header,
+      ContainerHeader {
+        length: 474378,
+        reference_sequence_id: 10,
+        alignment_start: 4999976,
+        alignment_span: 68584,
+        record_count: 10000,
+        record_counter: 0,
+        bases: 760000,
+        block_count: 38,
+        landmarks: vec![953],
+      }
+    );
+    assert_eq!(size, 26);
+    assert_eq!(header.encode(), &bytes[..size]);
+
+    let slice = SliceHeader::read(&cram[6134 + size + 953..]).unwrap();
+    assert_eq!(
+      slice,
+      SliceHeader {
+        reference_sequence_id: 10,
+        alignment_start: 4999976,
+        alignment_span: 68584,
+        record_count: 10000,
+        record_counter: 0,
+        block_count: 36,
+      }
+    );
+  }
+
+  #[test]
+  fn container_header_invalid_checksum() {
+    let cram = std::fs::read(default_dir_data().join("cram").join(CRAM_FILE_NAME)).unwrap();
+    let mut bytes = cram[6134..6160].to_vec();
+    bytes[4] = 11;
+
+    assert!(ContainerHeader::read(&bytes).is_err());
+  }
+
+  #[test]
+  fn container_header_with_slices() {
+    let header = ContainerHeader {
+      length: 3100,
+      reference_sequence_id: MULTI_REFERENCE_ID,
+      alignment_start: 0,
+      alignment_span: 0,
+      record_count: 30,
+      record_counter: 100,
+      bases: 3000,
+      block_count: 16,
+      landmarks: vec![100, 1100, 2100],
+    };
+    let slices = [
+      SliceHeader {
+        reference_sequence_id: 1,
+        alignment_start: 500,
+        alignment_span: 100,
+        record_count: 10,
+        record_counter: 110,
+        block_count: 4,
+      },
+      SliceHeader {
+        reference_sequence_id: 1,
+        alignment_start: 550,
+        alignment_span: 200,
+        record_count: 10,
+        record_counter: 120,
+        block_count: 5,
+      },
+    ];
+
+    let rewritten = header
+      .with_slices(100, &[(1100, 1000), (2100, 1000)], &slices)
+      .unwrap();
+    assert_eq!(
+      ContainerHeader::read(&rewritten.encode()).unwrap().0,
+      rewritten
+    );
+    assert_eq!(
+      rewritten,
+      ContainerHeader {
+        length: 2100,
+        reference_sequence_id: 1,
+        alignment_start: 500,
+        alignment_span: 250,
+        record_count: 20,
+        record_counter: 110,
+        bases: 3000,
+        block_count: 12,
+        landmarks: vec![100, 1100],
+      }
+    );
+  }
+
+  #[test]
+  fn container_header_with_slices_read() {
+    let cram = std::fs::read(default_dir_data().join("cram").join(CRAM_FILE_NAME)).unwrap();
+    let bytes = &cram[6134..6134 + MAX_CONTAINER_HEADER_SIZE as usize];
+
+    let (header, size) = ContainerHeader::read(bytes).unwrap();
+    let slice = SliceHeader::read(&cram[6134 + size + 953..]).unwrap();
+    let rewritten = header
+      .with_slices(953, &[(953, header.length as u64 - 953)], &[slice])
+      .unwrap();
+
+    let encoded = rewritten.encode();
+    assert_eq!(encoded, &bytes[..size]);
+    assert_eq!(
+      ContainerHeader::read(&encoded).unwrap(),
+      (header, encoded.len())
+    );
+  }
+
+  #[test]
+  fn normalise_coalesced_ranges() {
+    let partial = PartialContainer {
+      header: ContainerHeader {
+        length: 3100,
+        reference_sequence_id: 1,
+        alignment_start: 0,
+        alignment_span: 1000,
+        record_count: 30,
+        record_counter: 100,
+        bases: 3000,
+        block_count: 16,
+        landmarks: vec![100, 1100, 2100],
+      },
+      end: 4130,
+      data_start: 1030,
+      compression_header: vec![0; 100],
+      slices: vec![(100, 1000), (1100, 1000), (2100, 1000)],
+      slice_headers: HashMap::from([(
+        1100,
+        SliceHeader {
+          reference_sequence_id: 1,
+          alignment_start: 500,
+          alignment_span: 100,
+          record_count: 10,
+          record_counter: 110,
+          block_count: 4,
+        },
+      )]),
+    };
+    let partial_containers = BTreeMap::from([(1000, partial)]);
+    let normalise = |start, end| {
+      CramSearch::normalise_range(
+        &partial_containers,
+        BytesPosition::default().with_start(start).with_end(end),
+      )
+    };
+
+    // A slice with a known header.
+    assert_eq!(
+      normalise(2130, 3130),
+      BytesPosition::default().with_start(2130).with_end(3130)
+    );
+    // A slice with an unknown header is widened to the whole container.
+    assert_eq!(
+      normalise(1130, 3130),
+      BytesPosition::default().with_start(1000).with_end(4130)
+    );
+    // A range which ends part way through the container.
+    assert_eq!(
+      normalise(500, 2130),
+      BytesPosition::default().with_start(500).with_end(4130)
+    );
+  }
+
+  #[test]
+  fn itf8_and_ltf8_round_trip() {
+    for value in [
+      0,
+      1,
+      127,
+      128,
+      16383,
+      16384,
+      2097152,
+      268435456,
+      i32::MAX,
+      -1,
+      i32::MIN,
+    ] {
+      let mut buf = vec![];
+      write_itf8(&mut buf, value);
+      assert_eq!(read_itf8(&mut buf.as_slice()).unwrap(), value);
+    }
+
+    for value in [0, 127, 128, 16384, 1 << 35, 1 << 55, 1 << 56, i64::MAX, -1] {
+      let mut buf = vec![];
+      write_ltf8(&mut buf, value);
+      assert_eq!(read_ltf8(&mut buf.as_slice()).unwrap(), value);
+    }
+
+    let mut buf = vec![];
+    write_itf8(&mut buf, -1);
+    assert_eq!(buf, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+  }
+
   #[tokio::test]
   async fn search_reference_name_without_seq_range_chr11() {
     with_local_storage(|storage| async move {
@@ -0,0 +1,530 @@
+//! Module providing the search capability using bgzipped FASTA files, indexed with a FAI index.
+//!
+
+use noodles::bgzf::gzi;
+use noodles::fasta::fai;
+use tokio::io::{AsyncReadExt, BufReader};
+use tracing::{instrument, trace};
+
+use crate::search::{first_existing_key, range_url, BGZF_EOF};
+use crate::{Class, Format, HtsGetError, Query, Response, Result};
+use htsget_storage::types::{
+  BytesPosition, BytesPositionOptions, DataBlock, GetOptions, HeadOptions,
+};
+use htsget_storage::{Storage, StorageMiddleware, StorageTrait};
+
+/// Allows searching through bgzipped FASTA files. The `referenceName` of the query selects a
+/// sequence from the FAI index, and `start` and `end` select a region of that sequence.
+pub struct FastaSearch {
+  storage: Storage,
+}
+
+impl FastaSearch {
+  /// Create the FASTA search.
+  pub fn new(storage: Storage) -> Self {
+    Self { storage }
+  }
+
+  /// Search based on the query. The storage is preprocessed and postprocessed like other formats,
+  /// so that middleware such as Crypt4GH applies to FASTA files.
+  #[instrument(level = "debug", skip(self))]
+  pub async fn search(&mut self, query: Query) -> Result<Response> {
+    if query.format() != Format::Fasta {
+      return Err(HtsGetError::unsupported_format(format!(
+        "using `{}` search, but query contains `{}` format",
+        Format::Fasta,
+        query.format()
+      )));
+    }
+    if query.class() == Class::Header {
+      return Err(HtsGetError::invalid_input(
+        "FASTA files do not have a header",
+      ));
+    }
+
+    let file_key = first_existing_key(
+      &self.storage,
+      query
+        .options()
+        .naming()
+        .file_keys(Format::Fasta, query.id()),
+      &query,
+    )
+    .await?;
+
+    // FASTA files do not have a header, so no data needs to be read when preprocessing.
+    self
+      .storage
+      .preprocess(
+        &file_key,
+        GetOptions::new(
+          BytesPosition::default().with_end(0),
+          query.request().headers(),
+        ),
+      )
+      .await?;
+
+    let file_size = self
+      .storage
+      .head(&file_key, HeadOptions::new(query.request().headers()))
+      .await?;
+
+    let mut byte_ranges = vec![];
+    for region in query.region_queries() {
+      match region.reference_name() {
+        None => byte_ranges.push(BytesPosition::default().with_end(file_size)),
+        Some(reference_name) => byte_ranges.extend(
+          self
+            .get_byte_ranges_for_reference_name(reference_name, &region, file_size)
+            .await?,
+        ),
+      }
+    }
+
+    let blocks = self
+      .storage
+      .postprocess(
+        &file_key,
+        BytesPositionOptions::new(byte_ranges, query.request().headers())
+          .with_coalesce(query.options().coalesce()),
+      )
+      .await?;
+
+    let mut urls = vec![];
+    for block in DataBlock::update_classes(blocks) {
+      match block {
+        DataBlock::Range(range) => {
+          urls.push(range_url(&self.storage, &file_key, &query, range).await?);
+        }
+        DataBlock::Data(data, class) => urls.push(self.storage.data_url(data, class)),
+      }
+    }
+
+    Ok(Response::new(Format::Fasta, urls))
+  }
+
+  /// Get the byte ranges of the BGZF blocks that contain the region of the sequence, followed by
+  /// the EOF block.
+  #[instrument(level = "trace", skip(self, query))]
+  async fn get_byte_ranges_for_reference_name(
+    &self,
+    reference_name: &str,
+    query: &Query,
+    file_size: u64,
+  ) -> Result<Vec<BytesPosition>> {
+    trace!("getting byte ranges for reference name");
+    let record = self.read_fai_record(reference_name, query).await?;
+
+    let length = record.length();
+    let start = u64::from(query.interval().start().unwrap_or(0));
+    let end = query
+      .interval()
+      .end()
+      .map(u64::from)
+      .unwrap_or(length)
+      .min(length);
+    if start >= end {
+      return Err(HtsGetError::invalid_range(format!(
+        "range {start}-{end} is empty or outside sequence `{reference_name}` of length {length}"
+      )));
+    }
+
+    let start = uncompressed_position(&record, start)?;
+    let end = uncompressed_position(&record, end - 1)? + 1;
+
+    let eof_start = file_size
+      .checked_sub(BGZF_EOF.len() as u64)
+      .ok_or_else(|| {
+        HtsGetError::invalid_input(format!(
+          "file of size {file_size} is too small to contain a BGZF end-of-file marker"
+        ))
+      })?;
+    let Some(blocks) = self.read_gzi(query).await? else {
+      // Without a GZI index, the blocks cannot be located, so the whole file is returned.
+      return Ok(vec![BytesPosition::default()
+        .with_end(file_size)
+        .with_class(Class::Body)]);
+    };
+
+    // Take the block containing the start, up to the block after the one containing the end.
+    let block_start = blocks
+      .iter()
+      .rev()
+      .find(|(_, uncompressed)| *uncompressed <= start)
+      .map(|(compressed, _)| *compressed)
+      .unwrap_or(0);
+    let block_end = blocks
+      .iter()
+      .find(|(_, uncompressed)| *uncompressed >= end)
+      .map(|(compressed, _)| *compressed)
+      .unwrap_or(eof_start);
+
+    Ok(vec![
+      BytesPosition::default()
+        .with_start(block_start)
+        .with_end(block_end)
+        .with_class(Class::Body),
+      BytesPosition::default()
+        .with_start(eof_start)
+        .with_end(file_size)
+        .with_class(Class::Body),
+    ])
+  }
+
+  /// Find the FAI record of the sequence.
+  async fn read_fai_record(&self, reference_name: &str, query: &Query) -> Result<fai::Record> {
+    let index_key = first_existing_key(
+      &self.storage,
+      query
+        .options()
+        .naming()
+        .index_keys(Format::Fasta, query.id()),
+      query,
+    )
+    .await?;
+
+    let mut fai = String::new();
+    self
+      .storage
+      .get(
+        &index_key,
+        GetOptions::new_with_default_range(query.request().headers()),
+      )
+      .await?
+      .read_to_string(&mut fai)
+      .await?;
+
+    for line in fai.lines().filter(|line| !line.is_empty()) {
+      let record: fai::Record = line
+        .parse()
+        .map_err(|err| HtsGetError::parse_error(format!("reading FAI index: {err}")))?;
+
+      let name: &[u8] = record.name().as_ref();
+      if name == reference_name.as_bytes() {
+        return Ok(record);
+      }
+    }
+
+    Err(HtsGetError::not_found(format!(
+      "reference name not found in FAI index: {reference_name}"
+    )))
+  }
+
+  /// Read the compressed and uncompressed offsets of the BGZF blocks, sorted by offset and
+  /// including the first block. Returns `None` if there is no GZI index.
+  async fn read_gzi(&self, query: &Query) -> Result<Option<Vec<(u64, u64)>>> {
+    let Ok(gzi_key) = first_existing_key(
+      &self.storage,
+      query
+        .options()
+        .naming()
+        .gzi_keys(Format::Fasta, query.id())?,
+      query,
+    )
+    .await
+    else {
+      return Ok(None);
+    };
+
+    let Ok(gzi_data) = self
+      .storage
+      .get(
+        &gzi_key,
+        GetOptions::new_with_default_range(query.request().headers()),
+      )
+      .await
+    else {
+      return Ok(None);
+    };
+
+    let mut blocks: Vec<(u64, u64)> = gzi::AsyncReader::new(BufReader::new(gzi_data))
+      .read_index()
+      .await?
+      .into_iter()
+      .collect();
+    blocks.push((0, 0));
+    blocks.sort_unstable();
+
+    Ok(Some(blocks))
+  }
+}
+
+/// Get the uncompressed offset of a 0-based position in the sequence, accounting for line breaks.
+fn uncompressed_position(record: &fai::Record, position: u64) -> Result<u64> {
+  if record.line_bases() == 0 || record.line_width() < record.line_bases() {
+    return Err(HtsGetError::invalid_input(format!(
+      "invalid FAI record with {} bases and width {} per line",
+      record.line_bases(),
+      record.line_width()
+    )));
+  }
+
+  Ok(
+    record.offset()
+      + (position / record.line_bases()) * record.line_width()
+      + position % record.line_bases(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use htsget_test::http::concat::ConcatResponse;
+  use std::future::Future;
+
+  use super::*;
+  use crate::from_storage::tests::with_local_storage_fn;
+  use crate::{Class::Body, Class::Header, Headers, HtsGetError::NotFound, Url};
+  #[cfg(feature = "experimental")]
+  use {
+    crate::from_storage::tests::with_local_storage_c4gh_encrypted_file,
+    htsget_storage::c4gh::storage::C4GHStorage, htsget_test::c4gh::get_decryption_keys,
+  };
+
+  const DATA_LOCATION: &str = "data/fasta";
+  const FASTA_FILE_NAME: &str = "sample.fa.gz";
+
+  #[tokio::test]
+  async fn search_all_sequences() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query = Query::new_with_default_request("sample", Format::Fasta);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Fasta,
+        vec![Url::new(expected_url())
+          .with_headers(Headers::default().with_header("Range", "bytes=0-67725"))],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        FASTA_FILE_NAME.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_with_seq_range() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query = Query::new_with_default_request("sample", Format::Fasta)
+        .with_reference_name("chr2")
+        .with_start(10000)
+        .with_end(20000);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Fasta,
+        vec![
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=41301-61915"))
+            .with_class(Body),
+          expected_eof_url(),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        FASTA_FILE_NAME.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_without_seq_range() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query =
+        Query::new_with_default_request("sample", Format::Fasta).with_reference_name("chr1");
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Fasta,
+        vec![
+          Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=0-61915"))
+            .with_class(Body),
+          expected_eof_url(),
+        ],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        FASTA_FILE_NAME.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_in_last_block() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query = Query::new_with_default_request("sample", Format::Fasta)
+        .with_reference_name("chr2")
+        .with_start(59000);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Fasta,
+        vec![Url::new(expected_url())
+          .with_headers(Headers::default().with_header("Range", "bytes=61916-67725"))
+          .with_class(Body)],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        FASTA_FILE_NAME.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_no_gzi() {
+    with_local_storage_fn(
+      |storage| async move {
+        let mut search = FastaSearch::new(storage);
+        let query =
+          Query::new_with_default_request("sample", Format::Fasta).with_reference_name("chr1");
+        let response = search.search(query).await;
+        println!("{response:#?}");
+
+        let expected_response = Ok(Response::new(
+          Format::Fasta,
+          vec![Url::new(expected_url())
+            .with_headers(Headers::default().with_header("Range", "bytes=0-67725"))
+            .with_class(Body)],
+        ));
+        assert_eq!(response, expected_response);
+
+        Some((
+          FASTA_FILE_NAME.to_string(),
+          (response.unwrap(), Body).into(),
+        ))
+      },
+      DATA_LOCATION,
+      &[FASTA_FILE_NAME, "sample.fa.gz.fai"],
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_non_existent_reference_name() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query =
+        Query::new_with_default_request("sample", Format::Fasta).with_reference_name("chr3");
+      let response = search.search(query).await;
+
+      assert!(matches!(response, Err(NotFound(_))));
+
+      None
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_invalid_range() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query = Query::new_with_default_request("sample", Format::Fasta)
+        .with_reference_name("chr2")
+        .with_start(60000);
+      let response = search.search(query).await;
+
+      assert!(matches!(response, Err(HtsGetError::InvalidRange(_))));
+
+      None
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_header() {
+    with_local_storage(|storage| async move {
+      let mut search = FastaSearch::new(storage);
+      let query = Query::new_with_default_request("sample", Format::Fasta).with_class(Header);
+      let response = search.search(query).await;
+
+      assert!(matches!(response, Err(HtsGetError::InvalidInput(_))));
+
+      None
+    })
+    .await;
+  }
+
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn search_reference_name_c4gh() {
+    with_local_storage_c4gh_encrypted_file(
+      |storage| async move {
+        let storage = C4GHStorage::new(get_decryption_keys().await, storage);
+        let mut search = FastaSearch::new(Storage::new(storage));
+        let query = Query::new_with_default_request("sample", Format::Fasta)
+          .with_reference_name("chr2")
+          .with_start(10000)
+          .with_end(20000);
+        let response = search.search(query).await.unwrap();
+        println!("{response:#?}");
+
+        assert!(response
+          .urls
+          .iter()
+          .any(|url| url.url == format!("{}.c4gh", expected_url())));
+
+        Some((format!("{FASTA_FILE_NAME}.c4gh"), (response, Body).into()))
+      },
+      DATA_LOCATION,
+      FASTA_FILE_NAME,
+    )
+    .await;
+  }
+
+  #[test]
+  fn uncompressed_position_with_line_breaks() {
+    let record: fai::Record = "chr2\t60000\t152512\t60\t61".parse().unwrap();
+
+    assert_eq!(uncompressed_position(&record, 0).unwrap(), 152512);
+    assert_eq!(uncompressed_position(&record, 60).unwrap(), 152573);
+    assert_eq!(uncompressed_position(&record, 10000).unwrap(), 162678);
+  }
+
+  #[test]
+  fn uncompressed_position_zero_line_bases() {
+    let record: fai::Record = "chr2\t60000\t152512\t0\t1".parse().unwrap();
+
+    assert!(matches!(
+      uncompressed_position(&record, 0),
+      Err(HtsGetError::InvalidInput(_))
+    ));
+  }
+
+  async fn with_local_storage<F, Fut>(test: F)
+  where
+    F: FnOnce(Storage) -> Fut,
+    Fut: Future<Output = Option<(String, ConcatResponse)>>,
+  {
+    with_local_storage_fn(test, DATA_LOCATION, &[]).await
+  }
+
+  fn expected_url() -> String {
+    "http://127.0.0.1:8081/sample.fa.gz".to_string()
+  }
+
+  fn expected_eof_url() -> Url {
+    Url::new(expected_url())
+      .with_headers(Headers::default().with_header("Range", "bytes=67698-67725"))
+      .with_class(Body)
+  }
+}
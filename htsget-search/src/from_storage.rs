@@ -1,12 +1,14 @@
 //! Module providing an implementation of the [HtsGet] trait using a [StorageTrait].
 //!
 
-use crate::search::Search;
+use crate::search::{existing_index_key, Search};
 use crate::{
-  bam_search::BamSearch,
+  annotation_search::AnnotationSearch,
+  bam_search::{BamCsiSearch, BamSearch},
   bcf_search::BcfSearch,
   cram_search::CramSearch,
-  vcf_search::VcfSearch,
+  fasta_search::FastaSearch,
+  vcf_search::{VcfCsiSearch, VcfSearch},
   {HtsGet, Query, Response, Result},
 };
 use crate::{Format, HtsGetError};
@@ -40,10 +42,51 @@ impl HtsGet for HtsGetFromStorage {
   async fn search(self, query: Query) -> Result<Response> {
     debug!(format = ?query.format(), ?query, "searching {:?}, with query {:?}", query.format(), query);
     match query.format() {
-      Format::Bam => BamSearch::new(self.into_inner()).search(query).await,
+      Format::Bam => {
+        // The index key is resolved once here and passed to the search, so it is not probed again.
+        let storage = self.into_inner();
+        let index_key = existing_index_key(&storage, &query).await;
+        if index_key
+          .as_deref()
+          .is_some_and(BamCsiSearch::uses_csi_index)
+        {
+          BamCsiSearch::new_csi(storage)
+            .with_index_key(&query, index_key)
+            .search(query)
+            .await
+        } else {
+          BamSearch::new(storage)
+            .with_index_key(&query, index_key)
+            .search(query)
+            .await
+        }
+      }
       Format::Cram => CramSearch::new(self.into_inner()).search(query).await,
-      Format::Vcf => VcfSearch::new(self.into_inner()).search(query).await,
+      Format::Vcf => {
+        let storage = self.into_inner();
+        let index_key = existing_index_key(&storage, &query).await;
+        if index_key
+          .as_deref()
+          .is_some_and(VcfCsiSearch::uses_csi_index)
+        {
+          VcfCsiSearch::new_csi(storage)
+            .with_index_key(&query, index_key)
+            .search(query)
+            .await
+        } else {
+          VcfSearch::new(storage)
+            .with_index_key(&query, index_key)
+            .search(query)
+            .await
+        }
+      }
       Format::Bcf => BcfSearch::new(self.into_inner()).search(query).await,
+      Format::Fasta => FastaSearch::new(self.into_inner()).search(query).await,
+      format @ (Format::Bed | Format::Gff3 | Format::Gtf) => {
+        AnnotationSearch::new(self.into_inner(), format)
+          .search(query)
+          .await
+      }
     }
   }
 }
@@ -53,21 +96,31 @@ impl ResolveResponse for HtsGetFromStorage {
   async fn from_file(file_storage: &storage::file::File, query: &Query) -> Result<Response> {
     let storage = Storage::from_file(file_storage, query).await?;
     let searcher = HtsGetFromStorage::new(storage);
-    searcher.search(query.clone()).await
+    searcher
+      .search(query.clone().with_options(file_storage.options().clone()))
+      .await
   }
 
   #[cfg(feature = "aws")]
   async fn from_s3(s3_storage: &storage::s3::S3, query: &Query) -> Result<Response> {
     let storage = Storage::from_s3(s3_storage, query).await;
     let searcher = HtsGetFromStorage::new(storage?);
-    searcher.search(query.clone()).await
+    searcher
+      .search(query.clone().with_options(s3_storage.options().clone()))
+      .await
   }
 
   #[cfg(feature = "url")]
   async fn from_url(url_storage_config: &storage::url::Url, query: &Query) -> Result<Response> {
     let storage = Storage::from_url(url_storage_config, query).await;
     let searcher = HtsGetFromStorage::new(storage?);
-    searcher.search(query.clone()).await
+    searcher
+      .search(
+        query
+          .clone()
+          .with_options(url_storage_config.options().clone()),
+      )
+      .await
   }
 }
 
@@ -97,24 +150,28 @@ pub(crate) mod tests {
 
   use htsget_config::config::location::{Location, LocationEither};
   use htsget_config::storage;
+  use htsget_config::storage::index_cache::IndexCache;
+  use htsget_config::storage::naming::{FormatNaming, Naming};
+  use htsget_config::storage::options::LocationOptions;
   use htsget_config::storage::Backend;
   use htsget_config::types::Class::Body;
   use htsget_config::types::Scheme::Http;
   use htsget_storage::local::FileStorage;
   #[cfg(feature = "experimental")]
-  use htsget_test::c4gh::decrypt_data;
+  use htsget_test::c4gh::{decrypt_data, encrypt_data};
   use htsget_test::http::concat::ConcatResponse;
   use http::uri::Authority;
   use tempfile::TempDir;
 
   use crate::bam_search::tests::{
-    expected_url as bam_expected_url, with_local_storage as with_bam_local_storage, BAM_FILE_NAME,
+    expected_url as bam_expected_url, with_local_storage as with_bam_local_storage,
+    with_local_storage_csi as with_bam_local_storage_csi, BAM_FILE_NAME,
   };
   use crate::vcf_search::tests::{
     expected_url as vcf_expected_url, with_local_storage as with_vcf_local_storage,
-    VCF_FILE_NAME_SPEC,
+    with_local_storage_csi as with_vcf_local_storage_csi, VCF_FILE_NAME_SPEC,
   };
-  use crate::{Headers, Url};
+  use crate::{Class, Headers, Url};
 
   use super::*;
 
@@ -138,6 +195,24 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_bam_csi() {
+    with_bam_local_storage_csi(|storage| async move {
+      let htsget = HtsGetFromStorage::new(storage);
+      let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+        .with_reference_name("11")
+        .with_start(5015000)
+        .with_end(5050000);
+      let response = htsget.search(query).await;
+      println!("{response:#?}");
+
+      assert!(response.is_ok());
+
+      Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+    })
+    .await;
+  }
+
   #[tokio::test]
   async fn search_vcf() {
     with_vcf_local_storage(|storage| async move {
@@ -157,6 +232,25 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_vcf_csi() {
+    with_vcf_local_storage_csi(|storage| async move {
+      let htsget = HtsGetFromStorage::new(storage);
+      let filename = "spec-v4.3";
+      let query = Query::new_with_default_request(filename, Format::Vcf);
+      let response = htsget.search(query).await;
+      println!("{response:#?}");
+
+      assert_eq!(response, expected_vcf_response(filename));
+
+      Some((
+        VCF_FILE_NAME_SPEC.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
   #[tokio::test]
   async fn from_local_storage() {
     with_config_local_storage(
@@ -204,6 +298,204 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn search_naming_templates() {
+    let tmp_dir = TempDir::new().unwrap();
+    let data_path = std::env::current_dir()
+      .unwrap()
+      .parent()
+      .unwrap()
+      .join("data/bam");
+    fs::copy(
+      data_path.join(BAM_FILE_NAME),
+      tmp_dir.path().join("sample.alignments.bam"),
+    )
+    .unwrap();
+    fs::copy(
+      data_path.join("htsnexus_test_NA12878.bam.bai"),
+      tmp_dir.path().join("sample.bai"),
+    )
+    .unwrap();
+
+    with_config_local_storage(
+      |_, mut local_storage| async move {
+        local_storage.set_options(LocationOptions::default().with_naming(
+          Naming::default().with_format(
+            Format::Bam,
+            FormatNaming::new(
+              vec!["{id}.bam".to_string(), "{id}.alignments.bam".to_string()],
+              vec!["{id}.bam.bai".to_string(), "{id}.bai".to_string()],
+              vec![],
+            ),
+          ),
+        ));
+
+        let query = Query::new_with_default_request("sample", Format::Bam)
+          .with_reference_name("11")
+          .with_start(5015000)
+          .with_end(5050000);
+        let response = HtsGetFromStorage::from_file(&local_storage, &query).await;
+        println!("{response:#?}");
+
+        let expected_url = "http://127.0.0.1:8081/sample.alignments.bam";
+        let expected_response = Ok(Response::new(
+          Format::Bam,
+          vec![
+            Url::new(expected_url)
+              .with_headers(Headers::default().with_header("Range", "bytes=0-4667"))
+              .with_class(Class::Header),
+            Url::new(expected_url)
+              .with_headers(Headers::default().with_header("Range", "bytes=256721-1065951"))
+              .with_class(Body),
+            Url::new(expected_url)
+              .with_headers(Headers::default().with_header("Range", "bytes=2596771-2596798"))
+              .with_class(Body),
+          ],
+        ));
+        assert_eq!(response, expected_response);
+
+        Some((
+          "sample.alignments.bam".to_string(),
+          (response.unwrap(), Body).into(),
+        ))
+      },
+      tmp_dir.path().to_str().unwrap(),
+      &[],
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_builds_bam_index() {
+    let cache_dir = TempDir::new().unwrap();
+    // An index cached for an older version of the file is not read, and is removed.
+    let stale_index = cache_dir.path().join("htsnexus_test_NA12878.bam.bai.1");
+    fs::write(&stale_index, b"stale").unwrap();
+
+    with_config_local_storage(
+      |_, mut local_storage| async move {
+        local_storage.set_options(
+          LocationOptions::default()
+            .with_index_cache(Some(IndexCache::new(cache_dir.path().to_path_buf()))),
+        );
+
+        let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+          .with_reference_name("11")
+          .with_start(5015000)
+          .with_end(5050000);
+        let response = HtsGetFromStorage::from_file(&local_storage, &query).await;
+        println!("{response:#?}");
+
+        assert!(response.is_ok());
+        assert!(is_index_cached(
+          cache_dir.path(),
+          "htsnexus_test_NA12878.bam.bai"
+        ));
+        assert!(!stale_index.exists());
+
+        // The second search reads the cached index.
+        assert_eq!(
+          HtsGetFromStorage::from_file(&local_storage, &query).await,
+          response
+        );
+
+        Some((BAM_FILE_NAME.to_string(), (response.unwrap(), Body).into()))
+      },
+      "data/bam",
+      &[BAM_FILE_NAME],
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_builds_vcf_index() {
+    let cache_dir = TempDir::new().unwrap();
+
+    with_config_local_storage(
+      |_, mut local_storage| async move {
+        local_storage.set_options(
+          LocationOptions::default()
+            .with_index_cache(Some(IndexCache::new(cache_dir.path().to_path_buf()))),
+        );
+
+        let query =
+          Query::new_with_default_request("spec-v4.3", Format::Vcf).with_reference_name("20");
+        let response = HtsGetFromStorage::from_file(&local_storage, &query).await;
+        println!("{response:#?}");
+
+        assert_eq!(response, expected_vcf_response("spec-v4.3"));
+        assert!(is_index_cached(cache_dir.path(), "spec-v4.3.vcf.gz.tbi"));
+
+        Some((
+          VCF_FILE_NAME_SPEC.to_string(),
+          (response.unwrap(), Body).into(),
+        ))
+      },
+      "data/vcf",
+      &[VCF_FILE_NAME_SPEC],
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_builds_cram_index() {
+    let cache_dir = TempDir::new().unwrap();
+
+    with_config_local_storage(
+      |_, mut local_storage| async move {
+        local_storage.set_options(
+          LocationOptions::default()
+            .with_index_cache(Some(IndexCache::new(cache_dir.path().to_path_buf()))),
+        );
+
+        let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Cram)
+          .with_reference_name("11")
+          .with_start(5000000)
+          .with_end(5050000);
+        let response = HtsGetFromStorage::from_file(&local_storage, &query).await;
+        println!("{response:#?}");
+
+        assert!(response.is_ok());
+        assert!(is_index_cached(
+          cache_dir.path(),
+          "htsnexus_test_NA12878.cram.crai"
+        ));
+
+        // The second search reads the cached index.
+        assert_eq!(
+          HtsGetFromStorage::from_file(&local_storage, &query).await,
+          response
+        );
+
+        Some((
+          "htsnexus_test_NA12878.cram".to_string(),
+          (response.unwrap(), Body).into(),
+        ))
+      },
+      "data/cram",
+      &["htsnexus_test_NA12878.cram"],
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_missing_index_without_cache() {
+    with_config_local_storage(
+      |_, local_storage| async move {
+        let query = Query::new_with_default_request("htsnexus_test_NA12878", Format::Bam)
+          .with_reference_name("11");
+        let response = HtsGetFromStorage::from_file(&local_storage, &query).await;
+
+        assert!(matches!(response, Err(HtsGetError::NotFound(_))));
+
+        None
+      },
+      "data/bam",
+      &[BAM_FILE_NAME],
+    )
+    .await;
+  }
+
   fn expected_vcf_response(filename: &str) -> Result<Response> {
     Ok(Response::new(
       Format::Vcf,
@@ -229,6 +521,16 @@ pub(crate) mod tests {
     base_path
   }
 
+  fn is_index_cached(cache_dir: &Path, key: &str) -> bool {
+    fs::read_dir(cache_dir).unwrap().any(|entry| {
+      entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .starts_with(&format!("{key}."))
+    })
+  }
+
   async fn with_config_local_storage_map<M, F, Fut>(
     test: F,
     path: &str,
@@ -318,6 +620,70 @@ pub(crate) mod tests {
     .await;
   }
 
+  /// Run a C4GH test where the index is encrypted and the plaintext index does not exist.
+  #[cfg(feature = "experimental")]
+  pub(crate) async fn with_local_storage_c4gh_encrypted_index<F, Fut>(
+    test: F,
+    file: &str,
+    index: &str,
+  ) where
+    F: FnOnce(Storage) -> Fut,
+    Fut: Future<Output = Option<(String, ConcatResponse)>>,
+  {
+    with_config_local_storage_map(
+      |base_path, local_storage| async move {
+        let index_path = base_path.join(index);
+        fs::write(
+          base_path.join(format!("{index}.c4gh")),
+          encrypt_data(&fs::read(&index_path).unwrap()),
+        )
+        .unwrap();
+        fs::remove_file(index_path).unwrap();
+
+        test(Storage::new(
+          FileStorage::new(base_path, local_storage).unwrap(),
+        ))
+        .await
+      },
+      "data/c4gh",
+      &[file, index],
+      decrypt_data,
+    )
+    .await;
+  }
+
+  /// Run a C4GH test where a file in the path is encrypted and the plaintext file does not exist.
+  #[cfg(feature = "experimental")]
+  pub(crate) async fn with_local_storage_c4gh_encrypted_file<F, Fut>(
+    test: F,
+    path: &str,
+    file: &str,
+  ) where
+    F: FnOnce(Storage) -> Fut,
+    Fut: Future<Output = Option<(String, ConcatResponse)>>,
+  {
+    with_config_local_storage_map(
+      |base_path, local_storage| async move {
+        let file_path = base_path.join(file);
+        fs::write(
+          base_path.join(format!("{file}.c4gh")),
+          encrypt_data(&fs::read(&file_path).unwrap()),
+        )
+        .unwrap();
+        fs::remove_file(file_path).unwrap();
+
+        test(Storage::new(
+          FileStorage::new(base_path, local_storage).unwrap(),
+        ))
+        .await
+      },
+      path,
+      &[],
+      decrypt_data,
+    )
+    .await;
+  }
+
   #[cfg(feature = "aws")]
   pub(crate) async fn with_aws_storage_fn<F, Fut>(test: F, path: &str, copy_files: &[&str])
   where
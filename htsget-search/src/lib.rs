@@ -18,20 +18,32 @@ use std::str::FromStr;
 use async_trait::async_trait;
 use tokio::task::JoinError;
 
+pub mod annotation_search;
 pub mod bam_search;
 pub mod bcf_search;
 pub mod cram_search;
+pub mod fasta_search;
 pub mod from_storage;
 pub mod search;
 pub mod vcf_search;
 
-/// Trait representing a search for either `reads` or `variants` in the HtsGet specification.
+/// Trait representing a search for `reads` or `variants` in the HtsGet specification, or for
+/// reference `sequences` and `annotations`.
 #[async_trait]
 pub trait HtsGet {
   async fn search(self, query: Query) -> Result<Response>;
 
   fn get_supported_formats(&self) -> Vec<Format> {
-    vec![Format::Bam, Format::Cram, Format::Vcf, Format::Bcf]
+    vec![
+      Format::Bam,
+      Format::Cram,
+      Format::Vcf,
+      Format::Bcf,
+      Format::Fasta,
+      Format::Bed,
+      Format::Gff3,
+      Format::Gtf,
+    ]
   }
 
   fn are_field_parameters_effective(&self) -> bool {
@@ -6,6 +6,7 @@
 //!
 
 use std::collections::BTreeSet;
+use std::path::Path;
 
 use async_trait::async_trait;
 use futures::StreamExt;
@@ -16,16 +17,20 @@ use noodles::csi::binning_index::index::Index;
 use noodles::csi::binning_index::index::{reference_sequence, ReferenceSequence};
 use noodles::csi::binning_index::ReferenceSequence as ReferenceSequenceExt;
 use noodles::csi::BinningIndex;
-use tokio::io;
+use tempfile::NamedTempFile;
+use tokio::fs::File;
 use tokio::io::{AsyncRead, BufReader};
 use tokio::select;
+use tokio::sync::OnceCell;
 use tokio::task::JoinHandle;
-use tracing::{instrument, trace, trace_span, Instrument};
+use tokio::{fs, io, task};
+use tracing::{debug, instrument, trace, trace_span, warn, Instrument};
 
+use htsget_config::storage::index_cache::IndexCache;
 use htsget_config::types::Class::Header;
 
 use crate::ConcurrencyError;
-use crate::{Class, Class::Body, Format, HtsGetError, Query, Response, Result};
+use crate::{Class, Class::Body, Format, HtsGetError, Query, Response, Result, Url};
 use htsget_storage::types::{
   BytesPosition, BytesPositionOptions, DataBlock, GetOptions, HeadOptions, RangeUrlOptions,
 };
@@ -59,6 +64,138 @@ pub(crate) async fn find_first<T>(
   result.ok_or_else(|| HtsGetError::not_found(msg))
 }
 
+/// Find the first key that exists in the storage, probing the keys in order. If there is only one
+/// key, it is returned without checking that it exists.
+#[instrument(level = "trace", skip(storage, query), ret)]
+pub(crate) async fn first_existing_key(
+  storage: &Storage,
+  keys: Vec<String>,
+  query: &Query,
+) -> Result<String> {
+  if let [key] = keys.as_slice() {
+    return Ok(key.to_string());
+  }
+
+  for key in &keys {
+    if storage
+      .head(key, HeadOptions::new(query.request().headers()))
+      .await
+      .is_ok()
+    {
+      return Ok(key.to_string());
+    }
+  }
+
+  Err(HtsGetError::not_found(format!(
+    "none of the keys exist: {}",
+    keys.join(", ")
+  )))
+}
+
+/// Find the first index key that exists for the query, in the probe order of its naming templates.
+#[instrument(level = "trace", skip(storage, query), ret)]
+pub(crate) async fn existing_index_key(storage: &Storage, query: &Query) -> Option<String> {
+  first_existing_key(
+    storage,
+    query
+      .options()
+      .naming()
+      .index_keys(query.format(), query.id()),
+    query,
+  )
+  .await
+  .ok()
+}
+
+/// The keys of the data file and index of a search. Each key is resolved from the naming
+/// templates at most once, and reused for the rest of the search.
+#[derive(Debug, Default)]
+pub struct SearchKeys {
+  query: Option<(String, Format)>,
+  file: OnceCell<String>,
+  index: OnceCell<String>,
+}
+
+impl SearchKeys {
+  /// Create the search keys for a query with an index key, if it has already been resolved.
+  pub fn with_index_key(query: &Query, index_key: Option<String>) -> Self {
+    Self {
+      query: Some((query.id().to_string(), query.format())),
+      file: OnceCell::new(),
+      index: OnceCell::new_with(index_key),
+    }
+  }
+
+  /// Clear the keys if they were resolved for a different query.
+  pub fn reset_for(&mut self, query: &Query) {
+    let target = Some((query.id().to_string(), query.format()));
+    if self.query != target {
+      *self = Self {
+        query: target,
+        ..Default::default()
+      };
+    }
+  }
+}
+
+/// Get the url of a byte range. If the range is at most the inline threshold of the query, the
+/// bytes are read and returned inline as a data url instead.
+#[instrument(level = "trace", skip(storage, query), ret)]
+pub(crate) async fn range_url(
+  storage: &Storage,
+  key: &str,
+  query: &Query,
+  range: BytesPosition,
+) -> Result<Url> {
+  let inline = match (query.options().inline_threshold(), range.get_end()) {
+    (Some(threshold), Some(end)) => end.saturating_sub(range.get_start().unwrap_or(0)) <= threshold,
+    _ => false,
+  };
+
+  let options = RangeUrlOptions::new(range, query.request().headers());
+  if inline {
+    Ok(storage.inline_range_url(key, options).await?)
+  } else {
+    Ok(storage.range_url(key, options).await?)
+  }
+}
+
+/// Write an index which was built to the cache path. The index is written to a unique temporary
+/// file first, so that a partially written index is never read and concurrent builds of the same
+/// index do not interfere. Indexes cached for older versions of the data file are then removed.
+fn write_cached_index<I>(
+  path: &Path,
+  index: &I,
+  write_index: fn(&Path, &I) -> io::Result<()>,
+) -> io::Result<()> {
+  let dir = path.parent().unwrap_or_else(|| Path::new("."));
+  let tmp_file = NamedTempFile::new_in(dir)?;
+  write_index(tmp_file.path(), index)?;
+  tmp_file.persist(path).map_err(|err| err.error)?;
+
+  let (Some(file_name), Some(stem)) = (path.file_name(), path.file_stem()) else {
+    return Ok(());
+  };
+  let prefix = format!("{}.", stem.to_string_lossy());
+  for entry in std::fs::read_dir(dir)? {
+    let entry = entry?;
+    let name = entry.file_name();
+    let is_stale = name != file_name
+      && name.to_str().is_some_and(|name| {
+        name
+          .strip_prefix(&prefix)
+          .is_some_and(|size| !size.is_empty() && size.bytes().all(|b| b.is_ascii_digit()))
+      });
+
+    if is_stale {
+      debug!(path = ?entry.path(), "removing stale cached index");
+      let _ = std::fs::remove_file(entry.path());
+    }
+  }
+
+  Ok(())
+}
+
 /// [SearchAll] represents searching bytes ranges that are applicable to all formats. Specifically,
 /// range for the whole file, and the header.
 ///
@@ -194,13 +331,19 @@ where
 pub trait Search<ReferenceSequence, Index, Reader, Header>:
   SearchAll<ReferenceSequence, Index, Reader, Header>
 where
-  Index: Send + Sync,
+  Index: Send + Sync + 'static,
   Header: Send + Sync,
   Reader: Send,
   Self: Sync + Send,
 {
   fn init_reader(inner: Streamable) -> Reader;
   async fn read_header(reader: &mut Reader) -> io::Result<Header>;
+
+  /// Read the header using the index, for formats where the index describes which lines belong
+  /// to the header. By default, the index is not used.
+  async fn read_header_with_index(reader: &mut Reader, _index: &Index) -> io::Result<Header> {
+    Self::read_header(reader).await
+  }
   async fn read_index_inner<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index>;
 
   /// Get ranges for a given reference name and an optional sequence range.
@@ -215,12 +358,24 @@ where
   /// Get the storage of this format.
   fn get_storage(&self) -> &Storage;
 
+  /// Get the resolved keys of this search.
+  fn get_keys(&self) -> &SearchKeys;
+
+  /// Get the mutable resolved keys of this search.
+  fn mut_keys(&mut self) -> &mut SearchKeys;
+
   /// Get the mutable storage of this format.
   fn mut_storage(&mut self) -> &mut Storage;
 
   /// Get the format of this format.
   fn get_format(&self) -> Format;
 
+  /// Update the data blocks of a body query after they have been merged, before the response
+  /// is built. This can be used to return extra data alongside the byte ranges.
+  fn update_data_blocks(&self, blocks: Vec<DataBlock>) -> Vec<DataBlock> {
+    blocks
+  }
+
   /// Get the position at the end of file marker.
   #[instrument(level = "trace", skip(self), ret)]
   async fn position_at_eof(&self, query: &Query) -> Result<u64> {
@@ -232,14 +387,87 @@ where
     )
   }
 
-  /// Read the index from the key.
+  /// Get the key of the data file, probing the naming templates of the query in order. The key
+  /// is only resolved once per search.
+  async fn file_key(&self, query: &Query) -> Result<String> {
+    self
+      .get_keys()
+      .file
+      .get_or_try_init(|| {
+        first_existing_key(
+          self.get_storage(),
+          query
+            .options()
+            .naming()
+            .file_keys(query.format(), query.id()),
+          query,
+        )
+      })
+      .await
+      .cloned()
+  }
+
+  /// Get the key of the index used to search the query. The key is only resolved once per search.
+  async fn index_key(&self, query: &Query) -> Result<String> {
+    self
+      .get_keys()
+      .index
+      .get_or_try_init(|| self.resolve_index_key(query))
+      .await
+      .cloned()
+  }
+
+  /// Resolve the key of the index used to search the query, probing the naming templates of the
+  /// query in order.
+  async fn resolve_index_key(&self, query: &Query) -> Result<String> {
+    first_existing_key(
+      self.get_storage(),
+      query
+        .options()
+        .naming()
+        .index_keys(query.format(), query.id()),
+      query,
+    )
+    .await
+  }
+
+  /// Build the index by reading through the data file. Returns `None` if indexes for this format
+  /// cannot be built, which is the default.
+  async fn build_index(&self, _query: &Query) -> Result<Option<Index>> {
+    Ok(None)
+  }
+
+  /// Write an index which was built to the path.
+  fn write_index(_path: &Path, _index: &Index) -> io::Result<()> {
+    Err(io::Error::new(
+      io::ErrorKind::Unsupported,
+      "writing this index is not supported",
+    ))
+  }
+
+  /// Read the index from the key. If the index does not exist and the query has an index cache,
+  /// the index is read from the cache, or built and written to the cache.
   #[instrument(level = "trace", skip(self))]
   async fn read_index(&self, query: &Query) -> Result<Index> {
     trace!("reading index");
+    match (
+      self.read_stored_index(query).await,
+      query.options().index_cache(),
+    ) {
+      (Err(HtsGetError::NotFound(err)), Some(cache)) => self
+        .read_cached_index(cache, query)
+        .await?
+        .ok_or(HtsGetError::NotFound(err)),
+      (result, _) => result,
+    }
+  }
+
+  /// Read the index from storage.
+  async fn read_stored_index(&self, query: &Query) -> Result<Index> {
     let storage = self
       .get_storage()
       .get(
-        &query.format().fmt_index(query.id()),
+        &self.index_key(query).await?,
         GetOptions::new_with_default_range(query.request().headers()),
       )
       .await?;
@@ -248,8 +476,46 @@ where
       .map_err(|err| HtsGetError::io_error(format!("reading {} index: {}", self.get_format(), err)))
   }
 
+  /// Read the index from the index cache, building and caching it if it is not there yet. Returns
+  /// `None` if indexes for this format cannot be built.
+  #[instrument(level = "debug", skip(self, query))]
+  async fn read_cached_index(&self, cache: &IndexCache, query: &Query) -> Result<Option<Index>> {
+    let file_size = self.file_size(query).await?;
+    let path = cache.path(&self.get_format().fmt_index(query.id()), file_size)?;
+    if let Ok(file) = File::open(&path).await {
+      match Self::read_index_inner(file).await {
+        Ok(index) => return Ok(Some(index)),
+        Err(err) => warn!(?path, "ignoring invalid cached index: {}", err),
+      }
+    }
+
+    let Some(index) = self.build_index(query).await? else {
+      return Ok(None);
+    };
+
+    debug!(?path, "writing built index to cache");
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+
+    let write_index: fn(&Path, &Index) -> io::Result<()> = Self::write_index;
+    let index = task::spawn_blocking(move || {
+      if let Err(err) = write_cached_index(&path, &index, write_index) {
+        warn!(?path, "failed to write index to cache: {}", err);
+      }
+
+      index
+    })
+    .await
+    .map_err(ConcurrencyError::new)?;
+
+    Ok(Some(index))
+  }
+
   /// Search based on the query.
   async fn search(&mut self, query: Query) -> Result<Response> {
+    self.mut_keys().reset_for(&query);
+
     match query.class() {
       Body => {
         let format = self.get_format();
@@ -266,29 +532,42 @@ where
 
         self.preprocess(&query, header_end).await?;
 
-        let mut byte_ranges = match query.reference_name().as_ref() {
-          None => self.get_byte_ranges_for_all(&query).await?,
-          Some(reference_name) => {
-            let (header, mut reader) = self.get_header(&query, header_end).await?;
-
-            let mut byte_ranges = self
-              .get_byte_ranges_for_reference_name(
-                reference_name.to_string(),
-                &index,
-                &header,
-                &query,
-              )
-              .await?;
-
-            byte_ranges.push(
+        // All regions are planned together, so the index and header are only read once, and
+        // overlapping byte ranges are merged when the storage postprocesses them.
+        let regions = query.region_queries();
+        let mut byte_ranges = vec![];
+        let header = if regions
+          .iter()
+          .any(|region| region.reference_name().is_some())
+        {
+          let (header, mut reader) = self.get_header(&query, &index, header_end).await?;
+
+          byte_ranges.push(
+            self
+              .get_byte_ranges_for_header(&index, &mut reader, &query)
+              .await?,
+          );
+
+          Some(header)
+        } else {
+          None
+        };
+
+        for region in regions {
+          match (region.reference_name(), &header) {
+            (Some(reference_name), Some(header)) => byte_ranges.extend(
               self
-                .get_byte_ranges_for_header(&index, &mut reader, &query)
+                .get_byte_ranges_for_reference_name(
+                  reference_name.to_string(),
+                  &index,
+                  header,
+                  &region,
+                )
                 .await?,
-            );
-
-            byte_ranges
+            ),
+            _ => byte_ranges.extend(self.get_byte_ranges_for_all(&region).await?),
           }
-        };
+        }
 
         let file_size = self.file_size(&query).await?;
         if let Some(eof) = self.get_eof_byte_positions(file_size) {
@@ -298,12 +577,15 @@ where
         let blocks = self
           .get_storage()
           .postprocess(
-            &query.format().fmt_file(query.id()),
-            BytesPositionOptions::new(byte_ranges, query.request().headers()),
+            &self.file_key(&query).await?,
+            BytesPositionOptions::new(byte_ranges, query.request().headers())
+              .with_coalesce(query.options().coalesce()),
           )
           .await?;
 
-        self.build_response(&query, blocks).await
+        self
+          .build_response(&query, self.update_data_blocks(blocks))
+          .await
       }
       Class::Header => {
         let index = self.read_index(&query).await?;
@@ -311,7 +593,7 @@ where
 
         self.preprocess(&query, header_end).await?;
 
-        let (_, mut reader) = self.get_header(&query, header_end).await?;
+        let (_, mut reader) = self.get_header(&query, &index, header_end).await?;
 
         let header_byte_ranges = self
           .get_byte_ranges_for_header(&index, &mut reader, &query)
@@ -320,7 +602,7 @@ where
         let blocks = self
           .get_storage()
           .postprocess(
-            &query.format().fmt_file(query.id()),
+            &self.file_key(&query).await?,
             BytesPositionOptions::new(vec![header_byte_ranges], query.request().headers()),
           )
           .await?;
@@ -331,11 +613,13 @@ where
   }
 
   async fn preprocess(&mut self, query: &Query, header_end: u64) -> Result<()> {
+    let file_key = self.file_key(query).await?;
+
     Ok(
       self
         .mut_storage()
         .preprocess(
-          &query.format().fmt_file(query.id()),
+          &file_key,
           GetOptions::new(
             BytesPosition::default().with_end(header_end),
             query.request().headers(),
@@ -350,7 +634,7 @@ where
       self
         .get_storage()
         .head(
-          &query.format().fmt_file(query.id()),
+          &self.file_key(query).await?,
           HeadOptions::new(query.request().headers()),
         )
         .await?,
@@ -363,21 +647,13 @@ where
     trace!("building response");
     let mut urls = vec![];
     let storage = self.get_storage();
+    let file_key = self.file_key(query).await?;
 
     for block in DataBlock::update_classes(byte_ranges) {
       match block {
         DataBlock::Range(range) => {
           trace!(range = ?range, "range");
-          let query_owned = query.clone();
-
-          urls.push(
-            storage
-              .range_url(
-                &query_owned.format().fmt_file(query_owned.id()),
-                RangeUrlOptions::new(range, query_owned.request().headers()),
-              )
-              .await?,
-          );
+          urls.push(range_url(storage, &file_key, query, range).await?);
         }
         DataBlock::Data(data, class) => {
           let data_url = self.get_storage().data_url(data, class);
@@ -390,8 +666,13 @@ where
   }
 
   /// Get the header from the file specified by the id and format.
-  #[instrument(level = "trace", skip(self))]
-  async fn get_header(&self, query: &Query, offset: u64) -> Result<(Header, Reader)> {
+  #[instrument(level = "trace", skip(self, index))]
+  async fn get_header(
+    &self,
+    query: &Query,
+    index: &Index,
+    offset: u64,
+  ) -> Result<(Header, Reader)> {
     trace!("getting header");
     let get_options = GetOptions::new(
       BytesPosition::default().with_end(offset),
@@ -400,14 +681,16 @@ where
 
     let reader_type = self
       .get_storage()
-      .get(&query.format().fmt_file(query.id()), get_options)
+      .get(&self.file_key(query).await?, get_options)
       .await?;
     let mut reader = Self::init_reader(reader_type);
 
     Ok((
-      Self::read_header(&mut reader).await.map_err(|err| {
-        HtsGetError::io_error(format!("reading `{}` header: {}", self.get_format(), err))
-      })?,
+      Self::read_header_with_index(&mut reader, index)
+        .await
+        .map_err(|err| {
+          HtsGetError::io_error(format!("reading `{}` header: {}", self.get_format(), err))
+        })?,
       reader,
     ))
   }
@@ -482,15 +765,28 @@ where
       Ok(chunks)
     });
 
-    let gzi_data = self
-      .get_storage()
-      .get(
-        &query.format().fmt_gzi(query.id())?,
-        GetOptions::new_with_default_range(query.request().headers()),
-      )
-      .await;
+    let gzi_key = first_existing_key(
+      self.get_storage(),
+      query
+        .options()
+        .naming()
+        .gzi_keys(query.format(), query.id())?,
+      query,
+    )
+    .await;
+    let gzi_data = match gzi_key {
+      Ok(gzi_key) => self
+        .get_storage()
+        .get(
+          &gzi_key,
+          GetOptions::new_with_default_range(query.request().headers()),
+        )
+        .await
+        .ok(),
+      Err(_) => None,
+    };
     let byte_ranges: Vec<BytesPosition> = match gzi_data {
-      Ok(gzi_data) => {
+      Some(gzi_data) => {
         let span = trace_span!("reading gzi");
         let gzi: Result<Vec<u64>> = async {
           trace!(id = ?query.id(), "reading gzi");
@@ -512,7 +808,7 @@ where
           .bytes_positions_from_chunks(query, chunks?.into_iter(), gzi?.into_iter())
           .await?
       }
-      Err(_) => {
+      None => {
         self
           .bytes_positions_from_chunks(
             query,
@@ -1,37 +1,143 @@
-//! Module providing the search capability using VCF files
+//! Module providing the search capability using VCF files, indexed with either tabix or CSI.
 //!
 
+use std::marker::PhantomData;
+use std::path::Path;
+
 use async_trait::async_trait;
 use futures_util::stream::FuturesOrdered;
 use noodles::bgzf;
 use noodles::bgzf::VirtualPosition;
-use noodles::csi::binning_index::index::reference_sequence::index::LinearIndex;
-use noodles::csi::binning_index::index::ReferenceSequence;
+use noodles::csi::binning_index::index::header::Builder as IndexHeaderBuilder;
+use noodles::csi::binning_index::index::reference_sequence::bin::Chunk;
+use noodles::csi::binning_index::index::reference_sequence::index::{BinnedIndex, LinearIndex};
+use noodles::csi::binning_index::index::{reference_sequence, Index, ReferenceSequence};
 use noodles::csi::BinningIndex;
-use noodles::tabix;
-use noodles::tabix::Index;
-use noodles::vcf;
+use noodles::vcf::variant::Record as _;
 use noodles::vcf::Header;
+use noodles::{csi, tabix, vcf};
 use tokio::io;
 use tokio::io::AsyncRead;
 use tracing::{instrument, trace};
 
 use htsget_config::types::HtsGetError;
 
-use crate::search::{find_first, BgzfSearch, Search};
+use crate::search::{find_first, first_existing_key, BgzfSearch, Search, SearchKeys};
 use crate::{Format, Query, Result};
-use htsget_storage::types::BytesPosition;
-use htsget_storage::{Storage, Streamable};
+use htsget_storage::types::{BytesPosition, GetOptions};
+use htsget_storage::{Storage, StorageTrait, Streamable};
 
 type AsyncReader = vcf::AsyncReader<bgzf::AsyncReader<Streamable>>;
 
-/// Allows searching through vcf files.
-pub struct VcfSearch {
+/// An index type that can be used to search VCF files.
+#[async_trait]
+pub trait VcfIndex: reference_sequence::Index + Send + Sync + Sized + 'static {
+  /// Get the default key of the index for the id.
+  fn index_key(id: &str) -> io::Result<String>;
+
+  /// Check whether the key refers to this type of index.
+  fn is_index_key(key: &str) -> bool;
+
+  /// Read the index.
+  async fn read_index<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<Self>>;
+
+  /// Build the index by reading all records, returning `None` if this type of index cannot be built.
+  async fn build_index(
+    reader: &mut AsyncReader,
+    header: &Header,
+  ) -> io::Result<Option<Index<Self>>>;
+
+  /// Write the index to the path.
+  fn write_index(path: &Path, index: &Index<Self>) -> io::Result<()>;
+}
+
+#[async_trait]
+impl VcfIndex for LinearIndex {
+  fn index_key(id: &str) -> io::Result<String> {
+    Ok(Format::Vcf.fmt_index(id))
+  }
+
+  fn is_index_key(key: &str) -> bool {
+    !BinnedIndex::is_index_key(key)
+  }
+
+  async fn read_index<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<Self>> {
+    tabix::AsyncReader::new(inner).read_index().await
+  }
+
+  async fn build_index(
+    reader: &mut AsyncReader,
+    header: &Header,
+  ) -> io::Result<Option<Index<Self>>> {
+    let mut indexer = tabix::index::Indexer::default();
+    indexer.set_header(IndexHeaderBuilder::vcf().build());
+    let mut record = vcf::Record::default();
+
+    let mut chunk_start = reader.get_ref().virtual_position();
+    while reader.read_record(&mut record).await? != 0 {
+      let chunk_end = reader.get_ref().virtual_position();
+
+      let start = record
+        .variant_start()
+        .transpose()?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing variant start"))?;
+      let end = record.variant_end(header)?;
+
+      indexer.add_record(
+        record.reference_sequence_name(),
+        start,
+        end,
+        Chunk::new(chunk_start, chunk_end),
+      )?;
+      chunk_start = chunk_end;
+    }
+
+    Ok(Some(indexer.build()))
+  }
+
+  fn write_index(path: &Path, index: &Index<Self>) -> io::Result<()> {
+    tabix::fs::write(path, index)
+  }
+}
+
+#[async_trait]
+impl VcfIndex for BinnedIndex {
+  fn index_key(id: &str) -> io::Result<String> {
+    Format::Vcf.fmt_csi_index(id)
+  }
+
+  fn is_index_key(key: &str) -> bool {
+    key.ends_with(".csi")
+  }
+
+  async fn read_index<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<Self>> {
+    csi::AsyncReader::new(inner).read_index().await
+  }
+
+  async fn build_index(
+    _reader: &mut AsyncReader,
+    _header: &Header,
+  ) -> io::Result<Option<Index<Self>>> {
+    Ok(None)
+  }
+
+  fn write_index(path: &Path, index: &Index<Self>) -> io::Result<()> {
+    csi::fs::write(path, index)
+  }
+}
+
+/// Allows searching through vcf files. By default, this uses a tabix index.
+pub struct VcfSearch<I = LinearIndex> {
   storage: Storage,
+  keys: SearchKeys,
+  index: PhantomData<I>,
 }
 
+/// Allows searching through vcf files using a CSI index.
+pub type VcfCsiSearch = VcfSearch<BinnedIndex>;
+
 #[async_trait]
-impl BgzfSearch<LinearIndex, AsyncReader, Header> for VcfSearch {
+impl<I: VcfIndex> BgzfSearch<I, AsyncReader, Header> for VcfSearch<I> {
   async fn read_bytes(reader: &mut AsyncReader) -> Option<usize> {
     reader.read_record(&mut Default::default()).await.ok()
   }
@@ -42,7 +148,7 @@ impl BgzfSearch<LinearIndex, AsyncReader, Header> for VcfSearch {
 }
 
 #[async_trait]
-impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for VcfSearch {
+impl<I: VcfIndex> Search<ReferenceSequence<I>, Index<I>, AsyncReader, Header> for VcfSearch<I> {
   fn init_reader(inner: Streamable) -> AsyncReader {
     AsyncReader::new(bgzf::AsyncReader::new(inner))
   }
@@ -51,15 +157,51 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for VcfS
     reader.read_header().await
   }
 
-  async fn read_index_inner<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index> {
-    tabix::AsyncReader::new(inner).read_index().await
+  async fn read_index_inner<T: AsyncRead + Unpin + Send>(inner: T) -> io::Result<Index<I>> {
+    I::read_index(inner).await
+  }
+
+  #[instrument(level = "debug", skip(self))]
+  async fn build_index(&self, query: &Query) -> Result<Option<Index<I>>> {
+    let file = self
+      .get_storage()
+      .get(
+        &self.file_key(query).await?,
+        GetOptions::new_with_default_range(query.request().headers()),
+      )
+      .await?;
+
+    let mut reader = Self::init_reader(file);
+    let header = Self::read_header(&mut reader).await?;
+
+    Ok(I::build_index(&mut reader, &header).await?)
+  }
+
+  fn write_index(path: &Path, index: &Index<I>) -> io::Result<()> {
+    I::write_index(path, index)
+  }
+
+  async fn resolve_index_key(&self, query: &Query) -> Result<String> {
+    let keys: Vec<String> = query
+      .options()
+      .naming()
+      .index_keys(Format::Vcf, query.id())
+      .into_iter()
+      .filter(|key| I::is_index_key(key))
+      .collect();
+
+    if keys.is_empty() {
+      Ok(I::index_key(query.id())?)
+    } else {
+      first_existing_key(self.get_storage(), keys, query).await
+    }
   }
 
   #[instrument(level = "trace", skip(self, index, query))]
   async fn get_byte_ranges_for_reference_name(
     &self,
     reference_name: String,
-    index: &Index,
+    index: &Index<I>,
     _header: &Header,
     query: &Query,
   ) -> Result<Vec<BytesPosition>> {
@@ -86,7 +228,7 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for VcfS
     }
 
     let ref_seq_id = find_first(
-      &format!("reference name not found in TBI file: {reference_name}"),
+      &format!("reference name not found in index: {reference_name}"),
       futures,
     )
     .await?;
@@ -101,6 +243,14 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for VcfS
     &self.storage
   }
 
+  fn get_keys(&self) -> &SearchKeys {
+    &self.keys
+  }
+
+  fn mut_keys(&mut self) -> &mut SearchKeys {
+    &mut self.keys
+  }
+
   fn mut_storage(&mut self) -> &mut Storage {
     &mut self.storage
   }
@@ -111,9 +261,38 @@ impl Search<ReferenceSequence<LinearIndex>, Index, AsyncReader, Header> for VcfS
 }
 
 impl VcfSearch {
-  /// Create the vcf search.
+  /// Create the vcf search using a tabix index.
   pub fn new(storage: Storage) -> Self {
-    Self { storage }
+    Self {
+      storage,
+      keys: Default::default(),
+      index: PhantomData,
+    }
+  }
+}
+
+impl VcfCsiSearch {
+  /// Create the vcf search using a CSI index.
+  pub fn new_csi(storage: Storage) -> Self {
+    Self {
+      storage,
+      keys: Default::default(),
+      index: PhantomData,
+    }
+  }
+
+  /// Check whether an index key refers to a CSI index.
+  pub fn uses_csi_index(key: &str) -> bool {
+    BinnedIndex::is_index_key(key)
+  }
+}
+
+impl<I> VcfSearch<I> {
+  /// Set the index key of the query if it has already been resolved, so that it is not probed
+  /// again.
+  pub fn with_index_key(mut self, query: &Query, index_key: Option<String>) -> Self {
+    self.keys = SearchKeys::with_index_key(query, index_key);
+    self
   }
 }
 
@@ -122,12 +301,14 @@ pub(crate) mod tests {
   use htsget_config::types::Class::Body;
   use htsget_test::http::concat::ConcatResponse;
   use std::future::Future;
+  use std::{env, fs};
+  use tempfile::TempDir;
 
   use super::*;
   #[cfg(feature = "aws")]
   use crate::from_storage::tests::with_aws_storage_fn;
   use crate::from_storage::tests::with_local_storage_fn;
-  use crate::search::SearchAll;
+  use crate::search::{existing_index_key, SearchAll};
   use crate::{Class::Header, Headers, HtsGetError::NotFound, Response, Url};
   #[cfg(feature = "experimental")]
   use {
@@ -139,6 +320,7 @@ pub(crate) mod tests {
   const INDEX_FILE_LOCATION: &str = "spec-v4.3.vcf.gz.tbi";
   pub(crate) const VCF_FILE_NAME_SPEC: &str = "spec-v4.3.vcf.gz";
   const VCF_FILE_NAME_SAMPLE: &str = "sample1-bcbio-cancer.vcf.gz";
+  const CSI_DATA_LOCATION: &str = "data/csi";
 
   #[tokio::test]
   async fn search_all_variants() {
@@ -339,6 +521,96 @@ pub(crate) mod tests {
     .await
   }
 
+  #[tokio::test]
+  async fn search_all_variants_csi() {
+    with_local_storage_csi(|storage| async move {
+      let mut search = VcfCsiSearch::new_csi(storage);
+      let filename = "sample1-bcbio-cancer";
+      let query = Query::new_with_default_request(filename, Format::Vcf);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(expected_vcf_response(filename));
+      assert_eq!(response, expected_response);
+
+      Some((
+        VCF_FILE_NAME_SAMPLE.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_without_seq_range_csi() {
+    with_local_storage_csi(|storage| async move {
+      let mut search = VcfCsiSearch::new_csi(storage);
+      let filename = "spec-v4.3";
+      let query = Query::new_with_default_request(filename, Format::Vcf).with_reference_name("20");
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(Response::new(
+        Format::Vcf,
+        vec![Url::new(expected_url(filename))
+          .with_headers(Headers::default().with_header("Range", "bytes=0-850"))],
+      ));
+      assert_eq!(response, expected_response);
+
+      Some((
+        VCF_FILE_NAME_SPEC.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn search_reference_name_with_seq_range_csi() {
+    with_local_storage_csi(|storage| async move {
+      let mut search = VcfCsiSearch::new_csi(storage);
+      let filename = "sample1-bcbio-cancer";
+      let query = Query::new_with_default_request(filename, Format::Vcf)
+        .with_reference_name("chrM")
+        .with_start(151)
+        .with_end(153);
+      let response = search.search(query).await;
+      println!("{response:#?}");
+
+      let expected_response = Ok(expected_vcf_response(filename));
+      assert_eq!(response, expected_response);
+
+      Some((
+        VCF_FILE_NAME_SAMPLE.to_string(),
+        (response.unwrap(), Body).into(),
+      ))
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn uses_csi_index() {
+    with_local_storage_csi(|storage| async move {
+      let query = Query::new_with_default_request("spec-v4.3", Format::Vcf);
+      assert!(existing_index_key(&storage, &query)
+        .await
+        .is_some_and(|key| VcfCsiSearch::uses_csi_index(&key)));
+
+      None
+    })
+    .await;
+
+    with_local_storage(|storage| async move {
+      let query = Query::new_with_default_request("spec-v4.3", Format::Vcf);
+      assert!(!existing_index_key(&storage, &query)
+        .await
+        .is_some_and(|key| VcfCsiSearch::uses_csi_index(&key)));
+
+      None
+    })
+    .await;
+  }
+
   #[cfg(feature = "aws")]
   #[tokio::test]
   async fn search_non_existent_id_reference_name_aws() {
@@ -410,6 +682,25 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[cfg(feature = "experimental")]
+  #[tokio::test]
+  async fn search_all_c4gh_many_recipients() {
+    with_local_storage_c4gh(|storage| async move {
+      let storage = C4GHStorage::new(get_decryption_keys().await, storage);
+      let mut search = VcfSearch::new(Storage::new(storage));
+      let query = Query::new_with_default_request("recipients/sample1-bcbio-cancer", Format::Vcf);
+      let response = search.search(query).await.unwrap();
+
+      println!("{:#?}", response);
+
+      Some((
+        "recipients/sample1-bcbio-cancer.vcf.gz.c4gh".to_string(),
+        (response, Body).into(),
+      ))
+    })
+    .await;
+  }
+
   #[cfg(feature = "experimental")]
   #[tokio::test]
   async fn search_all_range_c4gh() {
@@ -466,6 +757,31 @@ pub(crate) mod tests {
     with_local_storage_fn(test, "data/vcf", &[]).await
   }
 
+  pub(crate) async fn with_local_storage_csi<F, Fut>(test: F)
+  where
+    F: FnOnce(Storage) -> Fut,
+    Fut: Future<Output = Option<(String, ConcatResponse)>>,
+  {
+    let tmp_dir = TempDir::new().unwrap();
+    let data_path = env::current_dir().unwrap().parent().unwrap().to_path_buf();
+
+    for name in ["spec-v4.3", "sample1-bcbio-cancer"] {
+      for (location, file_name) in [
+        (VCF_LOCATION, format!("{name}.vcf.gz")),
+        (VCF_LOCATION, format!("{name}.vcf.gz.gzi")),
+        (CSI_DATA_LOCATION, format!("{name}.vcf.gz.csi")),
+      ] {
+        fs::copy(
+          data_path.join(location).join(&file_name),
+          tmp_dir.path().join(&file_name),
+        )
+        .unwrap();
+      }
+    }
+
+    with_local_storage_fn(test, tmp_dir.path().to_str().unwrap(), &[]).await
+  }
+
   pub(crate) fn expected_url(name: &str) -> String {
     format!("http://127.0.0.1:8081/{name}.vcf.gz")
   }
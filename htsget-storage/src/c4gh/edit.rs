@@ -96,10 +96,19 @@ impl<'a> EditHeader<'a> {
     }
   }
 
-  /// Encrypt the header packet.
+  /// Encrypt the header packet, using the key which decrypted the current header if it is known.
   pub fn encrypt_header_packet(&self, header_packet: Vec<u8>) -> Result<Vec<u8>> {
+    let keys = match self
+      .current_header
+      .key_index
+      .and_then(|index| self.keys.get(index))
+    {
+      Some(key) => vec![key.clone()],
+      None => self.keys.to_vec(),
+    };
+
     Ok(
-      encrypt(&header_packet, &HashSet::from_iter(self.keys.to_vec()))?
+      encrypt(&header_packet, &HashSet::from_iter(keys))?
         .into_iter()
         .last()
         .ok_or_else(|| {
@@ -229,6 +238,8 @@ impl<'a> EditHeader<'a> {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use htsget_config::storage::c4gh::local::C4GHLocal;
+  use htsget_config::storage::c4gh::C4GHKeys;
   use htsget_test::c4gh::get_decryption_keys;
   use htsget_test::util::default_dir;
   use std::fs::File;
@@ -255,6 +266,29 @@ mod tests {
     assert_eq!(edit, expected_edit_list());
   }
 
+  #[tokio::test]
+  async fn test_from_buffer_tries_keys_in_order() {
+    let mut src =
+      File::open(default_dir().join("data/c4gh/htsnexus_test_NA12878.bam.c4gh")).unwrap();
+    let mut buf = vec![];
+    src.read_to_end(&mut buf).unwrap();
+
+    let other_keys = C4GHKeys::try_from(C4GHLocal::new(
+      default_dir().join("data/c4gh/keys/alice.sec"),
+      default_dir().join("data/c4gh/keys/bob.pub"),
+    ))
+    .unwrap()
+    .keys()
+    .await
+    .unwrap();
+    let keys = [other_keys, get_decryption_keys().await].concat();
+
+    let header =
+      DeserializedHeader::from_buffer(&mut BufReader::new(Cursor::new(buf)), &keys).unwrap();
+
+    assert_eq!(header.key_index, Some(1));
+  }
+
   fn test_unencrypted_positions() -> Vec<UnencryptedPosition> {
     vec![
       UnencryptedPosition::new(0, 7853),
@@ -8,6 +8,8 @@ use crypt4gh::{body_decrypt, body_decrypt_parts, header, Keys, WriteInfo};
 use std::cmp::min;
 use std::io;
 use std::io::{BufWriter, Cursor, Read};
+use std::slice;
+use tracing::{debug, trace};
 
 mod edit;
 pub mod storage;
@@ -25,6 +27,8 @@ pub struct DeserializedHeader {
   pub(crate) session_keys: Vec<Vec<u8>>,
   pub(crate) header_size: u64,
   pub(crate) edit_list: Option<Vec<u64>>,
+  /// The index of the key which decrypted the header, if a single key decrypted it.
+  pub(crate) key_index: Option<usize>,
 }
 
 impl Clone for DeserializedHeader {
@@ -38,6 +42,7 @@ impl Clone for DeserializedHeader {
       session_keys: self.session_keys.clone(),
       header_size: self.header_size,
       edit_list: self.edit_list.clone(),
+      key_index: self.key_index,
     }
   }
 }
@@ -55,10 +60,12 @@ impl DeserializedHeader {
       session_keys,
       header_size,
       edit_list,
+      key_index: None,
     }
   }
 
-  /// Grab all the required information from the header.
+  /// Grab all the required information from the header. The keys are tried in order, so that
+  /// files encrypted to old and new keys can be read during key rotation.
   /// This is more or less directly copied from https://github.com/EGA-archive/crypt4gh-rust/blob/2d41a1770067003bc67ab499841e0def186ed218/src/lib.rs#L283-L314
   pub fn from_buffer<R: Read>(read_buffer: &mut R, keys: &[Keys]) -> Result<Self, Crypt4GHError> {
     // Get header info
@@ -100,21 +107,63 @@ impl DeserializedHeader {
       })
       .collect::<Result<Vec<Vec<u8>>, Crypt4GHError>>()?;
 
+    let (key_index, packets) = Self::decrypt_packets(encrypted_packets, keys)?;
     let DecryptedHeaderPackets {
       data_enc_packets: session_keys,
       edit_list_packet,
-    } = header::deconstruct_header_body(encrypted_packets, keys, &None)?;
+    } = packets;
 
     let header_size = 16 + header_lengths;
 
-    Ok(DeserializedHeader::new(
+    let mut header = DeserializedHeader::new(
       header_info,
       session_keys,
       header_size as u64,
       edit_list_packet,
+    );
+    header.key_index = key_index;
+
+    Ok(header)
+  }
+
+  /// Decrypt the header packets with the first key that can decrypt them, returning the index
+  /// of the key.
+  fn decrypt_packets(
+    encrypted_packets: Vec<Vec<u8>>,
+    keys: &[Keys],
+  ) -> Result<(Option<usize>, DecryptedHeaderPackets), Crypt4GHError> {
+    for (index, key) in keys.iter().enumerate() {
+      match header::deconstruct_header_body(encrypted_packets.clone(), slice::from_ref(key), &None)
+      {
+        Ok(packets) if !packets.data_enc_packets.is_empty() => {
+          debug!(key_index = index, "decrypted C4GH header");
+          return Ok((Some(index), packets));
+        }
+        Ok(_) => trace!(
+          key_index = index,
+          "C4GH key did not decrypt any header packets"
+        ),
+        Err(err) => trace!(
+          key_index = index,
+          "C4GH key failed to decrypt header: {}",
+          err
+        ),
+      }
+    }
+
+    // No single key decrypted the header, so try all the keys together, which also returns
+    // the error if none of them work.
+    Ok((
+      None,
+      header::deconstruct_header_body(encrypted_packets, keys, &None)?,
     ))
   }
 
+  /// Get the size of the header, which is where the encrypted data blocks start.
+  pub fn header_size(&self) -> u64 {
+    self.header_size
+  }
+
   /// Check if an edit list is present.
   pub fn contains_edit_list(&self) -> bool {
     self.edit_list.is_some()
@@ -157,6 +206,11 @@ impl DecryptedData {
   pub fn into_inner(self) -> Vec<u8> {
     self.0
   }
+
+  /// Get the inner data as a slice.
+  pub fn as_slice(&self) -> &[u8] {
+    &self.0
+  }
 }
 
 /// Convert an encrypted file position to an unencrypted position if the header length is known.
@@ -182,7 +236,12 @@ pub fn to_unencrypted_file_size(encrypted_file_size: u64, header_length: u64) ->
     return 0;
   }
 
-  to_unencrypted(encrypted_file_size, header_length) - MAC_SIZE
+  // Every data block has a nonce and a MAC, including the last block which may be partial.
+  let data_size = encrypted_file_size - header_length;
+  let remainder = data_size % DATA_BLOCK_SIZE;
+
+  (data_size / DATA_BLOCK_SIZE) * ENCRYPTED_BLOCK_SIZE
+    + remainder.saturating_sub(NONCE_SIZE + MAC_SIZE)
 }
 
 fn to_current_data_block(pos: u64, header_len: u64) -> u64 {
@@ -311,6 +370,10 @@ mod tests {
     assert_eq!(result, 0);
     let result = to_unencrypted_file_size(124 + 12 + 16 + 12, 124);
     assert_eq!(result, 12);
+    let result = to_unencrypted_file_size(124 + 2 * DATA_BLOCK_SIZE, 124);
+    assert_eq!(result, 2 * ENCRYPTED_BLOCK_SIZE);
+    let result = to_unencrypted_file_size(to_encrypted_file_size(100000, 120), 120);
+    assert_eq!(result, 100000);
   }
 
   #[test]
@@ -6,7 +6,7 @@ use crate::c4gh::{
   to_unencrypted_file_size, unencrypted_clamp, unencrypted_clamp_next, unencrypted_to_data_block,
   unencrypted_to_next_data_block, DecryptedData, DeserializedHeader,
 };
-use crate::error::StorageError::{InternalError, IoError};
+use crate::error::StorageError::{InternalError, IoError, KeyNotFound};
 use crate::error::{Result, StorageError};
 use crate::types::BytesPosition;
 use crate::{
@@ -15,7 +15,7 @@ use crate::{
 };
 use async_trait::async_trait;
 use crypt4gh::error::Crypt4GHError;
-use crypt4gh::Keys;
+use crypt4gh::{header, Keys};
 use htsget_config::types::{Class, Format, Url};
 use std::cmp::min;
 use std::collections::HashMap;
@@ -24,8 +24,13 @@ use std::io;
 use std::io::{BufReader, Cursor, Read};
 use tokio::io::AsyncReadExt;
 
-/// Max C4GH header size in bytes. Supports 50 regular sized encrypted packets. 16 + (108 * 50).
-const MAX_C4GH_HEADER_SIZE: u64 = 5416;
+/// The size of the C4GH header info, which contains the number of header packets.
+const C4GH_HEADER_INFO_SIZE: u64 = 16;
+/// The size of the length at the start of each header packet.
+const C4GH_PACKET_LENGTH_SIZE: u64 = 4;
+/// The size of a regular header packet containing a data encryption key, used to estimate how many
+/// bytes to read for the remaining header packets.
+const C4GH_HEADER_PACKET_SIZE: u64 = 108;
 
 /// This represents the state that the C4GHStorage needs to save, like the file sizes and header
 /// sizes.
@@ -43,6 +48,7 @@ pub struct C4GHStorage {
   keys: Vec<Keys>,
   inner: Box<dyn StorageTrait + Send + Sync + 'static>,
   state: HashMap<String, C4GHState>,
+  plaintext_urls: bool,
 }
 
 impl Clone for C4GHStorage {
@@ -51,6 +57,7 @@ impl Clone for C4GHStorage {
       keys: self.keys.clone(),
       inner: self.inner.clone_box(),
       state: self.state.clone(),
+      plaintext_urls: self.plaintext_urls,
     }
   }
 }
@@ -73,17 +80,63 @@ impl C4GHStorage {
       keys,
       inner,
       state: Default::default(),
+      plaintext_urls: false,
     }
   }
 
+  /// Return urls to the plaintext objects and plaintext byte ranges, instead of the encrypted
+  /// objects and an edited header. This is used for data servers which decrypt objects for
+  /// trusted clients. Objects which are stored unencrypted are served as they are.
+  pub fn with_plaintext_urls(mut self, plaintext_urls: bool) -> Self {
+    self.plaintext_urls = plaintext_urls;
+    self
+  }
+
+  /// Whether the object has been decrypted into the state, rather than being served as it is
+  /// stored when using plaintext urls.
+  fn is_decrypted(&self, key: &str) -> bool {
+    self.state.contains_key(&Self::format_key(key))
+  }
+
+  /// Get a url to the plaintext of an object. Decrypted objects are only stored in their encrypted
+  /// form, so the url of the encrypted object is used without the C4GH extension.
+  async fn plaintext_range_url(&self, key: &str, options: RangeUrlOptions<'_>) -> Result<Url> {
+    if !self.is_decrypted(key) {
+      return self.inner.range_url(key, options).await;
+    }
+
+    let mut url = self
+      .inner
+      .range_url(&Self::format_key(key), options)
+      .await?;
+    url.url = url
+      .url
+      .strip_suffix(".c4gh")
+      .ok_or_else(|| InternalError("expected a url to a C4GH object".to_string()))?
+      .to_string();
+
+    Ok(url)
+  }
+
   /// Format a C4GH key.
   pub fn format_key(key: &str) -> String {
     format!("{}.c4gh", key)
   }
 
-  /// Get a C4GH object and decrypt it if it is not an index.
+  /// Get a C4GH object and decrypt it. Indexes are returned as they are if they are not
+  /// encrypted, otherwise they are decrypted in full.
   pub async fn get_object(&self, key: &str, options: GetOptions<'_>) -> Result<Streamable> {
     if Format::is_index(key) {
+      let index = self.inner.get(key, options.clone()).await;
+      return match index {
+        Err(KeyNotFound(_)) => Ok(Streamable::from_async_read(Cursor::new(
+          self.decrypt_index(key, options).await?,
+        ))),
+        index => index,
+      };
+    }
+
+    if self.plaintext_urls && !self.is_decrypted(key) {
       return self.inner.get(key, options).await;
     }
 
@@ -98,6 +151,68 @@ impl C4GHStorage {
     )))
   }
 
+  /// Read and decrypt an encrypted index, which is stored under the C4GH key of the index. The
+  /// range of the options refers to the decrypted index, so the whole encrypted index is read and
+  /// the range is applied after decrypting it.
+  pub async fn decrypt_index(&self, key: &str, options: GetOptions<'_>) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    self
+      .inner
+      .get(
+        &Self::format_key(key),
+        GetOptions::new_with_default_range(options.request_headers()),
+      )
+      .await?
+      .read_to_end(&mut buf)
+      .await?;
+
+    let mut reader = BufReader::new(buf.as_slice());
+    let deserialized_header = DeserializedHeader::from_buffer(&mut reader, &self.keys)?;
+    let data = DecryptedData::from_header(&mut reader, deserialized_header)?.into_inner();
+
+    let len = data.len() as u64;
+    let start = min(options.range.start.unwrap_or(0), len) as usize;
+    let end = min(options.range.end.unwrap_or(len), len).max(start as u64) as usize;
+
+    Ok(data[start..end].to_vec())
+  }
+
+  /// Get the size of an index, which is the size of the decrypted index if it is encrypted. The
+  /// decrypted size is calculated from the encrypted size and the header, unless the index has an
+  /// edit list.
+  pub async fn index_size(&self, key: &str, options: GetOptions<'_>) -> Result<u64> {
+    let size = self.inner.head(key, (&options).into()).await;
+    match size {
+      Err(KeyNotFound(_)) => {
+        let c4gh_key = Self::format_key(key);
+        let encrypted_file_size = self.inner.head(&c4gh_key, (&options).into()).await?;
+        let buf = self
+          .read_header(&c4gh_key, &options, encrypted_file_size)
+          .await?;
+        let deserialized_header =
+          DeserializedHeader::from_buffer(&mut BufReader::new(buf.as_slice()), &self.keys)?;
+
+        if deserialized_header.contains_edit_list() {
+          return Ok(
+            self
+              .decrypt_index(
+                key,
+                GetOptions::new_with_default_range(options.request_headers()),
+              )
+              .await?
+              .len() as u64,
+          );
+        }
+
+        Ok(to_unencrypted_file_size(
+          encrypted_file_size,
+          deserialized_header.header_size,
+        ))
+      }
+      size => size,
+    }
+  }
+
   /// Get the size of the unencrypted object and update state.
   pub async fn preprocess_for_state(
     &mut self,
@@ -105,7 +220,7 @@ impl C4GHStorage {
     mut options: GetOptions<'_>,
   ) -> Result<u64> {
     if Format::is_index(key) {
-      return self.inner.head(key, (&options).into()).await;
+      return self.index_size(key, options).await;
     }
 
     let key = Self::format_key(key);
@@ -113,18 +228,11 @@ impl C4GHStorage {
     // Get the file size.
     let encrypted_file_size = self.inner.head(&key, (&options).into()).await?;
 
-    let mut c4gh_header_options = options.clone();
-    c4gh_header_options.range.end = Some(min(MAX_C4GH_HEADER_SIZE, encrypted_file_size));
-
     // Also need to determine the header size.
-    let mut buf = vec![];
-    self
-      .inner
-      .get(&key, c4gh_header_options)
-      .await?
-      .take(MAX_C4GH_HEADER_SIZE)
-      .read_to_end(&mut buf)
+    let buf = self
+      .read_header(&key, &options, encrypted_file_size)
       .await?;
+    let header_size = buf.len() as u64;
 
     let mut reader = BufReader::new(buf.as_slice());
 
@@ -135,15 +243,15 @@ impl C4GHStorage {
     // Grab remaining bytes after knowing the header size.
     let mut remaining = vec![];
 
-    if encrypted_file_size > MAX_C4GH_HEADER_SIZE {
+    if encrypted_file_size > header_size {
       let end = unencrypted_to_next_data_block(
         options.range.end.unwrap_or(encrypted_file_size),
         deserialized_header.header_size,
         encrypted_file_size,
       );
-      options.range.start = Some(MAX_C4GH_HEADER_SIZE);
+      options.range.start = Some(header_size);
 
-      if end < MAX_C4GH_HEADER_SIZE {
+      if end < header_size {
         options.range.end = None;
       } else {
         options.range.end = Some(min(end, encrypted_file_size));
@@ -172,6 +280,106 @@ impl C4GHStorage {
     Ok(unencrypted_file_size)
   }
 
+  /// Read exactly the bytes of the C4GH header. The header info is read first to get the number
+  /// of header packets, and then the length of each packet determines where the next one starts,
+  /// so that headers with any number of packets can be read.
+  async fn read_header(
+    &self,
+    key: &str,
+    options: &GetOptions<'_>,
+    encrypted_file_size: u64,
+  ) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    self
+      .extend_header(
+        key,
+        options,
+        &mut buf,
+        C4GH_HEADER_INFO_SIZE,
+        C4GH_HEADER_INFO_SIZE,
+      )
+      .await?;
+
+    let header_info = header::deconstruct_header_info(
+      buf
+        .as_slice()
+        .try_into()
+        .map_err(|_| InternalError("invalid C4GH header info".to_string()))?,
+    )?;
+
+    let mut header_size = C4GH_HEADER_INFO_SIZE;
+    for packet in 0..u64::from(header_info.packets_count) {
+      // Read the packet length, estimating that the remaining packets are regular sized so that
+      // the header is usually read in one request.
+      let remaining_packets = u64::from(header_info.packets_count) - packet;
+      let length_end = header_size + C4GH_PACKET_LENGTH_SIZE;
+      let estimated_end = header_size + remaining_packets * C4GH_HEADER_PACKET_SIZE;
+      self
+        .extend_header(
+          key,
+          options,
+          &mut buf,
+          length_end,
+          min(estimated_end, encrypted_file_size).max(length_end),
+        )
+        .await?;
+
+      let length = u32::from_le_bytes(
+        buf[header_size as usize..length_end as usize]
+          .try_into()
+          .expect("slice has length 4"),
+      );
+      if u64::from(length) < C4GH_PACKET_LENGTH_SIZE {
+        return Err(InternalError(format!(
+          "invalid C4GH header packet length: {length}"
+        )));
+      }
+
+      header_size += u64::from(length);
+    }
+
+    self
+      .extend_header(key, options, &mut buf, header_size, header_size)
+      .await?;
+    buf.truncate(header_size as usize);
+
+    Ok(buf)
+  }
+
+  /// Extend the header bytes so that there are at least `required` bytes, reading up to `end`.
+  async fn extend_header(
+    &self,
+    key: &str,
+    options: &GetOptions<'_>,
+    header: &mut Vec<u8>,
+    required: u64,
+    end: u64,
+  ) -> Result<()> {
+    let start = header.len() as u64;
+    if start >= required {
+      return Ok(());
+    }
+
+    let mut options = options.clone();
+    options.range = BytesPosition::default().with_start(start).with_end(end);
+
+    self
+      .inner
+      .get(key, options)
+      .await?
+      .take(end - start)
+      .read_to_end(header)
+      .await?;
+
+    if (header.len() as u64) < required {
+      return Err(InternalError(format!(
+        "C4GH header of `{key}` is truncated"
+      )));
+    }
+
+    Ok(())
+  }
+
   /// Compute the data blocks including edit lists, additional data encryption packets, and encrypted bytes.
   pub async fn compute_data_blocks(
     &self,
@@ -268,15 +476,27 @@ impl C4GHStorage {
 #[async_trait]
 impl StorageMiddleware for C4GHStorage {
   async fn preprocess(&mut self, key: &str, options: GetOptions<'_>) -> Result<()> {
+    if self.plaintext_urls && self.inner.head(key, (&options).into()).await.is_ok() {
+      return Ok(());
+    }
+
     self.preprocess_for_state(key, options).await?;
     Ok(())
   }
 
+  /// Crypt4GH positions are not coalesced, because they are aligned to encrypted blocks and
+  /// returned alongside edit lists. Plaintext positions are returned as they are.
   async fn postprocess(
     &self,
     key: &str,
     positions_options: BytesPositionOptions<'_>,
   ) -> Result<Vec<DataBlock>> {
+    if self.plaintext_urls {
+      return Ok(DataBlock::from_bytes_positions(
+        positions_options.merge_all().into_inner(),
+      ));
+    }
+
     self.compute_data_blocks(key, positions_options).await
   }
 }
@@ -288,13 +508,57 @@ impl StorageTrait for C4GHStorage {
     self.get_object(key, options).await
   }
 
-  /// Get a url for the file at key. This refers to the underlying `StorageTrait`.
+  /// Get a url for the file at key. This refers to the underlying `StorageTrait`, using the
+  /// plaintext key if plaintext urls are returned.
   async fn range_url(&self, key: &str, options: RangeUrlOptions<'_>) -> Result<Url> {
+    if self.plaintext_urls {
+      return self.plaintext_range_url(key, options).await;
+    }
+
     self.inner.range_url(&Self::format_key(key), options).await
   }
 
+  /// Inline the bytes of the underlying encrypted file, as the ranges refer to it. For plaintext
+  /// urls, the decrypted bytes are inlined if they have already been decrypted, otherwise a url
+  /// is returned.
+  async fn inline_range_url(&self, key: &str, options: RangeUrlOptions<'_>) -> Result<Url> {
+    if self.plaintext_urls {
+      let Some(state) = self.state.get(&Self::format_key(key)) else {
+        return self.inner.inline_range_url(key, options).await;
+      };
+
+      let data = state.decrypted_data.as_slice();
+      let range = options.range();
+      let start = range.get_start().unwrap_or(0) as usize;
+      return match range.get_end().map(|end| end as usize) {
+        Some(end) if start <= end && end <= data.len() => {
+          Ok(self.data_url(data[start..end].to_vec(), range.class))
+        }
+        _ => self.plaintext_range_url(key, options).await,
+      };
+    }
+
+    self
+      .inner
+      .inline_range_url(&Self::format_key(key), options)
+      .await
+  }
+
   /// Get the size of the underlying file and the encrypted file, updating any state.
-  async fn head(&self, key: &str, _options: HeadOptions<'_>) -> Result<u64> {
+  async fn head(&self, key: &str, options: HeadOptions<'_>) -> Result<u64> {
+    if Format::is_index(key) {
+      return self
+        .index_size(
+          key,
+          GetOptions::new_with_default_range(options.request_headers()),
+        )
+        .await;
+    }
+
+    if self.plaintext_urls && !self.is_decrypted(key) {
+      return self.inner.head(key, options).await;
+    }
+
     Ok(
       self
         .state
@@ -314,13 +578,14 @@ impl From<Crypt4GHError> for StorageError {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::local::tests::with_local_storage;
+  use crate::local::tests::{test_local_storage, with_local_storage};
   #[cfg(feature = "aws")]
   use crate::s3::tests::with_aws_s3_storage;
   #[cfg(feature = "url")]
   use crate::url::tests::{test_headers, with_url_test_server};
   use htsget_config::types::Headers;
   use htsget_test::c4gh::{encrypt_data, get_decryption_keys};
+  use htsget_test::util::default_dir;
   use http::HeaderMap;
   use std::future::Future;
   use std::path::Path;
@@ -485,6 +750,110 @@ mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn test_get_encrypted_index_local_storage() {
+    with_local_c4gh_storage(|storage| async move {
+      let mut index = vec![];
+      storage
+        .get(
+          "folder/key.bai",
+          GetOptions::new_with_default_range(&Default::default()),
+        )
+        .await
+        .unwrap()
+        .read_to_end(&mut index)
+        .await
+        .unwrap();
+
+      assert_eq!(index, b"value1");
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn test_get_encrypted_index_range_local_storage() {
+    with_local_c4gh_storage(|storage| async move {
+      let mut index = vec![];
+      storage
+        .get(
+          "folder/key.bai",
+          GetOptions::new(
+            BytesPosition::default().with_start(1).with_end(4),
+            &Default::default(),
+          ),
+        )
+        .await
+        .unwrap()
+        .read_to_end(&mut index)
+        .await
+        .unwrap();
+
+      assert_eq!(index, b"alu");
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn test_head_encrypted_index_local_storage() {
+    with_local_c4gh_storage(|storage| async move {
+      let size = storage
+        .head("folder/key.bai", HeadOptions::new(&Default::default()))
+        .await
+        .unwrap();
+
+      assert_eq!(size, 6);
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn test_get_missing_index_local_storage() {
+    with_local_c4gh_storage(|storage| async move {
+      let result = storage
+        .get(
+          "folder/missing.bai",
+          GetOptions::new_with_default_range(&Default::default()),
+        )
+        .await;
+
+      assert!(matches!(result, Err(KeyNotFound(_))));
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn test_preprocess_many_recipients() {
+    let mut storage = C4GHStorage::new(
+      get_decryption_keys().await,
+      test_local_storage(&default_dir().join("data/c4gh/recipients")),
+    );
+    let key = "sample1-bcbio-cancer.vcf.gz";
+    let headers = Default::default();
+    let options = GetOptions::new_with_default_range(&headers);
+
+    storage.preprocess(key, options.clone()).await.unwrap();
+
+    let state = storage.state.get(&format!("{}.c4gh", key)).unwrap();
+    assert_eq!(state.deserialized_header.header_info.packets_count, 64);
+    assert_eq!(state.deserialized_header.header_size, 16 + 64 * 108);
+    assert_eq!(state.encrypted_file_size, 10450);
+
+    let mut object = vec![];
+    storage
+      .get(key, options)
+      .await
+      .unwrap()
+      .read_to_end(&mut object)
+      .await
+      .unwrap();
+    assert_eq!(
+      object,
+      read(default_dir().join("data/vcf/sample1-bcbio-cancer.vcf.gz"))
+        .await
+        .unwrap()
+    );
+  }
+
   async fn test_preprocess(storage: &mut C4GHStorage, key: &str, headers: &HeaderMap) {
     storage
       .preprocess(key, GetOptions::new_with_default_range(headers))
@@ -594,6 +963,12 @@ mod tests {
       .write_all(&data)
       .await
       .unwrap();
+    File::create(base_path.join("folder/key.bai.c4gh"))
+      .await
+      .unwrap()
+      .write_all(&data)
+      .await
+      .unwrap();
   }
 
   pub(crate) async fn with_local_c4gh_storage<F, Fut>(test: F)
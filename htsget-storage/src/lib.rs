@@ -13,7 +13,7 @@ use crate::error::StorageError;
 use crate::error::StorageError::InvalidKey;
 use crate::local::FileStorage;
 #[cfg(feature = "aws")]
-use crate::s3::S3Storage;
+use crate::s3::{ProxySigner, S3Storage};
 use crate::types::{BytesPositionOptions, DataBlock, GetOptions, HeadOptions, RangeUrlOptions};
 #[cfg(feature = "url")]
 use crate::url::UrlStorage;
@@ -34,7 +34,7 @@ use std::fmt::{Debug, Formatter};
 use std::path::Path;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use tokio::io::{AsyncRead, ReadBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
 
 #[cfg(feature = "experimental")]
 pub mod c4gh;
@@ -124,6 +124,10 @@ impl StorageTrait for Storage {
     self.inner.range_url(key, options).await
   }
 
+  async fn inline_range_url(&self, key: &str, options: RangeUrlOptions<'_>) -> Result<Url> {
+    self.inner.inline_range_url(key, options).await
+  }
+
   async fn head(&self, key: &str, options: HeadOptions<'_>) -> Result<u64> {
     self.inner.head(key, options).await
   }
@@ -135,11 +139,13 @@ impl StorageTrait for Storage {
 
 impl Storage {
   #[cfg(feature = "experimental")]
-  /// Wrap an existing storage with C4GH storage
+  /// Wrap an existing storage with C4GH storage. If `plaintext_urls` is set, queries without an
+  /// encryption scheme are also wrapped so that tickets point at the plaintext objects.
   pub async fn from_c4gh_keys(
     keys: Option<&C4GHKeys>,
     encryption_scheme: Option<EncryptionScheme>,
     storage: Storage,
+    plaintext_urls: bool,
   ) -> Result<Storage> {
     match (keys, encryption_scheme) {
       (Some(keys), Some(EncryptionScheme::C4GH)) => Ok(Storage::new(C4GHStorage::new_box(
@@ -150,6 +156,17 @@ impl Storage {
           .map_err(|err| StorageError::InternalError(err.to_string()))?,
         storage.into_inner(),
       ))),
+      (Some(keys), None) if plaintext_urls => Ok(Storage::new(
+        C4GHStorage::new_box(
+          keys
+            .clone()
+            .keys()
+            .await
+            .map_err(|err| StorageError::InternalError(err.to_string()))?,
+          storage.into_inner(),
+        )
+        .with_plaintext_urls(true),
+      )),
       (None, Some(EncryptionScheme::C4GH)) => Err(StorageError::UnsupportedFormat(
         "C4GH keys have not been configured for this id".to_string(),
       )),
@@ -163,7 +180,13 @@ impl Storage {
 
     cfg_if! {
       if #[cfg(feature = "experimental")] {
-        Self::from_c4gh_keys(file.keys(), _query.encryption_scheme(), storage).await
+        Self::from_c4gh_keys(
+          file.keys(),
+          _query.encryption_scheme(),
+          storage,
+          file.plaintext_urls(),
+        )
+        .await
       } else {
         Ok(storage)
       }
@@ -173,18 +196,20 @@ impl Storage {
   /// Create from s3 config.
   #[cfg(feature = "aws")]
   pub async fn from_s3(s3: &storage::s3::S3, _query: &Query) -> Result<Storage> {
-    let storage = Storage::new(
-      S3Storage::new_with_default_config(
-        s3.bucket().to_string(),
-        s3.endpoint().map(str::to_string),
-        s3.path_style(),
-      )
-      .await,
-    );
+    let mut storage = S3Storage::new_with_default_config(
+      s3.bucket().to_string(),
+      s3.endpoint().map(str::to_string),
+      s3.path_style(),
+    )
+    .await;
+    if let (Some(proxy), Some(secret)) = (s3.proxy(), s3.proxy_secret()) {
+      storage = storage.with_proxy(proxy.clone(), ProxySigner::new(secret));
+    }
+    let storage = Storage::new(storage);
 
     cfg_if! {
       if #[cfg(feature = "experimental")] {
-        Self::from_c4gh_keys(s3.keys(), _query.encryption_scheme(), storage).await
+        Self::from_c4gh_keys(s3.keys(), _query.encryption_scheme(), storage, false).await
       } else {
         Ok(storage)
       }
@@ -204,7 +229,7 @@ impl Storage {
 
     cfg_if! {
       if #[cfg(feature = "experimental")] {
-        Self::from_c4gh_keys(url.keys(), _query.encryption_scheme(), storage).await
+        Self::from_c4gh_keys(url.keys(), _query.encryption_scheme(), storage, false).await
       } else {
         Ok(storage)
       }
@@ -232,6 +257,29 @@ pub trait StorageTrait: StorageMiddleware + StorageClone {
   /// Get the size of the object represented by the key.
   async fn head(&self, key: &str, options: HeadOptions<'_>) -> Result<u64>;
 
+  /// Get the url of the object represented by the key using a bytes range, by reading the bytes
+  /// and returning them inline as a data uri. This should only be used for small ranges.
+  async fn inline_range_url(&self, key: &str, options: RangeUrlOptions<'_>) -> Result<Url> {
+    let range = options.range().clone();
+    let length = range
+      .get_end()
+      .unwrap_or(u64::MAX)
+      .saturating_sub(range.get_start().unwrap_or(0));
+
+    let mut data = vec![];
+    self
+      .get(
+        key,
+        GetOptions::new(range.clone(), options.response_headers()),
+      )
+      .await?
+      .take(length)
+      .read_to_end(&mut data)
+      .await?;
+
+    Ok(self.data_url(data, range.class))
+  }
+
   /// Get the url of the object using an inline data uri.
   fn data_url(&self, data: Vec<u8>, class: Option<Class>) -> Url {
     Url::new(format!(
@@ -354,14 +402,18 @@ mod tests {
       Some(&C4GHKeys::from_join_handle(keys)),
       Some(EncryptionScheme::C4GH),
       storage.clone(),
+      false,
     )
     .await;
     assert!(result.is_ok());
 
-    let result = Storage::from_c4gh_keys(None, None, storage.clone()).await;
+    let result = Storage::from_c4gh_keys(None, None, storage.clone(), false).await;
+    assert!(result.is_ok());
+
+    let result = Storage::from_c4gh_keys(None, None, storage.clone(), true).await;
     assert!(result.is_ok());
 
-    let result = Storage::from_c4gh_keys(None, Some(EncryptionScheme::C4GH), storage).await;
+    let result = Storage::from_c4gh_keys(None, Some(EncryptionScheme::C4GH), storage, false).await;
     assert!(matches!(result, Err(StorageError::UnsupportedFormat(_))));
   }
 
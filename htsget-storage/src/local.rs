@@ -152,8 +152,8 @@ pub(crate) mod tests {
 
   use super::*;
   use crate::types::BytesPosition;
+  use crate::{Class, Headers, Url};
   use crate::{GetOptions, RangeUrlOptions, StorageError};
-  use crate::{Headers, Url};
 
   #[tokio::test]
   async fn get_non_existing_key() {
@@ -302,6 +302,24 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn inline_url_of_existing_key_with_specified_range() {
+    with_local_storage(|storage, _| async move {
+      let result = StorageTrait::inline_range_url(
+        &storage,
+        "folder/../key1",
+        RangeUrlOptions::new(
+          BytesPosition::new(Some(1), Some(4), Some(Class::Body)),
+          &Default::default(),
+        ),
+      )
+      .await;
+      let expected = Url::new("data:;base64,YWx1").with_class(Class::Body);
+      assert!(matches!(result, Ok(url) if url == expected));
+    })
+    .await;
+  }
+
   #[tokio::test]
   async fn file_size() {
     with_local_storage(|storage, _| async move {
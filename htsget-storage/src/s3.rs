@@ -6,7 +6,7 @@ use std::io;
 use std::io::ErrorKind::Other;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use aws_config::BehaviorVersion;
@@ -20,7 +20,10 @@ use aws_sdk_s3::types::StorageClass;
 use aws_sdk_s3::Client;
 use bytes::Bytes;
 use futures::Stream;
+use hmac::{Hmac, Mac};
+use http::Uri;
 use pin_project_lite::pin_project;
+use sha2::Sha256;
 use tokio_util::io::StreamReader;
 use tracing::instrument;
 use tracing::{debug, warn};
@@ -41,11 +44,73 @@ pub enum Retrieval {
   Delayed(StorageClass),
 }
 
+/// Signs and verifies the urls of a data endpoint which proxies a bucket. Urls carry an
+/// `expires` unix timestamp and a `signature`, which is a HMAC-SHA256 of the key and the
+/// timestamp, so that only urls returned in tickets can be used to fetch objects.
+#[derive(Clone)]
+pub struct ProxySigner {
+  secret: Vec<u8>,
+}
+
+impl ProxySigner {
+  /// Create a signer from the shared secret.
+  pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+    Self {
+      secret: secret.into(),
+    }
+  }
+
+  /// Get the query string which signs the key until the unix timestamp.
+  pub fn sign_until(&self, key: &str, expires: u64) -> String {
+    format!(
+      "expires={expires}&signature={}",
+      hex::encode(self.mac(key, expires).finalize().into_bytes())
+    )
+  }
+
+  /// Get the query string which signs the key for the presigned request expiry.
+  pub fn sign(&self, key: &str) -> String {
+    self.sign_until(key, Self::now() + S3Storage::PRESIGNED_REQUEST_EXPIRY)
+  }
+
+  /// Check that the signature is valid for the key and that it has not expired.
+  pub fn verify(&self, key: &str, expires: &str, signature: &str) -> bool {
+    let (Ok(expires), Ok(signature)) = (expires.parse::<u64>(), hex::decode(signature)) else {
+      return false;
+    };
+
+    expires >= Self::now() && self.mac(key, expires).verify_slice(&signature).is_ok()
+  }
+
+  fn mac(&self, key: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac =
+      Hmac::<Sha256>::new_from_slice(&self.secret).expect("expected HMAC to accept any key size");
+    mac.update(key.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+  }
+
+  fn now() -> u64 {
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|now| now.as_secs())
+      .unwrap_or_default()
+  }
+}
+
+impl Debug for ProxySigner {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ProxySigner").finish_non_exhaustive()
+  }
+}
+
 /// Implementation for the [StorageTrait] trait utilising data from an S3 bucket.
 #[derive(Debug, Clone)]
 pub struct S3Storage {
   client: Client,
   bucket: String,
+  proxy: Option<(Uri, ProxySigner)>,
 }
 
 impl S3Storage {
@@ -53,7 +118,18 @@ impl S3Storage {
   pub const PRESIGNED_REQUEST_EXPIRY: u64 = 1000;
 
   pub fn new(client: Client, bucket: String) -> Self {
-    S3Storage { client, bucket }
+    S3Storage {
+      client,
+      bucket,
+      proxy: None,
+    }
+  }
+
+  /// Return urls that point at a data endpoint which proxies the bucket, instead of presigned urls.
+  /// The urls are signed with the signer.
+  pub fn with_proxy(mut self, proxy: Uri, signer: ProxySigner) -> Self {
+    self.proxy = Some((proxy, signer));
+    self
   }
 
   pub async fn new_with_default_config(
@@ -256,12 +332,20 @@ impl StorageTrait for S3Storage {
     ))
   }
 
-  /// Return an S3 pre-signed htsget URL. This function does not check that the key exists, so this
-  /// should be checked before calling it.
+  /// Return an S3 pre-signed htsget URL, or a URL of the proxy if it is set. This function does not
+  /// check that the key exists, so this should be checked before calling it.
   #[instrument(level = "trace", skip(self))]
   async fn range_url(&self, key: &str, options: RangeUrlOptions<'_>) -> Result<Url> {
-    let presigned_url = self.s3_presign_url(key, options.range()).await?;
-    let url = options.apply(Url::new(presigned_url));
+    let url = match &self.proxy {
+      Some((proxy, signer)) => format!(
+        "{}/{}?{}",
+        proxy.to_string().trim_end_matches('/'),
+        key,
+        signer.sign(key)
+      ),
+      None => self.s3_presign_url(key, options.range()).await?,
+    };
+    let url = options.apply(Url::new(url));
 
     debug!(calling_from = ?self, key, ?url, "getting url with key {:?}", key);
     Ok(url)
@@ -296,11 +380,12 @@ pub(crate) mod tests {
   use htsget_test::aws_mocks::with_s3_test_server;
 
   use crate::local::tests::create_local_test_files;
-  use crate::s3::S3Storage;
+  use crate::s3::{ProxySigner, S3Storage};
   use crate::types::BytesPosition;
   use crate::Headers;
   use crate::{GetOptions, RangeUrlOptions, StorageTrait};
   use crate::{HeadOptions, StorageError};
+  use http::Uri;
 
   pub(crate) async fn with_aws_s3_storage_fn<F, Fut>(test: F, folder_name: String, base_path: &Path)
   where
@@ -369,6 +454,62 @@ pub(crate) mod tests {
     .await;
   }
 
+  #[tokio::test]
+  async fn proxy_url_with_specified_range() {
+    with_aws_s3_storage(|storage, _| async move {
+      let result = storage
+        .with_proxy(
+          Uri::from_static("https://example.com/data/"),
+          ProxySigner::new("secret"),
+        )
+        .range_url(
+          "key2",
+          RangeUrlOptions::new(
+            BytesPosition::new(Some(7), Some(9), None),
+            &Default::default(),
+          ),
+        )
+        .await
+        .unwrap();
+      let (url, query) = result.url.split_once('?').unwrap();
+      let (expires, signature) = query
+        .strip_prefix("expires=")
+        .and_then(|query| query.split_once("&signature="))
+        .unwrap();
+      assert_eq!(url, "https://example.com/data/key2");
+      assert!(ProxySigner::new("secret").verify("key2", expires, signature));
+      assert_eq!(
+        result.headers,
+        Some(Headers::default().with_header("Range", "bytes=7-8"))
+      );
+    })
+    .await;
+  }
+
+  #[test]
+  fn proxy_signer_rejects_invalid_signatures() {
+    let signer = ProxySigner::new("secret");
+    let expires = ProxySigner::now() + 10;
+    let query = signer.sign_until("key2", expires);
+    let signature = query.split_once("&signature=").unwrap().1;
+
+    assert!(signer.verify("key2", &expires.to_string(), signature));
+    assert!(!signer.verify("key1", &expires.to_string(), signature));
+    assert!(!signer.verify("key2", &(expires + 1).to_string(), signature));
+    assert!(!signer.verify("key2", &expires.to_string(), "invalid"));
+    assert!(!ProxySigner::new("other").verify("key2", &expires.to_string(), signature));
+  }
+
+  #[test]
+  fn proxy_signer_rejects_expired_signatures() {
+    let signer = ProxySigner::new("secret");
+    let expires = ProxySigner::now() - 1;
+    let query = signer.sign_until("key2", expires);
+    let signature = query.split_once("&signature=").unwrap().1;
+
+    assert!(!signer.verify("key2", &expires.to_string(), signature));
+  }
+
   #[tokio::test]
   async fn url_with_specified_range() {
     with_aws_s3_storage(|storage, _| async move {
@@ -1,3 +1,4 @@
+use htsget_config::storage::coalesce::Coalesce;
 use htsget_config::types::{Class, Headers, Url};
 use http::HeaderMap;
 use std::cmp::Ordering;
@@ -196,6 +197,50 @@ impl BytesPosition {
       optimized_ranges
     }
   }
+
+  /// Merge ranges like `merge_all`, and then also merge ranges separated by at most
+  /// `max_gap_bytes`. If `max_urls` is set, the ranges with the smallest gaps between them are
+  /// merged until there are at most `max_urls` ranges.
+  #[instrument(level = "trace", ret)]
+  pub fn coalesce_all(
+    ranges: Vec<BytesPosition>,
+    max_gap_bytes: u64,
+    max_urls: Option<usize>,
+  ) -> Vec<BytesPosition> {
+    let mut coalesced_ranges: Vec<BytesPosition> = Vec::new();
+    for range in Self::merge_all(ranges) {
+      match coalesced_ranges.last_mut() {
+        Some(last) if last.gap(&range) <= max_gap_bytes => {
+          last.merge_with(&range);
+        }
+        _ => coalesced_ranges.push(range),
+      }
+    }
+
+    if let Some(max_urls) = max_urls {
+      while coalesced_ranges.len() > max_urls.max(1) {
+        let index = coalesced_ranges
+          .windows(2)
+          .enumerate()
+          .min_by_key(|(_, pair)| pair[0].gap(&pair[1]))
+          .map(|(index, _)| index)
+          .unwrap_or_default();
+
+        let next = coalesced_ranges.remove(index + 1);
+        coalesced_ranges[index].merge_with(&next);
+      }
+    }
+
+    coalesced_ranges
+  }
+
+  /// The number of bytes between the end of this position and the start of the next one.
+  fn gap(&self, next: &BytesPosition) -> u64 {
+    match (self.end, next.start) {
+      (Some(end), Some(start)) => start.saturating_sub(end),
+      _ => 0,
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -241,11 +286,27 @@ impl<'a> GetOptions<'a> {
 pub struct BytesPositionOptions<'a> {
   pub(crate) positions: Vec<BytesPosition>,
   pub(crate) headers: &'a HeaderMap,
+  pub(crate) coalesce: Option<Coalesce>,
 }
 
 impl<'a> BytesPositionOptions<'a> {
   pub fn new(positions: Vec<BytesPosition>, headers: &'a HeaderMap) -> Self {
-    Self { positions, headers }
+    Self {
+      positions,
+      headers,
+      coalesce: None,
+    }
+  }
+
+  /// Set the policy used to coalesce bytes positions when merging.
+  pub fn with_coalesce(mut self, coalesce: Option<&Coalesce>) -> Self {
+    self.coalesce = coalesce.cloned();
+    self
+  }
+
+  /// Get the coalesce policy.
+  pub fn coalesce(&self) -> Option<&Coalesce> {
+    self.coalesce.as_ref()
   }
 
   /// Get the response headers.
@@ -262,9 +323,16 @@ impl<'a> BytesPositionOptions<'a> {
     self.positions
   }
 
-  /// Merge all bytes positions
+  /// Merge all bytes positions, coalescing them if a coalesce policy is set.
   pub fn merge_all(mut self) -> Self {
-    self.positions = BytesPosition::merge_all(self.positions);
+    self.positions = match &self.coalesce {
+      Some(coalesce) => BytesPosition::coalesce_all(
+        self.positions,
+        coalesce.max_gap_bytes(),
+        coalesce.max_urls(),
+      ),
+      None => BytesPosition::merge_all(self.positions),
+    };
     self
   }
 }
@@ -646,6 +714,72 @@ mod tests {
     assert_eq!(BytesPosition::merge_all(ranges), expected_ranges);
   }
 
+  #[test]
+  fn bytes_position_coalesce_all_max_gap_bytes() {
+    let ranges = vec![
+      BytesPosition::new(Some(0), Some(10), Some(Class::Header)),
+      BytesPosition::new(Some(15), Some(20), Some(Class::Body)),
+      BytesPosition::new(Some(25), Some(30), Some(Class::Body)),
+      BytesPosition::new(Some(100), Some(110), Some(Class::Body)),
+    ];
+
+    assert_eq!(
+      BytesPosition::coalesce_all(ranges, 5, None),
+      vec![
+        BytesPosition::new(Some(0), Some(30), None),
+        BytesPosition::new(Some(100), Some(110), Some(Class::Body)),
+      ]
+    );
+  }
+
+  #[test]
+  fn bytes_position_coalesce_all_max_urls() {
+    let ranges = vec![
+      BytesPosition::new(Some(0), Some(10), None),
+      BytesPosition::new(Some(50), Some(60), None),
+      BytesPosition::new(Some(65), Some(70), None),
+      BytesPosition::new(Some(100), None, None),
+    ];
+
+    assert_eq!(
+      BytesPosition::coalesce_all(ranges.clone(), 0, Some(3)),
+      vec![
+        BytesPosition::new(Some(0), Some(10), None),
+        BytesPosition::new(Some(50), Some(70), None),
+        BytesPosition::new(Some(100), None, None),
+      ]
+    );
+    assert_eq!(
+      BytesPosition::coalesce_all(ranges.clone(), 0, Some(2)),
+      vec![
+        BytesPosition::new(Some(0), Some(10), None),
+        BytesPosition::new(Some(50), None, None),
+      ]
+    );
+    assert_eq!(
+      BytesPosition::coalesce_all(ranges, 0, Some(0)),
+      vec![BytesPosition::new(Some(0), None, None)]
+    );
+  }
+
+  #[test]
+  fn bytes_position_options_merge_all_with_coalesce() {
+    let headers = HeaderMap::default();
+    let options = BytesPositionOptions::new(
+      vec![
+        BytesPosition::new(Some(0), Some(10), None),
+        BytesPosition::new(Some(20), Some(30), None),
+      ],
+      &headers,
+    )
+    .with_coalesce(Some(&Coalesce::new(10, None)));
+
+    assert_eq!(
+      options.merge_all().into_inner(),
+      vec![BytesPosition::new(Some(0), Some(30), None)]
+    );
+  }
+
   #[test]
   fn bytes_position_new() {
     let result = BytesPosition::new(Some(1), Some(2), Some(Class::Header));
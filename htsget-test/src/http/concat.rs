@@ -229,6 +229,32 @@ impl ReadRecords {
 
         self.iterate_records(reader.records()).await
       }
+      Format::Fasta => {
+        // Regions of a sequence don't start at a record, so only check that the blocks decompress.
+        let mut sequence = Vec::new();
+        bgzf::AsyncReader::new(self.merged_bytes.as_slice())
+          .read_to_end(&mut sequence)
+          .await
+          .map_err(TestError::read_record)?;
+        println!("total sequence bytes read: {}", sequence.len());
+
+        Ok(())
+      }
+      Format::Bed | Format::Gff3 | Format::Gtf => {
+        let mut annotations = Vec::new();
+        bgzf::AsyncReader::new(self.merged_bytes.as_slice())
+          .read_to_end(&mut annotations)
+          .await
+          .map_err(TestError::read_record)?;
+
+        let total_records = annotations
+          .split(|byte| *byte == b'\n')
+          .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+          .count();
+        println!("total records read: {}", total_records);
+
+        Ok(())
+      }
     }
   }
 
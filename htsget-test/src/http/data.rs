@@ -0,0 +1,57 @@
+//! Tests for data servers, which serve the files of the data directory.
+//!
+
+use crate::http::{Header, TestRequest, TestServer};
+use crate::util::default_dir_data;
+use http::header::RANGE;
+use http::{HeaderValue, Method, StatusCode};
+
+/// The file used to test data servers, relative to the data directory.
+pub const DATA_SERVER_TEST_FILE: &str = "bam/htsnexus_test_NA12878.bam.bai";
+
+/// Test that the data server returns a whole file, with uri specified as the base of the server.
+pub async fn test_data_server_file_uri<T: TestRequest>(tester: &impl TestServer<T>, uri: &str) {
+  let request = tester
+    .request()
+    .method(Method::GET)
+    .uri(format!("{uri}/{DATA_SERVER_TEST_FILE}"));
+  let response = tester.test_server(request, "".to_string()).await;
+
+  assert!(response.is_success());
+  assert_eq!(
+    response.body,
+    std::fs::read(default_dir_data().join(DATA_SERVER_TEST_FILE)).unwrap()
+  );
+}
+
+/// Test that the data server returns the byte range of a file, with uri specified as the base of
+/// the server.
+pub async fn test_data_server_range_uri<T: TestRequest>(tester: &impl TestServer<T>, uri: &str) {
+  let request = tester
+    .request()
+    .method(Method::GET)
+    .uri(format!("{uri}/{DATA_SERVER_TEST_FILE}"))
+    .insert_header(Header {
+      name: RANGE,
+      value: HeaderValue::from_static("bytes=0-3"),
+    });
+  let response = tester.test_server(request, "".to_string()).await;
+
+  assert_eq!(response.status, StatusCode::PARTIAL_CONTENT.as_u16());
+  assert_eq!(response.body, b"BAI\x01");
+}
+
+/// Test that the data server returns not found for a missing file, with uri specified as the base
+/// of the server.
+pub async fn test_data_server_missing_file_uri<T: TestRequest>(
+  tester: &impl TestServer<T>,
+  uri: &str,
+) {
+  let request = tester
+    .request()
+    .method(Method::GET)
+    .uri(format!("{uri}/bam/missing.bam"));
+  let response = tester.test_server(request, "".to_string()).await;
+
+  assert_eq!(response.status, StatusCode::NOT_FOUND.as_u16());
+}
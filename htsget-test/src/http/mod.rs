@@ -3,6 +3,7 @@
 
 pub mod concat;
 pub mod cors;
+pub mod data;
 pub mod server;
 
 use std::fs;
@@ -15,6 +16,7 @@ use htsget_config::config::advanced::cors::{AllowType, CorsConfig, TaggedAllowTy
 use htsget_config::config::advanced::regex_location::RegexLocation;
 use htsget_config::config::data_server::{DataServerConfig, DataServerEnabled};
 use htsget_config::config::location::{LocationEither, Locations};
+use htsget_config::config::services::{ServiceConfig, Services};
 use htsget_config::config::ticket_server::TicketServerConfig;
 use htsget_config::config::Config;
 use htsget_config::storage::file::File;
@@ -167,6 +169,16 @@ pub fn default_test_config() -> Config {
   default_test_config_params(addr, None, Scheme::Http)
 }
 
+/// Default config with an additional service mounted under the `/cohort` prefix, which uses
+/// the same locations as the top-level service.
+pub fn default_test_config_with_services() -> Config {
+  let config = default_test_config();
+  let locations = Locations::new(config.locations().to_vec());
+  let service = ServiceConfig::new("/cohort".to_string(), locations, None, None).unwrap();
+
+  config.with_services(Services::new(vec![service]).unwrap())
+}
+
 /// Config with tls ticket server, using the current cargo manifest directory.
 pub fn config_with_tls<P: AsRef<Path>>(path: P) -> Config {
   let addr = get_dynamic_addr();
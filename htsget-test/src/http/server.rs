@@ -244,6 +244,62 @@ pub async fn test_service_info<T: TestRequest>(tester: &impl TestServer<T>) {
   test_response_service_info(&response);
 }
 
+/// Test requests to a service mounted under the `/cohort` prefix.
+pub async fn test_services<R, T>(tester: &impl TestServer<T>)
+where
+  T: TestRequest,
+  R: for<'de> Deserialize<'de> + Eq + Debug,
+{
+  test_responses::<R, T>(
+    tester,
+    vec![
+      tester
+        .request()
+        .method(Method::GET)
+        .uri("/cohort/variants/1-vcf/sample1-bcbio-cancer"),
+      post_request_one(tester)
+        .uri("/cohort/variants/2-vcf/sample1-bcbio-cancer")
+        .set_payload("{}"),
+    ],
+    Class::Body,
+  )
+  .await;
+
+  let request = tester
+    .request()
+    .method(Method::GET)
+    .uri("/cohort/variants/service-info");
+  let response = tester
+    .test_server(request, tester.get_expected_path().await)
+    .await;
+
+  test_response_service_info(&response);
+}
+
+/// Test the OpenAPI document.
+pub async fn test_openapi<T: TestRequest>(tester: &impl TestServer<T>) {
+  let request = tester.request().method(Method::GET).uri("/openapi.json");
+  let response = tester
+    .test_server(request, tester.get_expected_path().await)
+    .await;
+
+  assert!(response.is_success());
+  let body = response.deserialize_body::<Value>().unwrap();
+
+  assert!(body["openapi"].is_string());
+  for path in [
+    "/reads/{id}",
+    "/reads/service-info",
+    "/variants/{id}",
+    "/variants/service-info",
+  ] {
+    assert!(body["paths"].get(path).is_some());
+  }
+  for schema in ["PostRequest", "JsonResponse", "WrappedHtsGetError"] {
+    assert!(body["components"]["schemas"].get(schema).is_some());
+  }
+}
+
 /// Test requests that should result in errors.
 pub async fn test_errors<T>(tester: &impl TestServer<T>)
 where